@@ -1,16 +1,30 @@
+use anyhow::Context;
 use std::{
     collections::{HashMap, VecDeque},
     time::{Duration, Instant},
 };
 use strum::*;
 
-use crate::bar::Bar;
+use crate::balance;
+use crate::bar::{Bar, SPEED_UNIT};
+use crate::challenge::Challenge;
+use crate::controls::Action;
+use crate::dev;
+use crate::events::{Event, EventLog};
 use crate::float::Float;
+use crate::metrics::{self, Counters};
 use crate::opts::Opts;
 use crate::prestige::{Prestige, PrestigeUpgrade};
+use crate::random_events::{RandomEvents, Rng};
+use crate::research::Research;
+use crate::share;
+use crate::strategy::{GameView, Purchase, Strategy};
 use crate::ui::{self, Ui, UiState};
 use crate::upgrade::{GlobalUpgrade, Upgrade};
 
+/// How many steps catching up can take, however long the gap.
+const MAX_STEPS_PER_ADVANCE: u32 = 1000;
+
 pub(crate) struct App {
     pub(crate) bars: VecDeque<Bar>,
     pub(crate) tick: Instant,
@@ -22,7 +36,43 @@ pub(crate) struct App {
     pub(crate) opts: Opts,
     pub(crate) ui: Ui,
     pub(crate) prestige: Prestige,
+    pub(crate) research: Research,
+    pub(crate) random_events: RandomEvents,
     pub(crate) last_automation: HashMap<GlobalUpgrade, Instant>,
+    pub(crate) clock: Clock,
+    pub(crate) events: EventLog,
+    pub(crate) counters: Counters,
+    pub(crate) last_metrics: Option<Instant>,
+}
+
+/// Maps the time passed to `on_tick` to game time, which the dev console
+/// can skip ahead or run faster.
+#[derive(Copy, Clone)]
+pub(crate) struct Clock {
+    real: Instant,
+    game: Instant,
+    speed: f64,
+    last_real: Instant,
+}
+
+impl Clock {
+    pub(crate) fn new(now: Instant) -> Self {
+        Self {
+            real: now,
+            game: now,
+            speed: 1.,
+            last_real: now,
+        }
+    }
+
+    fn game_time(&self, real: Instant) -> Instant {
+        let elapsed = real.saturating_duration_since(self.real);
+        if self.speed == 1. {
+            self.game + elapsed
+        } else {
+            self.game + elapsed.mul_f64(self.speed)
+        }
+    }
 }
 
 struct UpgradeCost {
@@ -31,58 +81,129 @@ struct UpgradeCost {
 }
 
 impl App {
-    pub(crate) fn load(opts: Opts, now: Instant) -> App {
-        let save = std::path::PathBuf::from(&opts.save_file);
-        let mut app = if !save.exists() {
-            App::new(opts)
-        } else {
-            let contents = std::fs::read_to_string(&opts.save_file).unwrap();
-            let save: crate::save::App = serde_json::from_str(&contents).unwrap();
-            save.into_game(opts, now)
+    /// Loads the save file, or starts a new game if there is none. A save
+    /// with problems is only loaded if `--repair` is passed, after keeping
+    /// a backup of it.
+    pub(crate) fn load(opts: Opts, now: Instant) -> anyhow::Result<App> {
+        let path = match &opts.save_file {
+            Some(path) if std::path::Path::new(path).exists() => path.clone(),
+            _ => {
+                log::info!("No save to load, starting a new game");
+                return Ok(App::new(opts));
+            }
         };
+        log::info!("Loading {path}");
+        let mut save = crate::save::App::read(&path)?;
+
+        let problems = save.validate();
+        if !problems.is_empty() {
+            if !opts.repair {
+                anyhow::bail!(
+                    "{path} has problems:\n{}\nRun with --repair to fix them",
+                    problem_list(&problems)
+                );
+            }
+            let backup = format!("{path}.bak");
+            std::fs::copy(&path, &backup)
+                .with_context(|| format!("Could not back up {path} to {backup}"))?;
+            for problem in save.repair() {
+                log::warn!("Repaired {problem}");
+            }
+        }
+
+        Ok(App::from_save(save, opts, now))
+    }
+
+    /// Turns a save into a game, fixing up what isn't saved.
+    pub(crate) fn from_save(save: crate::save::App, opts: Opts, now: Instant) -> App {
+        let mut app = save.into_game(opts, now);
 
         let global_speed_levels = app.get_global_upgrade_u(GlobalUpgrade::Speed);
         for bar in &mut app.bars {
-            bar.adjust_speed_multiplier(global_speed_levels);
+            bar.adjust_speed_multiplier(global_speed_levels, &app.research);
         }
 
         app
     }
 
-    pub(crate) fn save(&self) {
-        let save = crate::save::App::from_game(self);
-        std::fs::write(
-            &self.opts.save_file,
-            serde_json::to_string_pretty(&save).unwrap(),
-        )
-        .unwrap();
+    /// Writes the save file, logging whether it worked.
+    pub(crate) fn save(&mut self) -> anyhow::Result<()> {
+        let Some(save_file) = &self.opts.save_file else {
+            return Ok(());
+        };
+        if self.opts.no_save {
+            return Ok(());
+        }
+        let res = crate::save::App::from_game(self).write(save_file);
+        self.last_save = Some(self.tick);
+        self.events.push(match &res {
+            Ok(()) => Event::Saved,
+            Err(err) => Event::SaveFailed(format!("{err:#}")),
+        });
+        res
     }
 
     fn new(opts: Opts) -> App {
+        let now = Instant::now();
         App {
             bars: VecDeque::new(),
-            tick: Instant::now(),
+            tick: now,
             last_bar_spawn: None,
-            bars_to_spawn: 4,
-            ui: Ui::new(opts.start_state),
+            bars_to_spawn: balance::get().app.initial_bars,
+            ui: Ui::new(opts.start_state, opts.dev),
             last_bar_number: 0,
             global_upgrades: GlobalUpgrade::iter().map(|g| (g, 0)).collect(),
             last_save: None,
             opts,
             prestige: Prestige::new(),
+            research: Research::new(),
+            random_events: RandomEvents::new(Rng::from_time(), now),
             last_automation: HashMap::new(),
+            clock: Clock::new(now),
+            events: EventLog::default(),
+            counters: Counters::default(),
+            last_metrics: None,
         }
     }
 
+    #[cfg(test)]
+    pub(crate) fn for_test() -> App {
+        use structopt::StructOpt;
+        let save_file = std::env::temp_dir().join("antsy-test-save.json");
+        App::new(Opts::from_iter([
+            "antsy",
+            "--save-file",
+            save_file.to_str().unwrap(),
+            "--no-random-events",
+        ]))
+    }
+
     pub(crate) fn prestige(&mut self) {
-        self.save();
+        self.start_run(None);
+    }
 
-        self.prestige.prestige(self.bars.len());
+    /// Prestiges if possible, then starts over under `challenge`.
+    fn start_run(&mut self, challenge: Option<Challenge>) {
+        let _ = self.save();
+
+        if self.prestige.can_prestige(self.bars.len()) {
+            let points = self.prestige.claimable_prestige(self.bars.len());
+            let completed = self.prestige.prestige(self.bars.len());
+            self.events.push(Event::Prestiged { points });
+            if let Some(challenge) = completed {
+                self.events.push(Event::ChallengeCompleted { challenge });
+            }
+        }
+        self.prestige.challenge = challenge;
+        self.random_events.end(self.tick);
+        if let Some(challenge) = challenge {
+            self.events.push(Event::ChallengeStarted { challenge });
+        }
 
         self.bars = VecDeque::new();
         self.last_bar_spawn = None;
         self.last_bar_number = 0;
-        self.bars_to_spawn = 4;
+        self.bars_to_spawn = balance::get().app.initial_bars;
         self.global_upgrades = GlobalUpgrade::iter().map(|g| (g, 0)).collect();
         self.ui.to_normal();
     }
@@ -94,24 +215,43 @@ impl App {
         self.global_upgrades[&upgrade]
     }
 
-    fn spawn_bar(&mut self) {
+    pub(crate) fn spawn_bar(&mut self) {
         self.last_bar_number += 1;
         self.bars.push_front(Bar::new(self.last_bar_number));
+        self.events.push(Event::BarSpawned {
+            number: self.last_bar_number,
+        });
         if let UiState::Normal(ui::Normal {
             highlight: ui::normal::Highlight::Bar { row, upgrade: _ },
+            ..
         }) = &mut self.ui.state
         {
             *row += 1;
         }
     }
 
+    /// What the next level of `upgrade` costs, or `None` if the current
+    /// challenge or missing research doesn't allow buying it.
+    pub(crate) fn global_upgrade_cost(&self, upgrade: GlobalUpgrade) -> Option<Float> {
+        let cost =
+            upgrade.cost(self.global_upgrades[&upgrade]) * self.random_events.cost_multiplier();
+        if !self.research.allows_global_upgrade(upgrade) {
+            None
+        } else if self.prestige.in_challenge(Challenge::NoGlobalUpgrades) {
+            (upgrade == GlobalUpgrade::ProgressBars).then_some(cost)
+        } else if self.prestige.has_reward(Challenge::NoGlobalUpgrades) {
+            Some(cost * balance::get().challenge.global_upgrade_discount)
+        } else {
+            Some(cost)
+        }
+    }
+
     fn global_upgrade_price(&self, upgrade: GlobalUpgrade) -> Option<Float> {
         if self.bars.is_empty() {
             return None;
         }
 
-        let level = self.global_upgrades[&upgrade];
-        let cost = upgrade.cost(level);
+        let cost = self.global_upgrade_cost(upgrade)?;
         if self.bars[self.bars.len() - 1].gathered >= cost {
             Some(cost)
         } else {
@@ -123,8 +263,25 @@ impl App {
         self.global_upgrade_price(upgrade).is_some()
     }
 
-    fn upgrade_price(&self, row: usize, upgrade: Upgrade) -> Option<UpgradeCost> {
+    /// What the next level of `upgrade` costs the bar in `row`, or `None`
+    /// if the current challenge or missing research doesn't allow buying it.
+    pub(crate) fn upgrade_cost(&self, row: usize, upgrade: Upgrade) -> Option<Float> {
         let cost = self.bars[row].upgrade_cost(upgrade);
+        if !self.research.allows_upgrade(upgrade) {
+            None
+        } else if upgrade != Upgrade::Speed {
+            Some(cost)
+        } else if self.prestige.in_challenge(Challenge::NoSpeedUpgrades) {
+            None
+        } else if self.prestige.has_reward(Challenge::NoSpeedUpgrades) {
+            Some(cost * balance::get().challenge.speed_upgrade_discount)
+        } else {
+            Some(cost)
+        }
+    }
+
+    fn upgrade_price(&self, row: usize, upgrade: Upgrade) -> Option<UpgradeCost> {
+        let cost = self.upgrade_cost(row, upgrade)?;
         let target = row as i64 - upgrade.cost_target();
         if target >= 0 && self.bars[target as usize].gathered >= cost {
             Some(UpgradeCost {
@@ -158,24 +315,164 @@ impl App {
         }
     }
 
-    pub(crate) fn try_purchase_highlighted_upgrade(&mut self) {
-        if let Some(highlight) = self.ui.normal_highlight() {
-            self.try_purchase_upgrade(highlight);
+    /// Applies everything but `Quit`, which is up to the caller.
+    pub(crate) fn apply_action(&mut self, action: Action, strategy: &mut dyn Strategy) {
+        match action {
+            Action::Quit | Action::Noop => (),
+            Action::PurchaseUpgrade(purchase) => {
+                self.try_purchase_upgrade(purchase);
+            }
+            Action::UpgradeAny => {
+                self.apply_strategy(strategy);
+            }
+            Action::AdjustTransfer { row, up } => self.adjust_transfer(row, up),
+            Action::ChargeBar { row } => self.charge_bar(row),
+            Action::Prestige => {
+                if self.prestige.can_prestige(self.bars.len()) {
+                    self.prestige();
+                }
+            }
+            Action::UnlockResearch(node) => {
+                if self.research.try_unlock(node) {
+                    self.events.push(Event::ResearchUnlocked { node });
+                    let global_speed_levels = self.get_global_upgrade_u(GlobalUpgrade::Speed);
+                    for bar in &mut self.bars {
+                        bar.adjust_speed_multiplier(global_speed_levels, &self.research);
+                    }
+                }
+            }
+            Action::ToggleChallenge(challenge) => {
+                // Abandoning keeps the bars earned under the challenge, only
+                // its reward is lost. Starting one prestiges, so it waits
+                // until the run is worth prestige points.
+                if self.prestige.in_challenge(challenge) {
+                    self.prestige.challenge = None;
+                    self.events.push(Event::ChallengeAbandoned { challenge });
+                } else if self.prestige.can_prestige(self.bars.len()) {
+                    self.start_run(Some(challenge));
+                }
+            }
+            Action::PurchasePrestigeUpgrade(upgrade) => {
+                if self.prestige.try_purchase_upgrade(upgrade) {
+                    self.counters.prestige_purchases += 1;
+                    self.events.push(Event::PrestigeUpgradePurchased {
+                        upgrade,
+                        level: self.prestige.level(upgrade),
+                    });
+                }
+            }
+            Action::Export => {
+                let exported = share::export(&crate::save::App::from_game(self));
+                // Only written when it's too long to copy from the screen
+                let too_long = !crate::render::export_fits(self.ui.size, &exported);
+                let save_file = self.opts.save_file.as_deref();
+                let save_file = save_file.filter(|_| !self.opts.no_save && too_long);
+                let written = save_file.map(|save_file| {
+                    let path = share::export_path(save_file);
+                    std::fs::write(&path, &exported)
+                        .with_context(|| format!("Could not write {path}"))
+                        .map(|()| path)
+                });
+                if let UiState::Share(share) = &mut self.ui.state {
+                    share.exported = Some(exported);
+                    share.export_file = None;
+                    share.message = None;
+                    match written {
+                        Some(Ok(path)) => share.export_file = Some(path),
+                        Some(Err(err)) => share.message = Some(format!("{err:#}")),
+                        None => (),
+                    }
+                }
+            }
+            Action::DevCommand(command) => {
+                let output = match dev::run(self, &command) {
+                    Ok(output) => output,
+                    Err(err) => format!("{err:#}"),
+                };
+                if let Some(console) = &mut self.ui.console {
+                    console.output = output;
+                }
+            }
+            Action::Import(code) => match share::import(&code) {
+                Ok(save) => {
+                    let (clock, counters) = (self.clock, self.counters);
+                    *self = App::from_save(save, self.opts.clone(), self.tick);
+                    self.clock = clock;
+                    self.counters = counters;
+                    self.ui.to_normal();
+                    let _ = self.save();
+                }
+                Err(err) => {
+                    if let UiState::Share(share) = &mut self.ui.state {
+                        share.message = Some(format!("{err:#}"));
+                    }
+                }
+            },
+        }
+    }
+
+    /// Moves the transfer ratio of the bar in `row` one step, keeping it
+    /// between 0 and the balance's maximum.
+    fn adjust_transfer(&mut self, row: usize, up: bool) {
+        let balance = &balance::get().bar;
+        if self.prestige.in_challenge(Challenge::NoTransfer) {
+            return;
+        }
+        let Some(bar) = self.bars.get_mut(row) else {
+            return;
+        };
+        let steps = (bar.transfer_ratio.0 / balance.transfer_ratio_step).round();
+        let steps = if up { steps + 1. } else { steps - 1. };
+        bar.transfer_ratio =
+            Float((steps * balance.transfer_ratio_step).clamp(0., balance.max_transfer_ratio));
+    }
+
+    fn charge_bar(&mut self, row: usize) {
+        let now = self.tick;
+        let Some(bar) = self.bars.get_mut(row) else {
+            return;
+        };
+        if bar.try_charge(now) {
+            self.events.push(Event::BarCharged {
+                number: bar.number,
+                boost: bar
+                    .boost_until
+                    .map_or(Duration::ZERO, |until| until.saturating_duration_since(now)),
+            });
         }
     }
 
-    fn try_purchase_upgrade(&mut self, highlight: ui::normal::Highlight) -> bool {
-        match highlight {
-            ui::normal::Highlight::None => {}
-            ui::normal::Highlight::Bar { upgrade, row } => {
+    fn try_purchase_upgrade(&mut self, purchase: Purchase) -> bool {
+        let purchased = self.purchase(purchase);
+        if purchased {
+            self.events.push(match purchase {
+                Purchase::Bar { upgrade, row } => Event::UpgradePurchased {
+                    number: self.bars[row].number,
+                    upgrade,
+                    level: self.bars[row].get_upgrade_u(upgrade),
+                },
+                Purchase::Global { upgrade } => Event::GlobalUpgradePurchased {
+                    upgrade,
+                    level: self.get_global_upgrade_u(upgrade),
+                    automated: false,
+                },
+            });
+        }
+        purchased
+    }
+
+    fn purchase(&mut self, purchase: Purchase) -> bool {
+        match purchase {
+            Purchase::Bar { upgrade, row } => {
                 if let Some(upgrade_cost) = self.upgrade_price(row, upgrade) {
                     let global_speed_levels = self.get_global_upgrade_u(GlobalUpgrade::Speed);
-                    self.bars[row].inc_upgrade(upgrade, global_speed_levels);
+                    self.bars[row].inc_upgrade(upgrade, global_speed_levels, &self.research);
                     self.bars[upgrade_cost.target].gathered -= upgrade_cost.cost;
+                    self.counters.bar_purchases += 1;
                     return true;
                 }
             }
-            ui::normal::Highlight::Global { upgrade } => {
+            Purchase::Global { upgrade } => {
                 if let Some(upgrade_cost) = self.global_upgrade_price(upgrade) {
                     *self
                         .global_upgrades
@@ -184,14 +481,15 @@ impl App {
                     let last_i = self.bars.len() - 1;
                     self.bars[last_i].gathered -= upgrade_cost;
                     if let GlobalUpgrade::ProgressBars = upgrade {
-                        self.bars_to_spawn += 2;
+                        self.bars_to_spawn += balance::get().app.bars_per_progress_bars_upgrade;
                     }
                     if let GlobalUpgrade::Speed = upgrade {
                         let global_speed_levels = self.get_global_upgrade_u(GlobalUpgrade::Speed);
                         for bar in &mut self.bars {
-                            bar.adjust_speed_multiplier(global_speed_levels);
+                            bar.adjust_speed_multiplier(global_speed_levels, &self.research);
                         }
                     }
+                    self.counters.global_purchases += 1;
                     return true;
                 }
             }
@@ -199,95 +497,200 @@ impl App {
         false
     }
 
-    pub(crate) fn purchase_any_upgrade(&mut self) {
-        for upgrade in GlobalUpgrade::upgrade_preference_order() {
-            if self.try_purchase_upgrade(ui::normal::Highlight::Global { upgrade }) {
-                return;
-            }
-        }
-        let bar_len = self.bars.len();
-        for upgrade in Upgrade::upgrade_preference_order() {
-            for row in (0..bar_len).rev() {
-                if self.try_purchase_upgrade(ui::normal::Highlight::Bar { upgrade, row }) {
-                    return;
-                }
-            }
-        }
+    /// Lets `strategy` look at the current state and attempts its
+    /// purchases in order. Returns how many went through.
+    pub(crate) fn apply_strategy(&mut self, strategy: &mut dyn Strategy) -> usize {
+        let purchases = strategy.decide(&GameView::new(self));
+        purchases
+            .into_iter()
+            .filter(|purchase| self.try_purchase_upgrade(*purchase))
+            .count()
     }
 
     fn speed_base(&self) -> Float {
         Float(self.opts.speed_base)
     }
 
+    /// Advances the game to `now`, in steps of at most `SPEED_UNIT` so
+    /// that the game runs at the same speed no matter how often it ticks,
+    /// and catches up on any time the process was suspended. Long gaps are
+    /// played in at most `MAX_STEPS_PER_ADVANCE` longer steps, completing
+    /// bars in bulk, so that catching up doesn't hang the game.
     pub(crate) fn on_tick(&mut self, now: Instant) {
+        self.clock.last_real = now;
+        self.advance_to(self.clock.game_time(now));
+    }
+
+    fn advance_to(&mut self, now: Instant) {
+        let step = std::cmp::max(
+            SPEED_UNIT,
+            now.saturating_duration_since(self.tick) / MAX_STEPS_PER_ADVANCE,
+        );
+        while self.tick < now {
+            let dt = std::cmp::min(now - self.tick, step);
+            self.step(self.tick + dt, dt);
+        }
+    }
+
+    /// Immediately plays `duration` of game time.
+    pub(crate) fn timewarp(&mut self, duration: Duration) {
+        self.clock.game += duration;
+        self.advance_to(self.clock.game_time(self.clock.last_real));
+    }
+
+    /// Makes game time pass `speed` times as fast as real time.
+    pub(crate) fn set_speed(&mut self, speed: f64) {
+        let real = self.clock.last_real;
+        self.clock = Clock {
+            real,
+            game: self.clock.game_time(real),
+            speed,
+            last_real: real,
+        };
+    }
+
+    /// How long to wait between spawning bars.
+    fn spawn_interval(&self) -> Duration {
+        let balance = &balance::get().challenge;
+        if self.prestige.in_challenge(Challenge::SlowSpawns) {
+            balance.slow_spawn_interval()
+        } else if self.prestige.has_reward(Challenge::SlowSpawns) {
+            balance::get()
+                .app
+                .bar_spawn_interval()
+                .mul_f64(balance.spawn_interval_factor)
+        } else {
+            balance::get().app.bar_spawn_interval()
+        }
+    }
+
+    /// The ratio of its gathered value the bar in `row` passes on, which
+    /// is 0 during the no transfer challenge.
+    pub(crate) fn transfer_ratio(&self, row: usize) -> Float {
+        if self.prestige.in_challenge(Challenge::NoTransfer) {
+            Float(0.)
+        } else {
+            self.bars[row].transfer_ratio
+        }
+    }
+
+    fn step(&mut self, now: Instant, dt: Duration) {
         self.tick = now;
 
+        let spawn_interval = self.spawn_interval();
         if self.bars_to_spawn > 0
-            && (self.last_bar_spawn.map_or(true, |last_bar_spawn| {
-                now - last_bar_spawn >= Duration::from_secs(1)
-            }))
+            && (self
+                .last_bar_spawn
+                .is_none_or(|last_bar_spawn| now - last_bar_spawn >= spawn_interval))
         {
             self.spawn_bar();
             self.bars_to_spawn -= 1;
             self.last_bar_spawn = Some(self.tick);
         }
 
+        let bar_numbers: Vec<_> = self.bars.iter().map(|bar| bar.number).collect();
+        let enabled = !self.opts.no_random_events;
+        if let Some(event) = self.random_events.tick(now, &bar_numbers, enabled) {
+            self.events.push(Event::RandomEventStarted { event });
+        }
+
         for i in 0..self.bars.len() {
             let read_bar = self.bars.get(i).unwrap().clone();
-            let speed_base = self.speed_base();
+            let speed_base = self.speed_base() * self.random_events.speed_multiplier();
             let (bar, next_bars) = self.bars.make_contiguous().split_at_mut(i + 1);
-            let done = bar[bar.len() - 1].inc(
+            let completions = bar[bar.len() - 1].inc(
                 speed_base,
                 self.global_upgrades[&GlobalUpgrade::Speed],
                 self.global_upgrades[&GlobalUpgrade::ExpGain],
                 self.global_upgrades[&GlobalUpgrade::ExpBoost],
                 &self.prestige,
+                &self.research,
                 now,
+                dt,
                 next_bars.get_mut(0),
+                &mut self.events,
             );
-            if done {
-                let gain = read_bar.gain(self);
-                self.bars[i].gathered += gain;
+            self.counters.completions += completions as u64;
+            self.research.points += Research::produced(
+                read_bar.level,
+                completions,
+                self.global_upgrades[&GlobalUpgrade::ResearchGain],
+            );
+            if completions > 0 {
+                // Only the first completion can be golden
+                let first_gain = read_bar.gain(self);
+                let base_gain = read_bar.base_gain(self);
+                let gain = first_gain + base_gain * (completions - 1) as f64;
 
                 if i + 1 < self.bars.len() {
-                    let mut transfer_ratio = self.bars[i].transfer_ratio;
+                    let mut transfer_ratio = self.transfer_ratio(i);
 
-                    if self.bars[i + 1].gathered < self.bars[i].gathered {
-                        transfer_ratio += Float(0.01)
+                    if !self.prestige.in_challenge(Challenge::NoTransfer)
+                        && self.bars[i + 1].gathered < self.bars[i].gathered
+                    {
+                        transfer_ratio += Float(balance::get().prestige.transfer_extra_value_ratio)
                             * self.prestige.level_f(PrestigeUpgrade::TransferExtraValue);
                     }
 
-                    let transferred = self.bars[i].gathered * transfer_ratio;
-                    let gained = read_bar.gain(self) - transferred;
+                    let gathered = self.bars[i].gathered;
+                    let first_transferred =
+                        transferred_over_completions(gathered, first_gain, transfer_ratio, 1);
+                    let transferred = first_transferred
+                        + transferred_over_completions(
+                            gathered + first_gain - first_transferred,
+                            base_gain,
+                            transfer_ratio,
+                            completions - 1,
+                        );
+                    self.bars[i].gathered += gain;
                     self.bars[i + 1].gathered += transferred;
                     self.bars[i].gathered -= transferred;
                     self.bars[i].last_completion = Some(Completion {
-                        gain: gained,
+                        gain: gain - transferred,
                         transferred: Some(transferred),
                         tick: now,
                     });
                 } else {
+                    self.bars[i].gathered += gain;
                     self.bars[i].last_completion = Some(Completion {
-                        gain: read_bar.gain(self),
+                        gain,
                         transferred: None,
                         tick: now,
                     });
                 }
+                self.random_events.completed(read_bar.number, now);
             }
         }
 
         self.auto_purchase(now);
+    }
 
-        match self.last_save {
-            None => self.save(),
-            Some(last_save) => {
-                if self.tick - last_save > Duration::from_secs(30) {
-                    self.save()
-                }
-            }
+    pub(crate) fn autosave(&mut self) {
+        if self
+            .last_save
+            .is_none_or(|last_save| self.tick - last_save > Duration::from_secs(30))
+        {
+            let _ = self.save();
         }
     }
 
+    /// Rewrites the `--metrics-file` every `metrics::WRITE_INTERVAL`.
+    pub(crate) fn write_metrics(&mut self) {
+        let Some(path) = &self.opts.metrics_file else {
+            return;
+        };
+        if self
+            .last_metrics
+            .is_some_and(|last| self.tick - last < metrics::WRITE_INTERVAL)
+        {
+            return;
+        }
+        if let Err(err) = metrics::write(path, self) {
+            log::warn!("{err:#}");
+        }
+        self.last_metrics = Some(self.tick);
+    }
+
     fn auto_purchase(&mut self, now: Instant) {
         const PAIRS: [(PrestigeUpgrade, GlobalUpgrade); 5] = [
             (PrestigeUpgrade::AutomateGlobalSpeed, GlobalUpgrade::Speed),
@@ -304,7 +707,13 @@ impl App {
                 let automation_interval = std::cmp::max(1, automation_interval);
                 let automation_interval = Duration::from_millis(automation_interval);
                 if now < last_automation + automation_interval {
-                    self.try_purchase_upgrade(ui::normal::Highlight::Global { upgrade: global });
+                    if self.purchase(Purchase::Global { upgrade: global }) {
+                        self.events.push(Event::GlobalUpgradePurchased {
+                            upgrade: global,
+                            level: self.get_global_upgrade_u(global),
+                            automated: true,
+                        });
+                    }
                     *self.last_automation.entry(global).or_insert(now) = now;
                 }
             }
@@ -312,9 +721,436 @@ impl App {
     }
 }
 
+pub(crate) fn problem_list(problems: &[crate::save::Problem]) -> String {
+    problems
+        .iter()
+        .map(|problem| format!("  {problem}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// How much is transferred to the next bar over `completions` completions,
+/// where each adds `gain` to `gathered` and then transfers `ratio` of it.
+fn transferred_over_completions(
+    gathered: Float,
+    gain: Float,
+    ratio: Float,
+    completions: usize,
+) -> Float {
+    if completions == 1 || ratio == 0. {
+        return (gathered + gain) * ratio;
+    }
+    // Each completion maps gathered to (gathered + gain) * (1 - ratio),
+    // which converges towards a fixed point.
+    let keep = Float(1.) - ratio;
+    let fixed_point = Float(keep.0 * gain.0 / ratio.0);
+    let kept = fixed_point + (gathered - fixed_point) * keep.powf(completions as f64);
+    gathered + gain * completions as f64 - kept
+}
+
 #[derive(Copy, Clone)]
 pub(crate) struct Completion {
     pub(crate) gain: Float,
     pub(crate) transferred: Option<Float>,
     pub(crate) tick: Instant,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::research::ResearchNode;
+    use proptest::prelude::*;
+    use proptest::strategy::Strategy as _;
+
+    fn app_with_bars(gathered: &[f64]) -> App {
+        let mut app = App::for_test();
+        app.bars_to_spawn = 0;
+        for (i, value) in gathered.iter().enumerate() {
+            let mut bar = Bar::new(gathered.len() - i);
+            bar.gathered = Float(*value);
+            app.bars.push_back(bar);
+        }
+        app.last_bar_number = gathered.len();
+        app
+    }
+
+    #[test]
+    fn bar_upgrade_is_paid_by_cost_target() {
+        let mut app = app_with_bars(&[0., 250., 0.]);
+        let purchase = Purchase::Bar {
+            upgrade: Upgrade::Double,
+            row: 2,
+        };
+        assert!(app.try_purchase_upgrade(purchase));
+        assert_eq!(app.bars[2].get_upgrade_u(Upgrade::Double), 1);
+        assert_eq!(app.bars[1].gathered, Float(50.));
+        assert_eq!(app.bars[2].gathered, Float(0.));
+        assert!(!app.try_purchase_upgrade(purchase));
+    }
+
+    #[test]
+    fn bar_upgrade_without_cost_target_bar_fails() {
+        let mut app = app_with_bars(&[1e9, 1e9]);
+        assert!(!app.try_purchase_upgrade(Purchase::Bar {
+            upgrade: Upgrade::Triple,
+            row: 1,
+        }));
+        assert_eq!(app.bars[0].gathered, Float(1e9));
+    }
+
+    #[test]
+    fn global_upgrade_is_paid_by_last_bar() {
+        let mut app = app_with_bars(&[100., 30.]);
+        assert!(app.try_purchase_upgrade(Purchase::Global {
+            upgrade: GlobalUpgrade::ProgressBars,
+        }));
+        assert_eq!(app.bars[1].gathered, Float(8.));
+        assert_eq!(app.bars[0].gathered, Float(100.));
+        assert_eq!(app.bars_to_spawn, 2);
+        assert_eq!(app.get_global_upgrade_u(GlobalUpgrade::ProgressBars), 1);
+    }
+
+    #[test]
+    fn challenge_locks_upgrades_until_completed() {
+        let mut app = app_with_bars(&[1e9, 1e9]);
+        app.prestige.challenge = Some(Challenge::NoSpeedUpgrades);
+        assert_eq!(app.upgrade_cost(0, Upgrade::Speed), None);
+        assert!(!app.can_afford(0, Upgrade::Speed));
+        assert!(app.can_afford(0, Upgrade::Gain));
+
+        app.prestige.challenge = None;
+        app.prestige
+            .completed_challenges
+            .insert(Challenge::NoSpeedUpgrades);
+        assert_eq!(
+            app.upgrade_cost(0, Upgrade::Speed),
+            Some(app.bars[0].upgrade_cost(Upgrade::Speed) * 0.9)
+        );
+    }
+
+    #[test]
+    fn challenges_only_start_when_prestiging() {
+        let mut app = app_with_bars(&[1.; 3]);
+        app.opts.no_save = true;
+        app.apply_action(
+            Action::ToggleChallenge(Challenge::NoTransfer),
+            &mut crate::strategy::Greedy,
+        );
+        assert_eq!(app.bars.len(), 3);
+        assert_eq!(app.prestige.challenge, None);
+    }
+
+    #[test]
+    fn exports_are_written_only_when_too_long_to_show() {
+        let mut app = App::for_test();
+        let save_file = std::env::temp_dir().join("antsy-test-export.json");
+        app.opts.save_file = Some(save_file.to_str().unwrap().to_owned());
+        let path = share::export_path(app.opts.save_file.as_deref().unwrap());
+        let _ = std::fs::remove_file(&path);
+        app.ui.to_share();
+        let export = |app: &mut App| {
+            app.apply_action(Action::Export, &mut crate::strategy::Greedy);
+            match &app.ui.state {
+                UiState::Share(share) => share.export_file.clone(),
+                _ => panic!("Left the share screen"),
+            }
+        };
+
+        app.ui.size = tui::layout::Rect::new(0, 0, 240, 60);
+        assert_eq!(export(&mut app), None);
+        app.ui.size = tui::layout::Rect::new(0, 0, 20, 8);
+        app.opts.no_save = true;
+        assert_eq!(export(&mut app), None);
+        assert!(!std::path::Path::new(&path).exists());
+
+        app.opts.no_save = false;
+        assert_eq!(export(&mut app), Some(path.clone()));
+        assert!(std::path::Path::new(&path).exists());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn prestiging_during_a_challenge_completes_it() {
+        let mut app = app_with_bars(&[1.; 10]);
+        app.opts.no_save = true;
+        let challenge = Challenge::NoTransfer;
+        app.apply_action(
+            Action::ToggleChallenge(challenge),
+            &mut crate::strategy::Greedy,
+        );
+        assert!(app.bars.is_empty());
+        assert_eq!(app.prestige.current, Float(1.));
+        assert_eq!(
+            app.events.latest(1).next(),
+            Some(&Event::ChallengeStarted { challenge })
+        );
+
+        app.bars = app_with_bars(&[1.; 10]).bars;
+        app.adjust_transfer(0, true);
+        assert_eq!(app.transfer_ratio(0), Float(0.));
+        app.apply_action(Action::Prestige, &mut crate::strategy::Greedy);
+        assert_eq!(
+            app.events.latest(1).next(),
+            Some(&Event::ChallengeCompleted { challenge })
+        );
+        assert_eq!(app.prestige.challenge, None);
+        assert!(app.prestige.has_reward(challenge));
+    }
+
+    #[test]
+    fn purchases_and_saves_are_logged() {
+        let mut app = app_with_bars(&[0., 250., 0.]);
+        app.spawn_bar();
+        app.try_purchase_upgrade(Purchase::Bar {
+            upgrade: Upgrade::Double,
+            row: 3,
+        });
+        app.opts.no_save = true;
+        app.save().unwrap();
+        let events: Vec<_> = app.events.latest(usize::MAX).cloned().collect();
+        assert_eq!(
+            events,
+            [
+                Event::BarSpawned { number: 4 },
+                Event::UpgradePurchased {
+                    number: 1,
+                    upgrade: Upgrade::Double,
+                    level: 1,
+                },
+            ]
+        );
+
+        app.opts.no_save = false;
+        app.opts.save_file = Some("/nonexistent/antsy/save.json".to_owned());
+        assert!(app.save().is_err());
+        assert!(matches!(
+            app.events.latest(1).next(),
+            Some(Event::SaveFailed(_))
+        ));
+    }
+
+    #[test]
+    fn transfer_ratio_moves_in_steps_up_to_the_maximum() {
+        let mut app = app_with_bars(&[0., 0.]);
+        app.adjust_transfer(0, true);
+        assert_eq!(app.bars[0].transfer_ratio, Float(0.02));
+        for _ in 0..100 {
+            app.adjust_transfer(0, true);
+        }
+        assert_eq!(app.bars[0].transfer_ratio, Float(0.5));
+        for _ in 0..100 {
+            app.adjust_transfer(1, false);
+        }
+        assert_eq!(app.bars[1].transfer_ratio, Float(0.));
+        app.adjust_transfer(5, true);
+    }
+
+    #[test]
+    fn completion_transfers_to_next_bar() {
+        let mut app = app_with_bars(&[100., 0.]);
+        app.bars[0].progress = Float(99.9);
+        app.on_tick(app.tick + SPEED_UNIT);
+
+        let transferred = 101. * 0.01;
+        assert_eq!(app.bars[0].gathered, Float(101. - transferred));
+        assert_eq!(app.bars[1].gathered, Float(transferred));
+        let completion = app.bars[0].last_completion.unwrap();
+        assert_eq!(completion.transferred, Some(Float(transferred)));
+        assert!(app.bars[1].last_completion.is_none());
+    }
+
+    #[test]
+    fn high_level_bars_earn_research_points() {
+        let mut app = app_with_bars(&[0., 0.]);
+        app.bars[0].level = 10;
+        for bar in &mut app.bars {
+            bar.progress = Float(99.9);
+        }
+        app.on_tick(app.tick + SPEED_UNIT);
+        assert_eq!(app.research.points, Float(0.01));
+
+        assert!(!app.can_afford_global(GlobalUpgrade::ResearchGain));
+        app.research.points = Float(100.);
+        for node in [
+            ResearchNode::Efficiency,
+            ResearchNode::Momentum,
+            ResearchNode::Grants,
+        ] {
+            app.apply_action(Action::UnlockResearch(node), &mut crate::strategy::Greedy);
+        }
+        assert_eq!(
+            app.global_upgrade_cost(GlobalUpgrade::ResearchGain),
+            Some(GlobalUpgrade::ResearchGain.cost(0))
+        );
+    }
+
+    #[test]
+    fn random_events_change_gains_and_costs() {
+        use crate::random_events::{ActiveEvent, RandomEvent};
+
+        let mut app = app_with_bars(&[0., 0.]);
+        app.random_events.active = Some(ActiveEvent {
+            event: RandomEvent::GoldenBar { number: 1 },
+            until: app.tick + Duration::from_secs(60),
+        });
+        app.bars[1].progress = Float(99.9);
+        app.on_tick(app.tick + SPEED_UNIT);
+        assert_eq!(app.bars[1].gathered, Float(10.));
+        assert!(app.random_events.active.is_none());
+
+        app.random_events.active = Some(ActiveEvent {
+            event: RandomEvent::MarketCrash,
+            until: app.tick + Duration::from_secs(30),
+        });
+        assert_eq!(
+            app.global_upgrade_cost(GlobalUpgrade::Gain),
+            Some(GlobalUpgrade::Gain.cost(0) * 0.5)
+        );
+
+        app.random_events.active = None;
+        app.random_events.next_at = app.tick;
+        app.on_tick(app.tick + SPEED_UNIT);
+        assert!(app.random_events.active.is_none());
+        app.opts.no_random_events = false;
+        app.on_tick(app.tick + SPEED_UNIT);
+        assert!(matches!(
+            app.events.latest(1).next(),
+            Some(Event::RandomEventStarted { .. })
+        ));
+    }
+
+    #[test]
+    fn golden_bar_multiplies_one_of_several_completions() {
+        use crate::random_events::{ActiveEvent, RandomEvent};
+
+        let mut app = app_with_bars(&[0., 0.]);
+        app.random_events.active = Some(ActiveEvent {
+            event: RandomEvent::GoldenBar { number: 2 },
+            until: app.tick + Duration::from_secs(60),
+        });
+        app.bars[0].progress = Float(50.);
+        let ratio = app.transfer_ratio(0);
+        app.step(app.tick + SPEED_UNIT * 1100, SPEED_UNIT * 1100);
+
+        let (mut gathered, mut transferred) = (Float(0.), Float(0.));
+        for gain in [10., 1., 1.] {
+            gathered += Float(gain);
+            transferred += gathered * ratio;
+            gathered -= gathered * ratio;
+        }
+        let completion = app.bars[0].last_completion.unwrap();
+        assert!((app.bars[0].gathered.0 - gathered.0).abs() < 1e-9);
+        assert!((completion.transferred.unwrap().0 - transferred.0).abs() < 1e-9);
+        assert!((completion.gain.0 + transferred.0 - 12.).abs() < 1e-9);
+        assert!(app.random_events.active.is_none());
+    }
+
+    #[test]
+    fn bulk_transfers_match_repeated_completions() {
+        let (gain, ratio) = (Float(7.), Float(0.03));
+        let (mut gathered, mut transferred) = (Float(500.), Float(0.));
+        for completions in 1..20 {
+            gathered += gain;
+            transferred += gathered * ratio;
+            gathered -= gathered * ratio;
+            let bulk = transferred_over_completions(Float(500.), gain, ratio, completions);
+            assert!((bulk.0 - transferred.0).abs() < 1e-9);
+        }
+        let bulk = transferred_over_completions(Float(500.), gain, Float(0.), 3);
+        assert_eq!(bulk, Float(0.));
+    }
+
+    #[test]
+    fn fast_bar_completes_several_times_per_tick() {
+        let mut app = app_with_bars(&[0., 0.]);
+        app.bars[0].level_speed = Float(2000.);
+        app.on_tick(app.tick + SPEED_UNIT);
+
+        let completion = app.bars[0].last_completion.unwrap();
+        let total = completion.gain + completion.transferred.unwrap();
+        assert!(total > 2.);
+        assert_eq!(app.bars[0].gathered + app.bars[1].gathered, total);
+    }
+
+    #[test]
+    fn last_bar_completion_keeps_everything() {
+        let mut app = app_with_bars(&[0., 5.]);
+        app.bars[1].progress = Float(99.9);
+        app.on_tick(app.tick + SPEED_UNIT);
+        assert_eq!(app.bars[1].gathered, Float(6.));
+        assert_eq!(app.bars[1].last_completion.unwrap().transferred, None);
+    }
+
+    #[test]
+    fn on_tick_spawns_bars_at_interval() {
+        let mut app = App::for_test();
+        let now = app.tick + SPEED_UNIT;
+        app.on_tick(now);
+        app.on_tick(now + Duration::from_millis(500));
+        assert_eq!(app.bars.len(), 1);
+        app.on_tick(now + Duration::from_secs(1));
+        assert_eq!(app.bars.len(), 2);
+        assert_eq!(app.bars[0].number, 2);
+        assert_eq!(app.bars_to_spawn, 2);
+    }
+
+    #[test]
+    fn progress_does_not_depend_on_tick_rate() {
+        let mut slow = app_with_bars(&[0.]);
+        let mut fast = app_with_bars(&[0.]);
+        let start = slow.tick;
+        fast.tick = start;
+        for i in 1..=10 {
+            fast.on_tick(start + SPEED_UNIT * i / 2);
+        }
+        slow.on_tick(start + SPEED_UNIT * 5);
+        assert!((slow.bars[0].progress.0 - 1.25).abs() < 1e-9);
+        assert!((fast.bars[0].progress.0 - 1.25).abs() < 1e-9);
+    }
+
+    #[test]
+    fn on_tick_catches_up_after_pause() {
+        let mut app = App::for_test();
+        app.on_tick(app.tick + Duration::from_secs(20));
+        assert_eq!(app.bars.len(), 4);
+        assert!(app.bars[3].exp > 0.);
+    }
+
+    #[test]
+    fn long_gaps_are_played_in_bulk() {
+        let mut app = app_with_bars(&[0.]);
+        let start = app.tick;
+        app.on_tick(start + Duration::from_secs(10 * 365 * 24 * 3600));
+        assert!(app.counters.completions > 1_000_000);
+        assert!(app.bars[0].gathered.0.is_finite());
+        assert!(app.bars[0].gathered > 0.);
+    }
+
+    fn purchase() -> impl proptest::strategy::Strategy<Value = Purchase> {
+        prop_oneof![
+            (0..Upgrade::COUNT, 0..12usize).prop_map(|(upgrade, row)| Purchase::Bar {
+                upgrade: Upgrade::iter().nth(upgrade).unwrap(),
+                row,
+            }),
+            (0..GlobalUpgrade::COUNT).prop_map(|upgrade| Purchase::Global {
+                upgrade: GlobalUpgrade::iter().nth(upgrade).unwrap(),
+            }),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn purchases_never_leave_gathered_negative(
+            gathered in prop::collection::vec(0f64..1e7, 12),
+            purchases in prop::collection::vec(purchase(), 0..50),
+        ) {
+            let mut app = app_with_bars(&gathered);
+            for purchase in purchases {
+                app.try_purchase_upgrade(purchase);
+                for bar in &app.bars {
+                    prop_assert!(bar.gathered >= 0.);
+                }
+            }
+        }
+    }
+}
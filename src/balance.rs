@@ -0,0 +1,371 @@
+use anyhow::Context;
+use serde::Deserialize;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::random_events::RandomEvent;
+use crate::research::ResearchNode;
+use crate::upgrade::{GlobalUpgrade, Upgrade};
+
+/// The shipped balance. A balance file only needs to contain the values
+/// it changes, everything else is taken from here.
+const DEFAULT: &str = include_str!("balance.json");
+
+static BALANCE: OnceLock<Balance> = OnceLock::new();
+
+/// The longest any balance duration may be, so that adding one to the
+/// current time can't overflow.
+const MAX_DURATION_SECS: u64 = 365 * 24 * 60 * 60;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Balance {
+    pub(crate) upgrades: Upgrades,
+    pub(crate) global_upgrades: GlobalUpgrades,
+    pub(crate) bar: Bar,
+    pub(crate) app: App,
+    pub(crate) prestige: Prestige,
+    pub(crate) challenge: Challenge,
+    pub(crate) research: Research,
+    pub(crate) random_events: RandomEvents,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Upgrades {
+    speed: UpgradeCost,
+    gain: UpgradeCost,
+    double: UpgradeCost,
+    triple: UpgradeCost,
+    quadruple: UpgradeCost,
+    quintuple: UpgradeCost,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct UpgradeCost {
+    pub(crate) base_cost: f64,
+    pub(crate) scaling: f64,
+    /// How many bars further down the chain pays for the upgrade
+    pub(crate) cost_target: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct GlobalUpgrades {
+    speed: GlobalUpgradeCost,
+    exp_boost: GlobalUpgradeCost,
+    progress_bars: GlobalUpgradeCost,
+    gain: GlobalUpgradeCost,
+    exp_gain: GlobalUpgradeCost,
+    research_gain: GlobalUpgradeCost,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct GlobalUpgradeCost {
+    pub(crate) base_cost: f64,
+    pub(crate) scaling: f64,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Bar {
+    pub(crate) gain_base: f64,
+    /// Exp needed for level n is exp_curve^n
+    pub(crate) exp_curve: f64,
+    /// level_speed grows by increment * (level + offset) on each level up
+    pub(crate) level_speed_increment: f64,
+    pub(crate) level_speed_offset: f64,
+    pub(crate) speed_upgrade_multiplier: f64,
+    pub(crate) global_speed_multiplier: f64,
+    /// Speed multiplier at which the bar is slowed down by 10x and gains
+    /// are increased by 10x instead
+    pub(crate) speed_rescale_threshold: f64,
+    pub(crate) boost_multiplier: f64,
+    pub(crate) boost_base_secs: u64,
+    /// Charging a bar by hand boosts it for this long, after which it can't
+    /// be charged again for the cooldown. Kept short next to the cooldown
+    /// so that idle play isn't far behind.
+    pub(crate) charge_boost_secs: u64,
+    pub(crate) charge_cooldown_secs: u64,
+    /// Share of gathered passed to the next bar on completion, for new bars
+    pub(crate) transfer_ratio: f64,
+    /// How much the player changes a bar's transfer ratio per key press,
+    /// and how high they can set it
+    pub(crate) transfer_ratio_step: f64,
+    pub(crate) max_transfer_ratio: f64,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct App {
+    pub(crate) initial_bars: usize,
+    pub(crate) bars_per_progress_bars_upgrade: usize,
+    bar_spawn_interval_ms: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Prestige {
+    pub(crate) bar_threshold: usize,
+    pub(crate) points_per_bar: f64,
+    pub(crate) upgrade_cost_base: usize,
+    pub(crate) complete_faster_factor: f64,
+    pub(crate) level_up_faster_factor: f64,
+    pub(crate) transfer_extra_exp_factor: f64,
+    pub(crate) transfer_extra_value_ratio: f64,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Challenge {
+    /// Time between bar spawns in the slow spawns challenge
+    slow_spawn_interval_ms: u64,
+    /// The rewards for completing challenges: costs, prestige points and
+    /// the spawn interval are multiplied by these
+    pub(crate) global_upgrade_discount: f64,
+    pub(crate) speed_upgrade_discount: f64,
+    pub(crate) prestige_bonus: f64,
+    pub(crate) spawn_interval_factor: f64,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Research {
+    /// Bars from this level on earn research points when they complete
+    pub(crate) min_level: usize,
+    /// Multiplied by 1 + the level of the research gain global upgrade
+    pub(crate) points_per_completion: f64,
+    /// What the efficiency and momentum nodes multiply gain and speed by
+    pub(crate) gain_multiplier: f64,
+    pub(crate) speed_multiplier: f64,
+    /// What the practice node multiplies the level_speed increment by
+    pub(crate) level_speed_factor: f64,
+    /// The research points each node costs
+    pub(crate) nodes: ResearchNodes,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct RandomEvents {
+    /// The time between random events is picked between half and one and
+    /// a half times this
+    mean_interval_secs: u64,
+    /// How long each event lasts. A golden bar also ends when it completes.
+    golden_bar_secs: u64,
+    speed_surge_secs: u64,
+    market_crash_secs: u64,
+    /// What the golden bar's gain, the speed of bars during a surge and
+    /// global upgrade costs during a crash are multiplied by
+    pub(crate) golden_bar_gain_multiplier: f64,
+    pub(crate) speed_surge_multiplier: f64,
+    pub(crate) market_crash_discount: f64,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ResearchNodes {
+    efficiency: f64,
+    momentum: f64,
+    quintuple: f64,
+    practice: f64,
+    grants: f64,
+}
+
+impl Balance {
+    /// Rejects values the game can't run with, naming the field.
+    fn validate(&self) -> anyhow::Result<()> {
+        // Levelling up never ends if the exp needed doesn't grow
+        if !self.bar.exp_curve.is_finite() || self.bar.exp_curve <= 1. {
+            anyhow::bail!("bar.exp_curve must be above 1, not {}", self.bar.exp_curve);
+        }
+        if !self.bar.transfer_ratio_step.is_finite() || self.bar.transfer_ratio_step <= 0. {
+            anyhow::bail!(
+                "bar.transfer_ratio_step must be above 0, not {}",
+                self.bar.transfer_ratio_step
+            );
+        }
+        let durations = [
+            ("bar.boost_base_secs", self.bar.boost_base_secs),
+            ("bar.charge_boost_secs", self.bar.charge_boost_secs),
+            ("bar.charge_cooldown_secs", self.bar.charge_cooldown_secs),
+            (
+                "random_events.mean_interval_secs",
+                self.random_events.mean_interval_secs,
+            ),
+            (
+                "random_events.golden_bar_secs",
+                self.random_events.golden_bar_secs,
+            ),
+            (
+                "random_events.speed_surge_secs",
+                self.random_events.speed_surge_secs,
+            ),
+            (
+                "random_events.market_crash_secs",
+                self.random_events.market_crash_secs,
+            ),
+        ];
+        for (field, secs) in durations {
+            if secs > MAX_DURATION_SECS {
+                anyhow::bail!("{field} must be at most {MAX_DURATION_SECS}, not {secs}");
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Upgrades {
+    pub(crate) fn get(&self, upgrade: Upgrade) -> &UpgradeCost {
+        match upgrade {
+            Upgrade::Speed => &self.speed,
+            Upgrade::Gain => &self.gain,
+            Upgrade::Double => &self.double,
+            Upgrade::Triple => &self.triple,
+            Upgrade::Quadruple => &self.quadruple,
+            Upgrade::Quintuple => &self.quintuple,
+        }
+    }
+}
+
+impl GlobalUpgrades {
+    pub(crate) fn get(&self, upgrade: GlobalUpgrade) -> &GlobalUpgradeCost {
+        match upgrade {
+            GlobalUpgrade::Speed => &self.speed,
+            GlobalUpgrade::ExpBoost => &self.exp_boost,
+            GlobalUpgrade::ProgressBars => &self.progress_bars,
+            GlobalUpgrade::Gain => &self.gain,
+            GlobalUpgrade::ExpGain => &self.exp_gain,
+            GlobalUpgrade::ResearchGain => &self.research_gain,
+        }
+    }
+}
+
+impl ResearchNodes {
+    pub(crate) fn cost(&self, node: ResearchNode) -> f64 {
+        match node {
+            ResearchNode::Efficiency => self.efficiency,
+            ResearchNode::Momentum => self.momentum,
+            ResearchNode::Quintuple => self.quintuple,
+            ResearchNode::Practice => self.practice,
+            ResearchNode::Grants => self.grants,
+        }
+    }
+}
+
+impl App {
+    pub(crate) fn bar_spawn_interval(&self) -> Duration {
+        Duration::from_millis(self.bar_spawn_interval_ms)
+    }
+}
+
+impl Challenge {
+    pub(crate) fn slow_spawn_interval(&self) -> Duration {
+        Duration::from_millis(self.slow_spawn_interval_ms)
+    }
+}
+
+impl RandomEvents {
+    pub(crate) fn mean_interval(&self) -> Duration {
+        Duration::from_secs(self.mean_interval_secs)
+    }
+
+    pub(crate) fn duration(&self, event: RandomEvent) -> Duration {
+        Duration::from_secs(match event {
+            RandomEvent::GoldenBar { .. } => self.golden_bar_secs,
+            RandomEvent::SpeedSurge => self.speed_surge_secs,
+            RandomEvent::MarketCrash => self.market_crash_secs,
+        })
+    }
+}
+
+/// Loads the balance, overriding the defaults with the values in `path`.
+/// Must be called before the first call to `get`.
+pub(crate) fn load(path: Option<&str>) -> anyhow::Result<()> {
+    let balance = match path {
+        None => default(),
+        Some(path) => {
+            let contents = std::fs::read_to_string(path)
+                .with_context(|| format!("Could not read balance file {path}"))?;
+            let overrides: serde_json::Value = serde_json::from_str(&contents)
+                .with_context(|| format!("Could not parse balance file {path}"))?;
+            with_overrides(overrides).with_context(|| format!("Invalid balance file {path}"))?
+        }
+    };
+    if BALANCE.set(balance).is_err() {
+        anyhow::bail!("Balance was already loaded");
+    }
+    Ok(())
+}
+
+pub(crate) fn get() -> &'static Balance {
+    BALANCE.get_or_init(default)
+}
+
+fn default() -> Balance {
+    serde_json::from_str(DEFAULT).expect("Default balance should be valid")
+}
+
+fn with_overrides(overrides: serde_json::Value) -> anyhow::Result<Balance> {
+    let mut balance: serde_json::Value =
+        serde_json::from_str(DEFAULT).expect("Default balance should be valid json");
+    merge(&mut balance, overrides);
+    let balance: Balance = serde_json::from_value(balance)?;
+    balance.validate()?;
+    Ok(balance)
+}
+
+fn merge(base: &mut serde_json::Value, overrides: serde_json::Value) {
+    match (base, overrides) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overrides) => *base = overrides,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn error(overrides: serde_json::Value) -> String {
+        match with_overrides(overrides) {
+            Ok(_) => panic!("the balance should be rejected"),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    #[test]
+    fn default_is_valid() {
+        default().validate().unwrap();
+    }
+
+    #[test]
+    fn exp_curve_must_grow() {
+        let err = error(json!({"bar": {"exp_curve": 1.0}}));
+        assert!(err.contains("bar.exp_curve"), "{err}");
+    }
+
+    #[test]
+    fn transfer_ratio_step_must_be_positive() {
+        let err = error(json!({"bar": {"transfer_ratio_step": 0.0}}));
+        assert!(err.contains("bar.transfer_ratio_step"), "{err}");
+    }
+
+    #[test]
+    fn durations_are_bounded() {
+        let err = error(json!({"random_events": {"speed_surge_secs": u64::MAX}}));
+        assert!(err.contains("random_events.speed_surge_secs"), "{err}");
+        let err = error(json!({"bar": {"charge_cooldown_secs": u64::MAX}}));
+        assert!(err.contains("bar.charge_cooldown_secs"), "{err}");
+    }
+}
@@ -3,10 +3,22 @@ use std::time::{Duration, Instant};
 use strum::*;
 
 use crate::app::{App, Completion};
+use crate::balance;
+use crate::events::{Event, EventLog};
 use crate::float::Float;
 use crate::prestige::{Prestige, PrestigeUpgrade};
+use crate::research::Research;
 use crate::upgrade::{GlobalUpgrade, Upgrade};
 
+/// 10^gain_exponent has to fit in a usize
+pub(crate) const MAX_GAIN_EXPONENT: usize = 19;
+
+/// Bar speeds are the progress made per this duration.
+pub(crate) const SPEED_UNIT: Duration = Duration::from_millis(40);
+
+/// Boosts stop stacking up at this length.
+pub(crate) const MAX_BOOST: Duration = Duration::from_secs(24 * 60 * 60);
+
 #[derive(Clone)]
 pub(crate) struct Bar {
     pub(crate) progress: Float,
@@ -18,6 +30,10 @@ pub(crate) struct Bar {
     pub(crate) exp: Float,
     pub(crate) level: usize,
     pub(crate) boost_until: Option<Instant>,
+    /// When the bar can be charged by hand again
+    pub(crate) charge_ready_at: Option<Instant>,
+    /// Slow down the progress bars. When progress finishes,
+    /// exp and gains need to be incremented accordingly.
     pub(crate) gain_exponent: usize,
     pub(crate) level_speed: Float,
 }
@@ -27,33 +43,40 @@ impl Bar {
         Bar {
             progress: 0.0.into(),
             gathered: 0.0.into(),
-            transfer_ratio: 0.01.into(),
+            transfer_ratio: balance::get().bar.transfer_ratio.into(),
             last_completion: None,
             upgrades: Upgrade::iter().map(|u| (u, 0)).collect(),
             number,
             exp: 0.0.into(),
             level: 1,
             boost_until: None,
-            /// Slow down the progress bars. When progress finishes,
-            /// exp and gains need to be incremented accordingly.
+            charge_ready_at: None,
             gain_exponent: 0,
             level_speed: 1.0.into(),
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn inc_exp(
         &mut self,
+        completions: usize,
         global_exp_gain_levels: usize,
         global_exp_boost: usize,
         global_speed_levels: usize,
         prestige: &Prestige,
+        research: &Research,
         now: Instant,
         next_bar: Option<&mut Bar>,
+        events: &mut EventLog,
     ) {
+        let balance = &balance::get().prestige;
         let mut exp_gain = Float(
             (1. + global_exp_gain_levels as f64)
                 * 10usize.pow(self.gain_exponent as u32) as f64
-                * 0.95_f64.powf(prestige.level_f(PrestigeUpgrade::LevelUpFaster)),
+                * balance
+                    .level_up_faster_factor
+                    .powf(prestige.level_f(PrestigeUpgrade::LevelUpFaster))
+                * completions as f64,
         );
 
         // Transfer exp
@@ -61,7 +84,10 @@ impl Bar {
             if next_bar.level < self.level
                 || (next_bar.level == self.level && next_bar.exp < self.exp)
             {
-                let remaining = exp_gain * 0.99_f64.powf(prestige.level_f(PrestigeUpgrade::TransferExtraExp));
+                let remaining = exp_gain
+                    * balance
+                        .transfer_extra_exp_factor
+                        .powf(prestige.level_f(PrestigeUpgrade::TransferExtraExp));
                 let transfer = exp_gain - remaining;
                 exp_gain = remaining;
                 next_bar.exp += transfer;
@@ -69,73 +95,168 @@ impl Bar {
         }
 
         self.exp += exp_gain;
-        self.check_level_up(global_exp_boost, global_speed_levels, now);
+        self.check_level_up(global_exp_boost, global_speed_levels, research, now, events);
     }
 
+    /// Levels up as many times as the bar's exp allows, logging one event
+    /// with the level it ends up at.
     pub(crate) fn check_level_up(
         &mut self,
         global_exp_boost: usize,
         global_speed_levels: usize,
+        research: &Research,
         now: Instant,
+        events: &mut EventLog,
     ) {
-        let exp_for_next_level = self.exp_for_next_level();
-        if self.exp >= exp_for_next_level {
-            self.exp -= exp_for_next_level;
+        let level = self.level;
+        while self.exp >= self.exp_for_next_level() {
+            self.exp -= self.exp_for_next_level();
             self.level += 1;
 
-            self.level_speed += Float(0.01 * (self.level as f64 + 3.));
+            let balance = &balance::get().bar;
+            self.level_speed += Float(
+                balance.level_speed_increment
+                    * research.level_speed_factor()
+                    * (self.level as f64 + balance.level_speed_offset),
+            );
 
-            self.adjust_speed_multiplier(global_speed_levels);
+            self.adjust_speed_multiplier(global_speed_levels, research);
 
-            let extra_dur = Duration::from_secs(1 + global_exp_boost as u64);
-            match self.boost_until {
-                None => self.boost_until = Some(now + extra_dur),
-                Some(boost_until) => {
-                    if boost_until < now {
-                        self.boost_until = Some(now + extra_dur);
-                    } else {
-                        self.boost_until = Some(now + (boost_until - now) + extra_dur);
-                    }
-                }
-            }
+            let extra_dur = Duration::from_secs(balance.boost_base_secs + global_exp_boost as u64);
+            self.extend_boost(now, extra_dur);
+        }
+        if self.level > level {
+            events.push(Event::LevelUp {
+                number: self.number,
+                level: self.level,
+                boost: self
+                    .boost_until
+                    .map_or(Duration::ZERO, |until| until.saturating_duration_since(now)),
+            });
+        }
+    }
+
+    fn extend_boost(&mut self, now: Instant, extra_dur: Duration) {
+        let remaining = self
+            .boost_until
+            .map_or(Duration::ZERO, |until| until.saturating_duration_since(now));
+        self.boost_until = Some(now + (remaining + extra_dur).min(MAX_BOOST));
+    }
+
+    /// How long until the bar can be charged again, if it's cooling down.
+    pub(crate) fn charge_cooldown(&self, now: Instant) -> Option<Duration> {
+        self.charge_ready_at
+            .filter(|&ready_at| ready_at > now)
+            .map(|ready_at| ready_at - now)
+    }
+
+    /// Boosts the bar by hand, unless it's still cooling down from the last
+    /// charge.
+    pub(crate) fn try_charge(&mut self, now: Instant) -> bool {
+        if self.charge_cooldown(now).is_some() {
+            return false;
         }
+        let balance = &balance::get().bar;
+        self.extend_boost(now, Duration::from_secs(balance.charge_boost_secs));
+        self.charge_ready_at = Some(now + Duration::from_secs(balance.charge_cooldown_secs));
+        true
     }
 
-    pub(crate) fn adjust_speed_multiplier(&mut self, global_speed_levels: usize) {
-        if self.speed_multiplier(global_speed_levels) >= 10. {
+    pub(crate) fn adjust_speed_multiplier(
+        &mut self,
+        global_speed_levels: usize,
+        research: &Research,
+    ) {
+        if self.speed_multiplier(global_speed_levels, research)
+            >= balance::get().bar.speed_rescale_threshold
+        {
             self.gain_exponent += 1;
         }
     }
 
+    /// The `gain_exponent` that repeated calls to `adjust_speed_multiplier`
+    /// settle on.
+    pub(crate) fn settled_gain_exponent(
+        &self,
+        global_speed_levels: usize,
+        research: &Research,
+    ) -> usize {
+        let threshold = balance::get().bar.speed_rescale_threshold;
+        let unscaled = self.speed_multiplier(global_speed_levels, research).0
+            * 10f64.powi(self.gain_exponent.min(MAX_GAIN_EXPONENT) as i32);
+        (0..MAX_GAIN_EXPONENT)
+            .find(|exponent| unscaled / 10f64.powi(*exponent as i32) < threshold)
+            .unwrap_or(MAX_GAIN_EXPONENT)
+    }
+
+    /// The `level_speed` a bar has after levelling up from 1 to `level`
+    /// with the current research.
+    pub(crate) fn level_speed_for(level: usize, research: &Research) -> Float {
+        let balance = &balance::get().bar;
+        let increment = balance.level_speed_increment * research.level_speed_factor();
+        if level < 2 {
+            return Float(1.);
+        }
+        // Each level from 2 up adds `increment * (level + offset)`
+        let levels = (level - 1) as f64;
+        let level_sum = levels * (level as f64 + 2.) / 2.;
+        Float(1. + increment * (level_sum + levels * balance.level_speed_offset))
+    }
+
+    /// The highest level whose exp requirement is finite. Levels above it
+    /// can't be reached by playing.
+    pub(crate) fn max_level() -> usize {
+        let exp_curve = balance::get().bar.exp_curve;
+        let mut level = (f64::MAX.ln() / exp_curve.ln()) as usize;
+        while !Self::exp_for_level(level).0.is_finite() {
+            level -= 1;
+        }
+        level
+    }
+
     pub(crate) fn is_boosted(&self, now: Instant) -> bool {
-        self.boost_until.map_or(false, |until| until > now)
+        self.boost_until.is_some_and(|until| until > now)
     }
 
     fn exp_for_level(level: usize) -> Float {
-        Float(1.5).powf(level as f64)
+        Float(balance::get().bar.exp_curve).powf(level as f64)
     }
 
     pub(crate) fn exp_for_next_level(&self) -> Float {
         Self::exp_for_level(self.level + 1)
     }
 
+    /// The gain of the next completion.
     pub(crate) fn gain(&self, app: &App) -> Float {
-        const GAIN_BASE: Float = Float(1.);
+        self.base_gain(app) * app.random_events.gain_multiplier(self.number)
+    }
+
+    /// The gain of a completion, leaving out random events.
+    pub(crate) fn base_gain(&self, app: &App) -> Float {
+        let gain_base = Float(balance::get().bar.gain_base);
         use Upgrade::*;
-        (GAIN_BASE + self.get_upgrade(Gain) + app.get_global_upgrade(GlobalUpgrade::Gain))
+        (gain_base + self.get_upgrade(Gain) + app.get_global_upgrade(GlobalUpgrade::Gain))
             * Float(2.).pow(self.get_upgrade(Double))
             * Float(3.).pow(self.get_upgrade(Triple))
             * Float(4.).pow(self.get_upgrade(Quadruple))
+            * Float(5.).pow(self.get_upgrade(Quintuple))
+            * app.research.gain_multiplier()
             * Float(10.0_f64.powf(self.gain_exponent as f64))
     }
 
-    fn speed(&self, speed_base: Float, global_speed_levels: usize) -> Float {
-        speed_base * self.speed_multiplier(global_speed_levels)
+    fn speed(&self, speed_base: Float, global_speed_levels: usize, research: &Research) -> Float {
+        speed_base * self.speed_multiplier(global_speed_levels, research)
     }
 
-    pub(crate) fn speed_multiplier(&self, global_speed_levels: usize) -> Float {
-        Float(1.25).pow(self.get_upgrade(Upgrade::Speed))
-            * Float(1.05).pow(global_speed_levels.into())
+    pub(crate) fn speed_multiplier(
+        &self,
+        global_speed_levels: usize,
+        research: &Research,
+    ) -> Float {
+        let balance = &balance::get().bar;
+        Float(balance.speed_upgrade_multiplier).pow(self.get_upgrade(Upgrade::Speed))
+            * Float(balance.global_speed_multiplier).pow(global_speed_levels.into())
+            * research.speed_multiplier()
             * self.level_speed
             * Float(10.0_f64).pow(-Float::from(self.gain_exponent))
     }
@@ -145,6 +266,7 @@ impl Bar {
         upgrade.base_cost() * upgrade.scaling().powf(level as f64)
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn inc(
         &mut self,
         speed_base: Float,
@@ -152,30 +274,41 @@ impl Bar {
         global_exp_gain_levels: usize,
         global_exp_boost: usize,
         prestige: &Prestige,
+        research: &Research,
         now: Instant,
+        dt: Duration,
         next_bar: Option<&mut Bar>,
-    ) -> bool {
-        let boost_mult = if self.boost_until.map_or(false, |until| now < until) {
-            2.
+        events: &mut EventLog,
+    ) -> usize {
+        let boost_mult = if self.boost_until.is_some_and(|until| now < until) {
+            balance::get().bar.boost_multiplier
         } else {
             1.
         };
-        let new = self.progress + self.speed(speed_base, global_speed_levels) * boost_mult;
-        if new > 100. * 0.95_f64.powf(prestige.level_f(PrestigeUpgrade::CompleteFaster)) {
+        let steps = dt.as_secs_f64() / SPEED_UNIT.as_secs_f64();
+        let new = self.progress
+            + self.speed(speed_base, global_speed_levels, research) * boost_mult * steps;
+        let complete_faster_factor = balance::get().prestige.complete_faster_factor;
+        let threshold =
+            100. * complete_faster_factor.powf(prestige.level_f(PrestigeUpgrade::CompleteFaster));
+        let completions = (new.0 / threshold).floor() as usize;
+        if completions > 0 {
             self.inc_exp(
+                completions,
                 global_exp_gain_levels,
                 global_exp_boost,
                 global_speed_levels,
                 prestige,
+                research,
                 now,
                 next_bar,
+                events,
             );
-            self.progress = Float(100.) - self.progress;
-            true
+            self.progress = new - Float(threshold * completions as f64);
         } else {
             self.progress = new;
-            false
         }
+        completions
     }
 
     pub(crate) fn recent_completion(&self, now: Instant) -> Option<Completion> {
@@ -185,13 +318,18 @@ impl Bar {
             .find(|c| now - c.tick < Duration::from_secs(1))
     }
 
-    pub(crate) fn inc_upgrade(&mut self, upgrade: Upgrade, global_speed_levels: usize) {
+    pub(crate) fn inc_upgrade(
+        &mut self,
+        upgrade: Upgrade,
+        global_speed_levels: usize,
+        research: &Research,
+    ) {
         *self
             .upgrades
             .entry(upgrade)
             .or_insert_with(|| panic!("Should have been init'd")) += 1;
         if let Upgrade::Speed = upgrade {
-            self.adjust_speed_multiplier(global_speed_levels);
+            self.adjust_speed_multiplier(global_speed_levels, research);
         }
     }
 
@@ -203,3 +341,218 @@ impl Bar {
         self.upgrades[&upgrade]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::research::ResearchNode;
+
+    fn assert_close(a: Float, b: f64) {
+        assert!((a.0 - b).abs() < 1e-9, "{} != {}", a.0, b);
+    }
+
+    fn inc(bar: &mut Bar, prestige: &Prestige, now: Instant) -> usize {
+        inc_for(bar, prestige, now, SPEED_UNIT)
+    }
+
+    fn inc_for(bar: &mut Bar, prestige: &Prestige, now: Instant, dt: Duration) -> usize {
+        bar.inc(
+            Float(0.25),
+            0,
+            0,
+            0,
+            prestige,
+            &Research::new(),
+            now,
+            dt,
+            None,
+            &mut EventLog::default(),
+        )
+    }
+
+    #[test]
+    fn inc_adds_speed_to_progress() {
+        let mut bar = Bar::new(1);
+        assert_eq!(inc(&mut bar, &Prestige::new(), Instant::now()), 0);
+        assert_close(bar.progress, 0.25);
+    }
+
+    #[test]
+    fn inc_doubles_speed_while_boosted() {
+        let now = Instant::now();
+        let mut bar = Bar::new(1);
+        bar.boost_until = Some(now + Duration::from_secs(1));
+        inc(&mut bar, &Prestige::new(), now);
+        assert_close(bar.progress, 0.5);
+    }
+
+    #[test]
+    fn inc_scales_with_elapsed_time() {
+        let mut bar = Bar::new(1);
+        inc_for(&mut bar, &Prestige::new(), Instant::now(), SPEED_UNIT / 4);
+        assert_close(bar.progress, 0.0625);
+    }
+
+    #[test]
+    fn inc_completes_and_gains_exp() {
+        let mut bar = Bar::new(1);
+        bar.progress = Float(99.9);
+        assert_eq!(inc(&mut bar, &Prestige::new(), Instant::now()), 1);
+        assert_close(bar.exp, 1.);
+        assert_close(bar.progress, 0.15);
+    }
+
+    #[test]
+    fn inc_completes_several_times_per_tick() {
+        let mut bar = Bar::new(1);
+        bar.progress = Float(50.);
+        let dt = SPEED_UNIT * 1100;
+        let completions = inc_for(&mut bar, &Prestige::new(), Instant::now(), dt);
+        assert_eq!(completions, 3);
+        assert_close(bar.progress, 25.);
+        assert_close(bar.exp, 3. - Bar::exp_for_level(2).0);
+        assert_eq!(bar.level, 2);
+    }
+
+    #[test]
+    fn check_level_up_can_level_several_times() {
+        let mut bar = Bar::new(1);
+        bar.exp = Bar::exp_for_level(2) + Bar::exp_for_level(3);
+        let mut events = EventLog::default();
+        bar.check_level_up(0, 0, &Research::new(), Instant::now(), &mut events);
+        assert_eq!(bar.level, 3);
+        assert_close(bar.exp, 0.);
+        let events: Vec<_> = events.latest(usize::MAX).collect();
+        assert_eq!(
+            events,
+            [&Event::LevelUp {
+                number: 1,
+                level: 3,
+                boost: Duration::from_secs(2),
+            }]
+        );
+    }
+
+    #[test]
+    fn check_level_up_below_threshold() {
+        let mut bar = Bar::new(1);
+        bar.exp = bar.exp_for_next_level() - Float(0.1);
+        bar.check_level_up(
+            0,
+            0,
+            &Research::new(),
+            Instant::now(),
+            &mut EventLog::default(),
+        );
+        assert_eq!(bar.level, 1);
+        assert!(bar.boost_until.is_none());
+    }
+
+    #[test]
+    fn check_level_up_increases_level_speed_and_boosts() {
+        let now = Instant::now();
+        let mut bar = Bar::new(1);
+        bar.exp = bar.exp_for_next_level() + Float(0.5);
+        bar.check_level_up(2, 0, &Research::new(), now, &mut EventLog::default());
+        assert_eq!(bar.level, 2);
+        assert_close(bar.exp, 0.5);
+        assert_close(bar.level_speed, 1. + 0.01 * (2. + 3.));
+        assert_eq!(bar.boost_until, Some(now + Duration::from_secs(3)));
+    }
+
+    #[test]
+    fn level_speed_for_matches_levelling_up() {
+        let mut bar = Bar::new(1);
+        for _ in 0..5 {
+            bar.exp = bar.exp_for_next_level();
+            bar.check_level_up(
+                0,
+                0,
+                &Research::new(),
+                Instant::now(),
+                &mut EventLog::default(),
+            );
+        }
+        assert_close(
+            Bar::level_speed_for(bar.level, &Research::new()),
+            bar.level_speed.0,
+        );
+    }
+
+    #[test]
+    fn check_level_up_extends_running_boost() {
+        let now = Instant::now();
+        let mut bar = Bar::new(1);
+        bar.boost_until = Some(now + Duration::from_secs(5));
+        bar.exp = bar.exp_for_next_level();
+        bar.check_level_up(0, 0, &Research::new(), now, &mut EventLog::default());
+        assert_eq!(bar.boost_until, Some(now + Duration::from_secs(6)));
+    }
+
+    #[test]
+    fn charging_boosts_until_the_cooldown_is_over() {
+        let now = Instant::now();
+        let mut bar = Bar::new(1);
+        bar.boost_until = Some(now + Duration::from_secs(1));
+        assert!(bar.try_charge(now));
+        assert_eq!(bar.boost_until, Some(now + Duration::from_secs(4)));
+        assert_eq!(bar.charge_cooldown(now), Some(Duration::from_secs(30)));
+
+        let later = now + Duration::from_secs(10);
+        assert!(!bar.try_charge(later));
+        assert_eq!(bar.charge_cooldown(later), Some(Duration::from_secs(20)));
+        assert!(bar.try_charge(now + Duration::from_secs(30)));
+    }
+
+    #[test]
+    fn gain_multiplies_upgrades() {
+        let mut app = App::for_test();
+        app.global_upgrades.insert(GlobalUpgrade::Gain, 1);
+        let mut bar = Bar::new(1);
+        bar.upgrades.insert(Upgrade::Gain, 2);
+        bar.upgrades.insert(Upgrade::Double, 1);
+        bar.upgrades.insert(Upgrade::Triple, 1);
+        assert_close(bar.gain(&app), (1. + 2. + 1.) * 2. * 3.);
+        bar.gain_exponent = 2;
+        assert_close(bar.gain(&app), (1. + 2. + 1.) * 2. * 3. * 100.);
+    }
+
+    #[test]
+    fn research_multiplies_gain_and_speed() {
+        let mut app = App::for_test();
+        let bar = Bar::new(1);
+        let (gain, speed) = (bar.gain(&app), bar.speed_multiplier(0, &app.research));
+        app.research.unlocked.insert(ResearchNode::Efficiency);
+        app.research.unlocked.insert(ResearchNode::Momentum);
+        assert_close(bar.gain(&app), gain.0 * 1.25);
+        assert_close(bar.speed_multiplier(0, &app.research), speed.0 * 1.25);
+    }
+
+    #[test]
+    fn speed_multiplier_combines_upgrades() {
+        let mut bar = Bar::new(1);
+        bar.upgrades.insert(Upgrade::Speed, 2);
+        bar.level_speed = Float(1.5);
+        assert_close(
+            bar.speed_multiplier(3, &Research::new()),
+            1.25_f64.powi(2) * 1.05_f64.powi(3) * 1.5,
+        );
+    }
+
+    #[test]
+    fn adjust_speed_multiplier_rescales_fast_bars() {
+        let app = App::for_test();
+        let research = Research::new();
+        let mut bar = Bar::new(1);
+        bar.level_speed = Float(12.);
+        let gain = bar.gain(&app);
+        bar.adjust_speed_multiplier(0, &research);
+        assert_eq!(bar.gain_exponent, 1);
+        assert_close(bar.speed_multiplier(0, &research), 1.2);
+        assert_close(bar.gain(&app), gain.0 * 10.);
+
+        bar.adjust_speed_multiplier(0, &research);
+        assert_eq!(bar.gain_exponent, 1);
+        assert_eq!(bar.settled_gain_exponent(0, &research), 1);
+    }
+}
@@ -0,0 +1,62 @@
+use strum::*;
+
+use crate::balance;
+
+/// A prestige run under a harder rule. Prestiging during one completes
+/// it, which grants its reward for good.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, EnumIter, EnumCount)]
+pub(crate) enum Challenge {
+    NoGlobalUpgrades,
+    NoTransfer,
+    NoSpeedUpgrades,
+    SlowSpawns,
+}
+
+impl Challenge {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Challenge::NoGlobalUpgrades => "No global upgrades",
+            Challenge::NoTransfer => "No transfer",
+            Challenge::NoSpeedUpgrades => "No speed upgrades",
+            Challenge::SlowSpawns => "Slow spawns",
+        }
+    }
+
+    /// What changes during the challenge.
+    pub(crate) fn rule(self) -> String {
+        match self {
+            // Without more bars there would be no way to prestige
+            Challenge::NoGlobalUpgrades => "Only Progress Bars can be bought globally".to_owned(),
+            Challenge::NoTransfer => "Bars pass nothing on".to_owned(),
+            Challenge::NoSpeedUpgrades => "Bars can't buy speed".to_owned(),
+            Challenge::SlowSpawns => format!(
+                "Bars spawn every {}s",
+                balance::get().challenge.slow_spawn_interval().as_secs()
+            ),
+        }
+    }
+
+    /// What completing the challenge grants.
+    pub(crate) fn reward(self) -> String {
+        let balance = &balance::get().challenge;
+        let percent = |factor: f64| ((1. - factor).abs() * 100.).round();
+        match self {
+            Challenge::NoGlobalUpgrades => format!(
+                "Global upgrades cost {}% less",
+                percent(balance.global_upgrade_discount)
+            ),
+            Challenge::NoTransfer => format!(
+                "Prestige gives {}% more points",
+                percent(balance.prestige_bonus)
+            ),
+            Challenge::NoSpeedUpgrades => format!(
+                "Speed upgrades cost {}% less",
+                percent(balance.speed_upgrade_discount)
+            ),
+            Challenge::SlowSpawns => format!(
+                "Bars spawn {}% sooner",
+                percent(balance.spawn_interval_factor)
+            ),
+        }
+    }
+}
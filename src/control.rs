@@ -0,0 +1,649 @@
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use strum::*;
+
+use crate::app::App;
+use crate::controls::Action;
+use crate::save;
+use crate::strategy::{GameView, Purchase};
+use crate::upgrade::{GlobalUpgrade, Upgrade};
+
+// The control socket speaks JSON-RPC 2.0, one request or response per
+// line. Upgrades are named like in save files, e.g. "Double" or
+// "LevelUpFaster", and bars by their number.
+//
+// Queries: get_state, get_bars, get_global_upgrades, get_prestige,
+// get_research.
+// Commands: buy {"bar": 3, "upgrade": "Double"}, buy {"global": "Speed"},
+// buy {"prestige": "LevelUpFaster"}, upgrade_any,
+// adjust_transfer {"bar": 3, "up": true}, charge_bar {"bar": 3}, prestige,
+// toggle_challenge {"challenge": "NoTransfer"},
+// unlock_research {"node": "Efficiency"}.
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// The request was understood, but the game refused it
+const REFUSED: i64 = -32000;
+
+/// Clients sending longer lines are dropped.
+const MAX_LINE: usize = 64 * 1024;
+
+/// A Unix socket serving queries and commands to other programs while the
+/// game runs. It never blocks, so the game loop polls it.
+pub(crate) struct ControlSocket {
+    path: PathBuf,
+    listener: UnixListener,
+    clients: Vec<Client>,
+}
+
+struct Client {
+    stream: UnixStream,
+    /// What has been read of the next request
+    partial: Vec<u8>,
+}
+
+impl ControlSocket {
+    /// Listens at `path`, replacing the socket of a previous session that
+    /// didn't clean up after itself. Anything else at `path` is left alone.
+    pub(crate) fn bind(path: &str) -> anyhow::Result<Self> {
+        let path = PathBuf::from(path);
+        if let Ok(metadata) = std::fs::symlink_metadata(&path) {
+            if !metadata.file_type().is_socket() {
+                anyhow::bail!("Not replacing {path:?} with the control socket, it isn't a socket");
+            }
+            if UnixStream::connect(&path).is_err() {
+                std::fs::remove_file(&path)
+                    .with_context(|| format!("Could not remove stale socket {path:?}"))?;
+            }
+        }
+        let listener = UnixListener::bind(&path)
+            .with_context(|| format!("Could not listen on control socket {path:?}"))?;
+        listener.set_nonblocking(true)?;
+        log::info!("Listening on control socket {path:?}");
+        Ok(Self {
+            path,
+            listener,
+            clients: vec![],
+        })
+    }
+
+    /// Answers every complete request that has arrived. Commands are
+    /// carried out with `apply`, so that they can be recorded.
+    pub(crate) fn poll(&mut self, app: &mut App, mut apply: impl FnMut(&mut App, Action)) {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => match stream.set_nonblocking(true) {
+                    Ok(()) => self.clients.push(Client {
+                        stream,
+                        partial: vec![],
+                    }),
+                    Err(err) => log::warn!("Dropped control client: {err}"),
+                },
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) => {
+                    log::warn!("Control socket accept failed: {err}");
+                    break;
+                }
+            }
+        }
+        self.clients
+            .retain_mut(|client| match client.serve(app, &mut apply) {
+                Ok(connected) => connected,
+                Err(err) => {
+                    log::warn!("Dropped control client: {err}");
+                    false
+                }
+            });
+    }
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+impl Client {
+    /// Answers the requests that can be read without blocking. Returns
+    /// false once the client has disconnected.
+    fn serve(
+        &mut self,
+        app: &mut App,
+        apply: &mut impl FnMut(&mut App, Action),
+    ) -> io::Result<bool> {
+        let mut connected = true;
+        let mut buffer = [0; 4096];
+        loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => {
+                    connected = false;
+                    break;
+                }
+                Ok(read) => self.partial.extend_from_slice(&buffer[..read]),
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+                Err(err) => return Err(err),
+            }
+        }
+        while let Some(end) = self.partial.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.partial.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(response) = respond(app, apply, &line) {
+                let mut response = serde_json::to_vec(&response)?;
+                response.push(b'\n');
+                self.stream.write_all(&response)?;
+            }
+        }
+        if self.partial.len() > MAX_LINE {
+            return Err(io::Error::other(format!(
+                "request longer than {MAX_LINE} bytes"
+            )));
+        }
+        Ok(connected)
+    }
+}
+
+#[derive(Deserialize)]
+struct Request {
+    jsonrpc: String,
+    /// Notifications have no id and get no response
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+struct Error {
+    code: i64,
+    message: String,
+}
+
+impl Error {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+fn respond(app: &mut App, apply: &mut impl FnMut(&mut App, Action), line: &str) -> Option<Value> {
+    let request: Request = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(err) => {
+            let code = match serde_json::from_str::<Value>(line) {
+                Ok(_) => INVALID_REQUEST,
+                Err(_) => PARSE_ERROR,
+            };
+            return Some(error_response(
+                Value::Null,
+                Error::new(code, err.to_string()),
+            ));
+        }
+    };
+    let result = if request.jsonrpc == "2.0" {
+        call(app, apply, &request.method, request.params)
+    } else {
+        Err(Error::new(
+            INVALID_REQUEST,
+            "Only JSON-RPC 2.0 is supported",
+        ))
+    };
+    let id = request.id?;
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(err) => error_response(id, err),
+    })
+}
+
+fn error_response(id: Value, err: Error) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": err.code, "message": err.message },
+    })
+}
+
+#[derive(Deserialize)]
+#[serde(untagged, deny_unknown_fields)]
+enum BuyParams {
+    Bar { bar: usize, upgrade: save::Upgrade },
+    Global { global: save::GlobalUpgrade },
+    Prestige { prestige: save::PrestigeUpgrade },
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AdjustTransferParams {
+    bar: usize,
+    up: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ChargeBarParams {
+    bar: usize,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ToggleChallengeParams {
+    challenge: save::Challenge,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct UnlockResearchParams {
+    node: save::ResearchNode,
+}
+
+fn params<T: for<'de> Deserialize<'de>>(params: Value) -> Result<T, Error> {
+    serde_json::from_value(params).map_err(|err| Error::new(INVALID_PARAMS, err.to_string()))
+}
+
+fn call(
+    app: &mut App,
+    apply: &mut impl FnMut(&mut App, Action),
+    method: &str,
+    params_value: Value,
+) -> Result<Value, Error> {
+    Ok(match method {
+        "get_state" => json!({
+            "bars": bars(app),
+            "global_upgrades": global_upgrades(app),
+            "prestige": prestige(app),
+            "research": research(app),
+            "random_event": random_event(app),
+        }),
+        "get_bars" => to_value(bars(app)),
+        "get_global_upgrades" => to_value(global_upgrades(app)),
+        "get_prestige" => to_value(prestige(app)),
+        "get_research" => to_value(research(app)),
+        "buy" => buy(app, apply, params(params_value)?)?,
+        "upgrade_any" => {
+            let before = app.events.pushed();
+            apply(app, Action::UpgradeAny);
+            json!({ "purchases": app.events.pushed() - before })
+        }
+        "adjust_transfer" => {
+            let AdjustTransferParams { bar, up } = params(params_value)?;
+            let row = find_bar(app, bar)?;
+            apply(app, Action::AdjustTransfer { row, up });
+            json!({ "transfer_ratio": app.transfer_ratio(row).0 })
+        }
+        "charge_bar" => {
+            let ChargeBarParams { bar } = params(params_value)?;
+            let row = find_bar(app, bar)?;
+            if app.bars[row].charge_cooldown(app.tick).is_some() {
+                return Err(Error::new(REFUSED, "The bar is still cooling down"));
+            }
+            apply(app, Action::ChargeBar { row });
+            json!({ "charge_cooldown": charge_cooldown(app, row) })
+        }
+        "prestige" => {
+            if !app.prestige.can_prestige(app.bars.len()) {
+                return Err(Error::new(REFUSED, "Not enough bars to prestige"));
+            }
+            let points = app.prestige.claimable_prestige(app.bars.len());
+            apply(app, Action::Prestige);
+            json!({ "points": points.0 })
+        }
+        "unlock_research" => {
+            let UnlockResearchParams { node } = params(params_value)?;
+            let node = node.into_game();
+            if !app.research.can_unlock(node) {
+                return Err(Error::new(REFUSED, "Can't research that"));
+            }
+            apply(app, Action::UnlockResearch(node));
+            json!({ "points": app.research.points.0 })
+        }
+        "toggle_challenge" => {
+            let ToggleChallengeParams { challenge } = params(params_value)?;
+            let challenge = challenge.into_game();
+            if !app.prestige.in_challenge(challenge) && !app.prestige.can_prestige(app.bars.len()) {
+                return Err(Error::new(
+                    REFUSED,
+                    "Not enough bars to prestige into a challenge",
+                ));
+            }
+            apply(app, Action::ToggleChallenge(challenge));
+            json!({ "challenge": app.prestige.challenge.map(save::Challenge::from_game) })
+        }
+        _ => {
+            return Err(Error::new(
+                METHOD_NOT_FOUND,
+                format!("Unknown method {method}"),
+            ))
+        }
+    })
+}
+
+fn buy(
+    app: &mut App,
+    apply: &mut impl FnMut(&mut App, Action),
+    params: BuyParams,
+) -> Result<Value, Error> {
+    let cant_afford = || Error::new(REFUSED, "Can't afford that");
+    let level = match params {
+        BuyParams::Bar { bar, upgrade } => {
+            let row = find_bar(app, bar)?;
+            let upgrade = upgrade.into_game();
+            let purchase = Purchase::Bar { upgrade, row };
+            if !GameView::new(app).can_afford(purchase) {
+                return Err(cant_afford());
+            }
+            apply(app, Action::PurchaseUpgrade(purchase));
+            app.bars[row].get_upgrade_u(upgrade)
+        }
+        BuyParams::Global { global } => {
+            let upgrade = global.into_game();
+            let purchase = Purchase::Global { upgrade };
+            if !GameView::new(app).can_afford(purchase) {
+                return Err(cant_afford());
+            }
+            apply(app, Action::PurchaseUpgrade(purchase));
+            app.get_global_upgrade_u(upgrade)
+        }
+        BuyParams::Prestige { prestige } => {
+            let upgrade = prestige.into_game();
+            if !app.prestige.can_afford(upgrade) {
+                return Err(cant_afford());
+            }
+            apply(app, Action::PurchasePrestigeUpgrade(upgrade));
+            app.prestige.level(upgrade)
+        }
+    };
+    Ok(json!({ "level": level }))
+}
+
+fn find_bar(app: &App, number: usize) -> Result<usize, Error> {
+    app.bars
+        .iter()
+        .position(|bar| bar.number == number)
+        .ok_or_else(|| Error::new(INVALID_PARAMS, format!("There is no bar #{number}")))
+}
+
+fn to_value(value: impl Serialize) -> Value {
+    serde_json::to_value(value).expect("Control responses are always valid json")
+}
+
+#[derive(Serialize)]
+struct BarState {
+    number: usize,
+    level: usize,
+    exp: f64,
+    exp_for_next_level: f64,
+    progress: f64,
+    gathered: f64,
+    transfer_ratio: f64,
+    boosted: bool,
+    /// Seconds until the bar can be charged again, absent if it can be now
+    charge_cooldown: Option<f64>,
+    upgrades: Vec<UpgradeState<save::Upgrade>>,
+}
+
+#[derive(Serialize)]
+struct UpgradeState<T> {
+    upgrade: T,
+    level: usize,
+    /// Absent for maxed prestige upgrades and ones locked by a challenge
+    cost: Option<f64>,
+    affordable: bool,
+}
+
+#[derive(Serialize)]
+struct PrestigeState {
+    points: f64,
+    claimable: f64,
+    can_prestige: bool,
+    upgrades: Vec<UpgradeState<save::PrestigeUpgrade>>,
+    challenge: Option<save::Challenge>,
+    completed_challenges: Vec<save::Challenge>,
+}
+
+#[derive(Serialize)]
+struct RandomEventState {
+    event: save::RandomEvent,
+    /// Seconds until the event ends
+    remaining: f64,
+}
+
+#[derive(Serialize)]
+struct ResearchState {
+    points: f64,
+    nodes: Vec<ResearchNodeState>,
+}
+
+#[derive(Serialize)]
+struct ResearchNodeState {
+    node: save::ResearchNode,
+    requires: Vec<save::ResearchNode>,
+    cost: f64,
+    unlocked: bool,
+    affordable: bool,
+}
+
+/// Bars from the newest to the oldest, like on screen.
+fn bars(app: &App) -> Vec<BarState> {
+    app.bars
+        .iter()
+        .enumerate()
+        .map(|(row, bar)| BarState {
+            number: bar.number,
+            level: bar.level,
+            exp: bar.exp.0,
+            exp_for_next_level: bar.exp_for_next_level().0,
+            progress: bar.progress.0,
+            gathered: bar.gathered.0,
+            transfer_ratio: app.transfer_ratio(row).0,
+            boosted: bar.is_boosted(app.tick),
+            charge_cooldown: charge_cooldown(app, row),
+            upgrades: Upgrade::iter()
+                .map(|upgrade| UpgradeState {
+                    upgrade: save::Upgrade::from_game(&upgrade),
+                    level: bar.get_upgrade_u(upgrade),
+                    cost: app.upgrade_cost(row, upgrade).map(|cost| cost.0),
+                    affordable: app.can_afford(row, upgrade),
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+fn charge_cooldown(app: &App, row: usize) -> Option<f64> {
+    app.bars[row]
+        .charge_cooldown(app.tick)
+        .map(|cooldown| cooldown.as_secs_f64())
+}
+
+fn global_upgrades(app: &App) -> Vec<UpgradeState<save::GlobalUpgrade>> {
+    GlobalUpgrade::iter()
+        .map(|upgrade| UpgradeState {
+            upgrade: save::GlobalUpgrade::from_game(upgrade),
+            level: app.get_global_upgrade_u(upgrade),
+            cost: app.global_upgrade_cost(upgrade).map(|cost| cost.0),
+            affordable: app.can_afford_global(upgrade),
+        })
+        .collect()
+}
+
+fn prestige(app: &App) -> PrestigeState {
+    let prestige = &app.prestige;
+    PrestigeState {
+        points: prestige.current.0,
+        claimable: prestige.claimable_prestige(app.bars.len()).0,
+        can_prestige: prestige.can_prestige(app.bars.len()),
+        upgrades: crate::prestige::PrestigeUpgrade::iter()
+            .map(|upgrade| UpgradeState {
+                upgrade: save::PrestigeUpgrade::from_game(&upgrade),
+                level: prestige.level(upgrade),
+                cost: (!prestige.is_max_level(upgrade)).then(|| prestige.cost(upgrade).0),
+                affordable: prestige.can_afford(upgrade),
+            })
+            .collect(),
+        challenge: prestige.challenge.map(save::Challenge::from_game),
+        completed_challenges: crate::challenge::Challenge::iter()
+            .filter(|&challenge| prestige.has_reward(challenge))
+            .map(save::Challenge::from_game)
+            .collect(),
+    }
+}
+
+fn random_event(app: &App) -> Option<RandomEventState> {
+    app.random_events
+        .remaining(app.tick)
+        .map(|(event, remaining)| RandomEventState {
+            event: save::RandomEvent::from_game(event),
+            remaining: remaining.as_secs_f64(),
+        })
+}
+
+fn research(app: &App) -> ResearchState {
+    let research = &app.research;
+    ResearchState {
+        points: research.points.0,
+        nodes: crate::research::ResearchNode::iter()
+            .map(|node| ResearchNodeState {
+                node: save::ResearchNode::from_game(node),
+                requires: node
+                    .requires()
+                    .iter()
+                    .map(|&node| save::ResearchNode::from_game(node))
+                    .collect(),
+                cost: node.cost().0,
+                unlocked: research.is_unlocked(node),
+                affordable: research.can_unlock(node),
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::float::Float;
+    use std::io::{BufRead, BufReader};
+    use std::time::Duration;
+
+    /// Sends `request` over the socket like another program would, and
+    /// returns the response.
+    fn request(socket: &mut ControlSocket, app: &mut App, request: Value) -> Value {
+        let mut client = UnixStream::connect(&socket.path).unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_millis(10)))
+            .unwrap();
+        writeln!(client, "{request}").unwrap();
+        let mut client = BufReader::new(client);
+        let mut response = String::new();
+        for _ in 0..500 {
+            socket.poll(app, |app, action| {
+                app.apply_action(action, &mut crate::strategy::Greedy)
+            });
+            if client.read_line(&mut response).is_ok() {
+                return serde_json::from_str(&response).unwrap();
+            }
+        }
+        panic!("No response to {request}");
+    }
+
+    fn bind(test: &str) -> ControlSocket {
+        let path = std::env::temp_dir().join(format!("antsy-test-{test}.sock"));
+        ControlSocket::bind(path.to_str().unwrap()).unwrap()
+    }
+
+    fn app_with_bars(count: usize) -> App {
+        let mut app = App::for_test();
+        app.bars_to_spawn = 0;
+        for _ in 0..count {
+            app.spawn_bar();
+        }
+        app
+    }
+
+    #[test]
+    fn queries_describe_the_game() {
+        let mut socket = bind("queries");
+        let mut app = app_with_bars(2);
+        app.bars[1].gathered = Float(12.5);
+
+        let response = request(
+            &mut socket,
+            &mut app,
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "get_state" }),
+        );
+        assert_eq!(response["id"], 1);
+        let bars = &response["result"]["bars"];
+        assert_eq!(bars[1]["number"], 1);
+        assert_eq!(bars[1]["gathered"], 12.5);
+        assert_eq!(bars[1]["upgrades"][2]["upgrade"], "Double");
+        assert_eq!(response["result"]["prestige"]["can_prestige"], false);
+    }
+
+    #[test]
+    fn commands_change_the_game() {
+        let mut socket = bind("commands");
+        let mut app = app_with_bars(2);
+
+        let buy = json!({
+            "jsonrpc": "2.0",
+            "id": "buy",
+            "method": "buy",
+            "params": { "bar": 2, "upgrade": "Gain" },
+        });
+        let response = request(&mut socket, &mut app, buy.clone());
+        assert_eq!(response["error"]["code"], REFUSED);
+
+        app.bars[0].gathered = Float(1e6);
+        let response = request(&mut socket, &mut app, buy);
+        assert_eq!(response["result"]["level"], 1);
+        assert_eq!(app.bars[0].get_upgrade_u(Upgrade::Gain), 1);
+
+        let response = request(
+            &mut socket,
+            &mut app,
+            json!({ "jsonrpc": "2.0", "id": 3, "method": "jump" }),
+        );
+        assert_eq!(response["error"]["code"], METHOD_NOT_FOUND);
+        let response = request(&mut socket, &mut app, json!("not a request"));
+        assert_eq!(response["error"]["code"], INVALID_REQUEST);
+    }
+
+    #[test]
+    fn only_sockets_are_replaced() {
+        let path = std::env::temp_dir().join("antsy-test-not-a-socket");
+        std::fs::write(&path, "keep me").unwrap();
+        assert!(ControlSocket::bind(path.to_str().unwrap()).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "keep me");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn overlong_requests_drop_the_client() {
+        let mut socket = bind("overlong");
+        let mut app = app_with_bars(1);
+        let mut client = UnixStream::connect(&socket.path).unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_millis(10)))
+            .unwrap();
+        client.write_all(&vec![b'x'; MAX_LINE + 1]).unwrap();
+        for _ in 0..500 {
+            socket.poll(&mut app, |app, action| {
+                app.apply_action(action, &mut crate::strategy::Greedy)
+            });
+            if let Ok(0) = client.read(&mut [0; 16]) {
+                return;
+            }
+        }
+        panic!("The client wasn't dropped");
+    }
+}
@@ -1,23 +1,64 @@
-use crossterm::event::KeyEvent;
+use crossterm::event::{KeyCode, KeyEvent};
 
+use crate::challenge::Challenge;
+use crate::prestige::PrestigeUpgrade;
+use crate::research::ResearchNode;
+use crate::strategy::Purchase;
 use crate::ui::{Ui, UiState};
 
+mod console;
 mod normal;
 mod prestige;
+mod profiles;
+mod research;
+mod share;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Clone)]
 pub(crate) enum Action {
     Quit,
     Noop,
-    PurchaseUpgrade,
-    PurchasePrestigeUpgrade,
+    PurchaseUpgrade(Purchase),
+    PurchasePrestigeUpgrade(PrestigeUpgrade),
     Prestige,
+    /// Start a run under the challenge, or abandon it if it's the current one
+    ToggleChallenge(Challenge),
     UpgradeAny,
+    UnlockResearch(ResearchNode),
+    /// Raise or lower how much the bar in `row` transfers to the next one
+    AdjustTransfer {
+        row: usize,
+        up: bool,
+    },
+    /// Boost the bar in `row` by hand, if it isn't cooling down
+    ChargeBar {
+        row: usize,
+    },
+    Export,
+    /// Replace the game with the save in a save string
+    Import(String),
+    /// A command typed into the developer console
+    DevCommand(String),
 }
 
+/// What the player chose in the profile picker. Profiles are referred to
+/// by name.
+#[derive(Debug, Clone)]
+pub(crate) enum PickerAction {
+    Quit,
+    Noop,
+    Play(String),
+    Create(String),
+    Rename(String, String),
+    Duplicate(String, String),
+    Delete(String),
+}
+
+#[allow(clippy::enum_variant_names)]
 enum UiToggle {
     ToNormal,
     ToPrestige,
+    ToResearch,
+    ToShare,
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -34,10 +75,22 @@ impl Ui {
         key: KeyEvent,
         bar_len: usize,
         prestige_upgrade_len: usize,
+        event_len: usize,
     ) -> Action {
+        if let Some(console) = &mut self.console {
+            if console.open {
+                return console.handle_keypress(key);
+            }
+            if key.code == KeyCode::Char('`') {
+                console.open = true;
+                return Action::Noop;
+            }
+        }
         let res = match &mut self.state {
-            UiState::Normal(normal) => normal.handle_keypress(key, bar_len),
+            UiState::Normal(normal) => normal.handle_keypress(key, bar_len, event_len),
             UiState::Prestige(prestige) => prestige.handle_keypress(key, prestige_upgrade_len),
+            UiState::Research(research) => research.handle_keypress(key),
+            UiState::Share(share) => share.handle_keypress(key),
         };
         match res {
             Err(UiToggle::ToNormal) => {
@@ -48,6 +101,14 @@ impl Ui {
                 self.to_prestige();
                 Action::Noop
             }
+            Err(UiToggle::ToResearch) => {
+                self.to_research();
+                Action::Noop
+            }
+            Err(UiToggle::ToShare) => {
+                self.to_share();
+                Action::Noop
+            }
             Ok(action) => action,
         }
     }
@@ -0,0 +1,21 @@
+use crossterm::event::{KeyCode, KeyEvent};
+
+use crate::controls::Action;
+use crate::ui::Console;
+
+impl Console {
+    pub(super) fn handle_keypress(&mut self, key: KeyEvent) -> Action {
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('`') => self.open = false,
+            KeyCode::Char(c) => self.input.push(c),
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            KeyCode::Enter if !self.input.is_empty() => {
+                return Action::DevCommand(std::mem::take(&mut self.input))
+            }
+            _ => (),
+        }
+        Action::Noop
+    }
+}
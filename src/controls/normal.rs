@@ -9,17 +9,43 @@ impl ui::Normal {
         &mut self,
         key: KeyEvent,
         bar_len: usize,
+        event_len: usize,
     ) -> Result<Action, UiToggle> {
         match key.code {
             KeyCode::Char('p') => return Err(UiToggle::ToPrestige),
+            KeyCode::Char('r') => return Err(UiToggle::ToResearch),
+            KeyCode::Char('s') => return Err(UiToggle::ToShare),
             KeyCode::Char('q') => return Ok(Action::Quit),
             KeyCode::Char('u') => return Ok(Action::UpgradeAny),
-            KeyCode::Enter | KeyCode::Char(' ') => return Ok(Action::PurchaseUpgrade),
+            KeyCode::Enter | KeyCode::Char(' ') => {
+                return Ok(self
+                    .highlight
+                    .purchase()
+                    .map_or(Action::Noop, Action::PurchaseUpgrade))
+            }
+            KeyCode::Char('+') | KeyCode::Char('=') | KeyCode::Char('-') => {
+                if let ui::normal::Highlight::Bar { row, .. } = self.highlight {
+                    let up = key.code != KeyCode::Char('-');
+                    return Ok(Action::AdjustTransfer { row, up });
+                }
+            }
+            KeyCode::Char('c') => {
+                if let ui::normal::Highlight::Bar { row, .. } = self.highlight {
+                    return Ok(Action::ChargeBar { row });
+                }
+            }
             KeyCode::Tab => self.change_highlight_pane(bar_len),
             KeyCode::Down => self.move_highlight(bar_len, Dir::Down),
             KeyCode::Up => self.move_highlight(bar_len, Dir::Up),
             KeyCode::Right => self.move_highlight(bar_len, Dir::Right),
             KeyCode::Left => self.move_highlight(bar_len, Dir::Left),
+            KeyCode::PageUp => {
+                let oldest_page = event_len.saturating_sub(ui::normal::LOG_LINES);
+                self.log_scroll = (self.log_scroll + ui::normal::LOG_LINES).min(oldest_page);
+            }
+            KeyCode::PageDown => {
+                self.log_scroll = self.log_scroll.saturating_sub(ui::normal::LOG_LINES)
+            }
             _ => (),
         };
         Ok(Action::Noop)
@@ -1,5 +1,7 @@
 use crossterm::event::{KeyCode, KeyEvent};
+use strum::EnumCount;
 
+use crate::challenge;
 use crate::controls::{Action, Dir, UiToggle};
 use crate::ui;
 use crate::ui::prestige::Highlight;
@@ -45,6 +47,14 @@ impl ui::Prestige {
             },
             PrestigeButton => match dir {
                 Dir::Left | Dir::Right => Upgrade(0),
+                Dir::Down => Challenge(0),
+                Dir::Up => Challenge(challenge::Challenge::COUNT - 1),
+            },
+            // The challenges are listed under the prestige button
+            Challenge(row) => match dir {
+                Dir::Left | Dir::Right => Upgrade(0),
+                Dir::Down if row + 1 < challenge::Challenge::COUNT => Challenge(row + 1),
+                Dir::Up if row > 0 => Challenge(row - 1),
                 Dir::Down | Dir::Up => PrestigeButton,
             },
         };
@@ -56,7 +66,14 @@ impl ui::Prestige {
         Ok(match self.highlight {
             Highlight::None => Action::Noop,
             Highlight::PrestigeButton => Action::Prestige,
-            Highlight::Upgrade(_) => Action::PurchasePrestigeUpgrade,
+            Highlight::Upgrade(_) => self
+                .highlight
+                .upgrade()
+                .map_or(Action::Noop, Action::PurchasePrestigeUpgrade),
+            Highlight::Challenge(_) => self
+                .highlight
+                .challenge()
+                .map_or(Action::Noop, Action::ToggleChallenge),
         })
     }
 }
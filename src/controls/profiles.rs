@@ -0,0 +1,141 @@
+use crossterm::event::{KeyCode, KeyEvent};
+
+use crate::controls::PickerAction;
+use crate::ui::profiles::{Picker, Prompt};
+
+impl Picker {
+    /// `names` are the listed profiles, in the order they are shown.
+    pub(crate) fn handle_keypress(&mut self, key: KeyEvent, names: &[String]) -> PickerAction {
+        self.message = None;
+        match self.prompt.take() {
+            None => self.handle_list_keypress(key, names),
+            Some(prompt) => self.handle_prompt_keypress(key, prompt, names),
+        }
+    }
+
+    fn handle_list_keypress(&mut self, key: KeyEvent, names: &[String]) -> PickerAction {
+        let selected = names.get(self.highlight).is_some();
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return PickerAction::Quit,
+            KeyCode::Char('n') => {
+                self.prompt = Some(Prompt::Create {
+                    name: String::new(),
+                })
+            }
+            KeyCode::Char('r') if selected => {
+                self.prompt = Some(Prompt::Rename {
+                    name: names[self.highlight].clone(),
+                })
+            }
+            KeyCode::Char('d') if selected => {
+                self.prompt = Some(Prompt::Duplicate {
+                    name: format!("{} copy", names[self.highlight]),
+                })
+            }
+            KeyCode::Char('x') | KeyCode::Delete if selected => {
+                self.prompt = Some(Prompt::ConfirmDelete)
+            }
+            KeyCode::Enter | KeyCode::Char(' ') if selected => {
+                return PickerAction::Play(names[self.highlight].clone())
+            }
+            KeyCode::Down if !names.is_empty() => {
+                self.highlight = (self.highlight + 1) % names.len()
+            }
+            KeyCode::Up if !names.is_empty() => {
+                self.highlight = self.highlight.checked_sub(1).unwrap_or(names.len() - 1)
+            }
+            _ => (),
+        }
+        PickerAction::Noop
+    }
+
+    fn handle_prompt_keypress(
+        &mut self,
+        key: KeyEvent,
+        mut prompt: Prompt,
+        names: &[String],
+    ) -> PickerAction {
+        let selected = || names.get(self.highlight).cloned().unwrap_or_default();
+        if key.code == KeyCode::Esc {
+            return PickerAction::Noop;
+        }
+        let name = match &mut prompt {
+            Prompt::ConfirmDelete => {
+                return match key.code {
+                    KeyCode::Char('y') => PickerAction::Delete(selected()),
+                    _ => PickerAction::Noop,
+                };
+            }
+            Prompt::Create { name } | Prompt::Rename { name } | Prompt::Duplicate { name } => name,
+        };
+        match key.code {
+            KeyCode::Char(c) => name.push(c),
+            KeyCode::Backspace => {
+                name.pop();
+            }
+            KeyCode::Enter => {
+                return match prompt {
+                    Prompt::Create { name } => PickerAction::Create(name),
+                    Prompt::Rename { name } => PickerAction::Rename(selected(), name),
+                    Prompt::Duplicate { name } => PickerAction::Duplicate(selected(), name),
+                    Prompt::ConfirmDelete => unreachable!(),
+                };
+            }
+            _ => (),
+        }
+        self.prompt = Some(prompt);
+        PickerAction::Noop
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::KeyModifiers;
+
+    fn press(picker: &mut Picker, code: KeyCode, names: &[String]) -> PickerAction {
+        picker.handle_keypress(KeyEvent::new(code, KeyModifiers::NONE), names)
+    }
+
+    fn type_text(picker: &mut Picker, text: &str, names: &[String]) {
+        for c in text.chars() {
+            press(picker, KeyCode::Char(c), names);
+        }
+    }
+
+    #[test]
+    fn create_prompts_for_a_name() {
+        let mut picker = Picker::new();
+        press(&mut picker, KeyCode::Char('n'), &[]);
+        type_text(&mut picker, "abcd", &[]);
+        press(&mut picker, KeyCode::Backspace, &[]);
+        let action = press(&mut picker, KeyCode::Enter, &[]);
+        assert!(matches!(action, PickerAction::Create(name) if name == "abc"));
+        assert!(picker.prompt.is_none());
+    }
+
+    #[test]
+    fn rename_acts_on_the_highlighted_profile() {
+        let names = ["a".to_owned(), "b".to_owned()];
+        let mut picker = Picker::new();
+        press(&mut picker, KeyCode::Up, &names);
+        press(&mut picker, KeyCode::Char('r'), &names);
+        type_text(&mut picker, "2", &names);
+        let action = press(&mut picker, KeyCode::Enter, &names);
+        assert!(matches!(action, PickerAction::Rename(from, to) if from == "b" && to == "b2"));
+    }
+
+    #[test]
+    fn delete_needs_confirmation() {
+        let names = ["a".to_owned()];
+        let mut picker = Picker::new();
+        press(&mut picker, KeyCode::Char('x'), &names);
+        assert!(matches!(
+            press(&mut picker, KeyCode::Char('n'), &names),
+            PickerAction::Noop
+        ));
+        press(&mut picker, KeyCode::Char('x'), &names);
+        let action = press(&mut picker, KeyCode::Char('y'), &names);
+        assert!(matches!(action, PickerAction::Delete(name) if name == "a"));
+    }
+}
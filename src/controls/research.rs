@@ -0,0 +1,39 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use strum::*;
+
+use crate::controls::{Action, UiToggle};
+use crate::research::ResearchNode;
+use crate::ui;
+
+impl ui::Research {
+    /// Up and down go through the nodes as listed, left to the node this
+    /// one requires and right to the first node requiring it.
+    pub(super) fn handle_keypress(&mut self, key: KeyEvent) -> Result<Action, UiToggle> {
+        let nodes: Vec<_> = ResearchNode::iter().collect();
+        let i = nodes
+            .iter()
+            .position(|&node| node == self.highlight)
+            .unwrap();
+        match key.code {
+            KeyCode::Char('r') => return Err(UiToggle::ToNormal),
+            KeyCode::Char('q') => return Ok(Action::Quit),
+            KeyCode::Enter | KeyCode::Char(' ') => {
+                return Ok(Action::UnlockResearch(self.highlight))
+            }
+            KeyCode::Down => self.highlight = nodes[(i + 1) % nodes.len()],
+            KeyCode::Up => self.highlight = nodes[(i + nodes.len() - 1) % nodes.len()],
+            KeyCode::Left => {
+                if let Some(&parent) = self.highlight.requires().first() {
+                    self.highlight = parent;
+                }
+            }
+            KeyCode::Right => {
+                if let Some(child) = self.highlight.unlocks().next() {
+                    self.highlight = child;
+                }
+            }
+            _ => (),
+        }
+        Ok(Action::Noop)
+    }
+}
@@ -0,0 +1,58 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::controls::{Action, UiToggle};
+use crate::ui;
+
+impl ui::Share {
+    /// Everything typed goes into the import field, so leaving is on Esc
+    /// and exporting on Ctrl+E. Importing asks for confirmation first.
+    pub(super) fn handle_keypress(&mut self, key: KeyEvent) -> Result<Action, UiToggle> {
+        if self.confirm_import {
+            self.confirm_import = false;
+            return Ok(match key.code {
+                KeyCode::Char('y') => Action::Import(std::mem::take(&mut self.input)),
+                _ => Action::Noop,
+            });
+        }
+        match key.code {
+            KeyCode::Esc => return Err(UiToggle::ToNormal),
+            KeyCode::Char('e') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                return Ok(Action::Export)
+            }
+            KeyCode::Char(c) => self.input.push(c),
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            KeyCode::Enter if !self.input.is_empty() => self.confirm_import = true,
+            _ => (),
+        }
+        Ok(Action::Noop)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(share: &mut ui::Share, code: KeyCode) -> Action {
+        share
+            .handle_keypress(KeyEvent::new(code, KeyModifiers::NONE))
+            .unwrap_or_else(|_| panic!("Left the share screen"))
+    }
+
+    #[test]
+    fn import_needs_confirmation() {
+        let mut share = ui::Share {
+            input: "antsy1".to_owned(),
+            ..Default::default()
+        };
+        press(&mut share, KeyCode::Enter);
+        assert!(matches!(press(&mut share, KeyCode::Esc), Action::Noop));
+        assert_eq!(share.input, "antsy1");
+
+        press(&mut share, KeyCode::Enter);
+        let action = press(&mut share, KeyCode::Char('y'));
+        assert!(matches!(action, Action::Import(code) if code == "antsy1"));
+        assert!(share.input.is_empty());
+    }
+}
@@ -0,0 +1,59 @@
+use std::panic;
+use std::time::{Duration, Instant};
+
+use crate::app::App;
+use crate::save;
+
+/// How often the state kept for an emergency save is refreshed.
+const LAST_GOOD_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The game as of a recent tick, kept to be written if the game panics.
+#[derive(Default)]
+pub(crate) struct LastGood {
+    save: Option<save::App>,
+    at: Option<Instant>,
+}
+
+impl LastGood {
+    /// Keeps a copy of `app` if the kept one is older than
+    /// `LAST_GOOD_INTERVAL`. Nothing is kept when the game isn't saved.
+    pub(crate) fn refresh(&mut self, app: &App, now: Instant) {
+        if app.opts.save_file.is_some()
+            && !app.opts.no_save
+            && self.at.is_none_or(|at| now - at >= LAST_GOOD_INTERVAL)
+        {
+            self.save = Some(save::App::from_game(app));
+            self.at = Some(now);
+        }
+    }
+}
+
+/// Runs a game loop, writing the state it last kept in `LastGood` to
+/// `save_file` if it panics.
+pub(crate) fn catch_panics(
+    save_file: Option<&str>,
+    run: impl FnOnce(&mut LastGood) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let mut last_good = LastGood::default();
+    match panic::catch_unwind(panic::AssertUnwindSafe(|| run(&mut last_good))) {
+        Ok(res) => res,
+        Err(_) => Err(emergency_save(save_file, last_good.save)),
+    }
+}
+
+/// Writes the state kept from a recent tick after the game panicked, and
+/// returns what to tell the player.
+fn emergency_save(save_file: Option<&str>, last_good: Option<save::App>) -> anyhow::Error {
+    let (Some(path), Some(save)) = (save_file, last_good) else {
+        return anyhow::anyhow!("The game crashed");
+    };
+    match save.write(path) {
+        Ok(()) => {
+            log::info!("Emergency save written to {path}");
+            anyhow::anyhow!(
+                "The game crashed, progress up to a few seconds before was saved to {path}"
+            )
+        }
+        Err(err) => anyhow::anyhow!("The game crashed, and saving failed: {err:#}"),
+    }
+}
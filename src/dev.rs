@@ -0,0 +1,238 @@
+use anyhow::Context;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use crate::app::App;
+use crate::bar::Bar;
+use crate::float::Float;
+use crate::upgrade::GlobalUpgrade;
+
+const HELP: &str = "give BAR [+]AMOUNT, level BAR [+]LEVELS, prestige-points [+]POINTS, \
+                    research-points [+]POINTS, spawn COUNT, timewarp DURATION, \
+                    set-speed MULTIPLIER, random-event";
+
+const MAX_TIMEWARP: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+const MAX_SPAWN: usize = 1000;
+
+/// Runs a developer console command against `app`, returning what to show
+/// in the console. Bars are referred to by their number.
+pub(crate) fn run(app: &mut App, command: &str) -> anyhow::Result<String> {
+    let args: Vec<&str> = command.split_whitespace().collect();
+    match args[..] {
+        ["give", bar, amount] => {
+            let bar = bar_mut(&mut app.bars, bar)?;
+            let gathered = relative(bar.gathered.0, amount)?;
+            if gathered < 0. {
+                anyhow::bail!("#{} can't have less than 0", bar.number);
+            }
+            bar.gathered = Float(gathered);
+            Ok(format!("#{} has {}", bar.number, bar.gathered))
+        }
+        ["level", bar, levels] => {
+            let global_speed_levels = app.get_global_upgrade_u(GlobalUpgrade::Speed);
+            let bar = bar_mut(&mut app.bars, bar)?;
+            let max_level = Bar::max_level() as f64;
+            bar.level = relative(bar.level as f64, levels)?.clamp(1., max_level) as usize;
+            bar.exp = Float(0.);
+            bar.level_speed = Bar::level_speed_for(bar.level, &app.research);
+            bar.gain_exponent = 0;
+            bar.gain_exponent = bar.settled_gain_exponent(global_speed_levels, &app.research);
+            Ok(format!("#{} is level {}", bar.number, bar.level))
+        }
+        ["prestige-points", points] => {
+            app.prestige.current = Float(relative(app.prestige.current.0, points)?.max(0.));
+            Ok(format!("{} prestige points", app.prestige.current))
+        }
+        ["research-points", points] => {
+            app.research.points = Float(relative(app.research.points.0, points)?.max(0.));
+            Ok(format!("{} research points", app.research.points))
+        }
+        ["spawn", count] => {
+            let count: usize = parse(count)?;
+            if count > MAX_SPAWN {
+                anyhow::bail!("At most {MAX_SPAWN} bars can be spawned at once");
+            }
+            for _ in 0..count {
+                app.spawn_bar();
+            }
+            Ok(format!("Spawned {count} bars"))
+        }
+        ["timewarp", duration] => {
+            let parsed = parse_duration(duration)?;
+            if parsed > MAX_TIMEWARP {
+                app.timewarp(MAX_TIMEWARP);
+                return Ok("Skipped 365d, the most one timewarp can".to_owned());
+            }
+            app.timewarp(parsed);
+            Ok(format!("Skipped {duration}"))
+        }
+        ["set-speed", speed] => {
+            let speed: f64 = parse(speed)?;
+            if !speed.is_finite() || speed <= 0. {
+                anyhow::bail!("Speed has to be positive");
+            }
+            app.set_speed(speed);
+            Ok(format!("Game runs at x{speed}"))
+        }
+        ["random-event"] => {
+            if app.opts.no_random_events {
+                anyhow::bail!("Random events are off");
+            }
+            app.random_events.active = None;
+            app.random_events.next_at = app.tick;
+            Ok("The next random event starts now".to_owned())
+        }
+        [] | ["help"] => Ok(HELP.to_owned()),
+        _ => anyhow::bail!("Unknown command, try {HELP}"),
+    }
+}
+
+fn parse<T: std::str::FromStr>(value: &str) -> anyhow::Result<T> {
+    value
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid number {value}"))
+}
+
+/// `value` either replaces `current`, or is added to it if it starts
+/// with + or -.
+fn relative(current: f64, value: &str) -> anyhow::Result<f64> {
+    let parsed: f64 = parse(value)?;
+    if !parsed.is_finite() {
+        anyhow::bail!("Invalid number {value}");
+    }
+    Ok(if value.starts_with(['+', '-']) {
+        current + parsed
+    } else {
+        parsed
+    })
+}
+
+fn bar_mut<'a>(bars: &'a mut VecDeque<Bar>, number: &str) -> anyhow::Result<&'a mut Bar> {
+    let number: usize = parse(number.trim_start_matches('#'))?;
+    bars.iter_mut()
+        .find(|bar| bar.number == number)
+        .with_context(|| format!("There is no bar #{number}"))
+}
+
+/// Parses durations like `90s`, `15m`, `1.5h` or `2d`.
+fn parse_duration(value: &str) -> anyhow::Result<Duration> {
+    let split = value
+        .find(|c: char| c.is_alphabetic())
+        .unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+    let secs = match unit {
+        "s" | "" => 1.,
+        "m" => 60.,
+        "h" => 60. * 60.,
+        "d" => 24. * 60. * 60.,
+        _ => anyhow::bail!("Unknown unit {unit}, use s, m, h or d"),
+    };
+    let amount: f64 = parse(amount)?;
+    Duration::try_from_secs_f64(amount * secs).with_context(|| format!("Invalid duration {value}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app_with_bars(count: usize) -> App {
+        let mut app = App::for_test();
+        run(&mut app, &format!("spawn {count}")).unwrap();
+        app
+    }
+
+    #[test]
+    fn give_and_level_act_on_bar_numbers() {
+        let mut app = app_with_bars(3);
+        run(&mut app, "give 1 1e6").unwrap();
+        assert_eq!(app.bars[2].gathered, Float(1e6));
+
+        run(&mut app, "level #2 +10").unwrap();
+        assert_eq!(app.bars[1].level, 11);
+        assert_eq!(
+            app.bars[1].level_speed,
+            Bar::level_speed_for(11, &app.research)
+        );
+        run(&mut app, "level 2 -100").unwrap();
+        assert_eq!(app.bars[1].level, 1);
+
+        assert!(run(&mut app, "give 9 1").is_err());
+    }
+
+    #[test]
+    fn give_rejects_invalid_totals() {
+        let mut app = app_with_bars(1);
+        run(&mut app, "give 1 10").unwrap();
+        run(&mut app, "give 1 +5").unwrap();
+        assert_eq!(app.bars[0].gathered, Float(15.));
+        for amount in ["nan", "-inf", "-20", "+inf"] {
+            assert!(run(&mut app, &format!("give 1 {amount}")).is_err());
+        }
+        assert_eq!(app.bars[0].gathered, Float(15.));
+    }
+
+    #[test]
+    fn levels_are_capped() {
+        let mut app = app_with_bars(1);
+        run(&mut app, "level 1 1e18").unwrap();
+        assert_eq!(app.bars[0].level, Bar::max_level());
+        assert!(app.bars[0].level_speed.0.is_finite());
+    }
+
+    #[test]
+    fn spawn_is_capped() {
+        let mut app = App::for_test();
+        assert!(run(&mut app, "spawn 1000000000").is_err());
+        assert!(app.bars.is_empty());
+    }
+
+    #[test]
+    fn prestige_points_can_be_set_or_added() {
+        let mut app = App::for_test();
+        run(&mut app, "prestige-points 50").unwrap();
+        run(&mut app, "prestige-points +5").unwrap();
+        assert_eq!(app.prestige.current, Float(55.));
+    }
+
+    #[test]
+    fn timewarp_plays_the_time() {
+        let mut app = App::for_test();
+        let start = app.tick;
+        run(&mut app, "timewarp 1m").unwrap();
+        assert_eq!(app.tick, start + Duration::from_secs(60));
+        assert!(!app.bars.is_empty());
+
+        // Real time continues from where it was
+        app.on_tick(start + Duration::from_secs(1));
+        assert_eq!(app.tick, start + Duration::from_secs(61));
+    }
+
+    #[test]
+    fn timewarp_is_clamped() {
+        let mut app = App::for_test();
+        let start = app.tick;
+        let reply = run(&mut app, "timewarp 3650d").unwrap();
+        assert_eq!(reply, "Skipped 365d, the most one timewarp can");
+        assert_eq!(app.tick, start + MAX_TIMEWARP);
+    }
+
+    #[test]
+    fn set_speed_scales_time() {
+        let mut app = App::for_test();
+        let start = app.tick;
+        run(&mut app, "set-speed 10").unwrap();
+        app.on_tick(start + Duration::from_secs(2));
+        assert_eq!(app.tick, start + Duration::from_secs(20));
+        assert!(run(&mut app, "set-speed 0").is_err());
+    }
+
+    #[test]
+    fn parse_durations() {
+        assert_eq!(parse_duration("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("1.5h").unwrap(), Duration::from_secs(5400));
+        assert_eq!(parse_duration("2d").unwrap(), Duration::from_secs(172800));
+        assert!(parse_duration("1y").is_err());
+        assert!(parse_duration("-1s").is_err());
+    }
+}
@@ -0,0 +1,226 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::Duration;
+
+use crate::challenge::Challenge;
+use crate::float::Float;
+use crate::prestige::PrestigeUpgrade;
+use crate::random_events::RandomEvent;
+use crate::research::ResearchNode;
+use crate::upgrade::{GlobalUpgrade, Upgrade};
+
+/// How many events are kept for the log pane.
+const CAPACITY: usize = 200;
+
+/// Something that happened in the game, shown in the log pane.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Event {
+    BarSpawned {
+        number: usize,
+    },
+    LevelUp {
+        number: usize,
+        level: usize,
+        boost: Duration,
+    },
+    BarCharged {
+        number: usize,
+        boost: Duration,
+    },
+    UpgradePurchased {
+        number: usize,
+        upgrade: Upgrade,
+        level: usize,
+    },
+    GlobalUpgradePurchased {
+        upgrade: GlobalUpgrade,
+        level: usize,
+        automated: bool,
+    },
+    PrestigeUpgradePurchased {
+        upgrade: PrestigeUpgrade,
+        level: usize,
+    },
+    Prestiged {
+        points: Float,
+    },
+    ChallengeStarted {
+        challenge: Challenge,
+    },
+    ChallengeCompleted {
+        challenge: Challenge,
+    },
+    ChallengeAbandoned {
+        challenge: Challenge,
+    },
+    ResearchUnlocked {
+        node: ResearchNode,
+    },
+    RandomEventStarted {
+        event: RandomEvent,
+    },
+    Saved,
+    SaveFailed(String),
+}
+
+impl Event {
+    /// How important the event is in the log file.
+    fn level(&self) -> log::Level {
+        match self {
+            Event::BarSpawned { .. } | Event::LevelUp { .. } | Event::BarCharged { .. } => {
+                log::Level::Debug
+            }
+            Event::SaveFailed(_) => log::Level::Error,
+            _ => log::Level::Info,
+        }
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::BarSpawned { number } => write!(f, "Bar #{number} spawned"),
+            Event::LevelUp {
+                number,
+                level,
+                boost,
+            } => write!(
+                f,
+                "#{number} reached L{level}, boosted for {}s",
+                boost.as_secs()
+            ),
+            Event::BarCharged { number, boost } => {
+                write!(f, "Charged #{number}, boosted for {}s", boost.as_secs())
+            }
+            Event::UpgradePurchased {
+                number,
+                upgrade,
+                level,
+            } => write!(f, "Bought {upgrade:?} level {level} for #{number}"),
+            Event::GlobalUpgradePurchased {
+                upgrade,
+                level,
+                automated,
+            } => {
+                let by = if *automated {
+                    "Automation bought"
+                } else {
+                    "Bought"
+                };
+                write!(f, "{by} global {upgrade:?} level {level}")
+            }
+            Event::PrestigeUpgradePurchased { upgrade, level } => {
+                write!(f, "Bought prestige {upgrade:?} level {level}")
+            }
+            Event::Prestiged { points } => write!(f, "Prestiged for {points} points"),
+            Event::ChallengeStarted { challenge } => {
+                write!(f, "Started the {} challenge", challenge.name())
+            }
+            Event::ChallengeCompleted { challenge } => write!(
+                f,
+                "Completed the {} challenge: {}",
+                challenge.name(),
+                challenge.reward()
+            ),
+            Event::ChallengeAbandoned { challenge } => {
+                write!(f, "Abandoned the {} challenge", challenge.name())
+            }
+            Event::ResearchUnlocked { node } => {
+                write!(f, "Researched {}: {}", node.name(), node.description())
+            }
+            Event::RandomEventStarted { event } => write!(f, "{}", event.description()),
+            Event::Saved => write!(f, "Saved"),
+            Event::SaveFailed(err) => write!(f, "Save failed: {err}"),
+        }
+    }
+}
+
+/// The most recent events, oldest first.
+#[derive(Clone, Default)]
+pub(crate) struct EventLog {
+    events: VecDeque<Event>,
+    /// Every event ever pushed, including the dropped ones
+    pushed: usize,
+}
+
+impl EventLog {
+    /// Adds `event` to the log pane and the log file.
+    pub(crate) fn push(&mut self, event: Event) {
+        log::log!(event.level(), "{event}");
+        if self.events.len() == CAPACITY {
+            self.events.pop_front();
+        }
+        self.events.push_back(event);
+        self.pushed += 1;
+    }
+
+    /// How many events have been pushed, so that callers can tell which
+    /// ones they haven't seen.
+    pub(crate) fn pushed(&self) -> usize {
+        self.pushed
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// `count` events before the newest `skip`, oldest first.
+    pub(crate) fn page(&self, count: usize, skip: usize) -> impl Iterator<Item = &Event> {
+        let end = self.events.len().saturating_sub(skip);
+        self.events.iter().take(end).skip(end.saturating_sub(count))
+    }
+
+    /// The last `count` events, oldest first.
+    pub(crate) fn latest(&self, count: usize) -> impl Iterator<Item = &Event> {
+        self.events
+            .iter()
+            .skip(self.events.len().saturating_sub(count))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn old_events_are_dropped() {
+        let mut log = EventLog::default();
+        for number in 0..CAPACITY + 5 {
+            log.push(Event::BarSpawned { number });
+        }
+        let latest: Vec<_> = log.latest(2).collect();
+        assert_eq!(
+            latest,
+            [
+                &Event::BarSpawned {
+                    number: CAPACITY + 3
+                },
+                &Event::BarSpawned {
+                    number: CAPACITY + 4
+                },
+            ]
+        );
+        assert_eq!(log.latest(usize::MAX).count(), CAPACITY);
+        assert_eq!(log.pushed(), CAPACITY + 5);
+    }
+
+    #[test]
+    fn pages_skip_the_newest_events() {
+        let mut log = EventLog::default();
+        for number in 0..10 {
+            log.push(Event::BarSpawned { number });
+        }
+        let numbers = |skip| {
+            log.page(3, skip)
+                .map(|event| match event {
+                    Event::BarSpawned { number } => *number,
+                    _ => unreachable!(),
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(numbers(0), [7, 8, 9]);
+        assert_eq!(numbers(4), [3, 4, 5]);
+        assert_eq!(numbers(8), [0, 1]);
+        assert!(numbers(20).is_empty());
+    }
+}
@@ -0,0 +1,56 @@
+use std::time::{Duration, Instant};
+use strum::*;
+
+use crate::app::App;
+use crate::strategy::{GameView, Strategy};
+use crate::upgrade::GlobalUpgrade;
+
+/// Runs the game without a terminal, letting `strategy` make a purchase
+/// decision every tick. Nothing is saved.
+pub(crate) fn run(mut app: App, strategy: &mut dyn Strategy, ticks: u64, tick_rate: Duration) {
+    let start = Instant::now();
+    let mut now = start;
+    let mut purchases = 0;
+    for _ in 0..ticks {
+        now += tick_rate;
+        app.on_tick(now);
+        purchases += app.apply_strategy(strategy);
+    }
+
+    println!(
+        "Simulated {ticks} ticks ({secs}s), {purchases} purchases",
+        secs = (now - start).as_secs()
+    );
+    print_summary(&app);
+}
+
+pub(crate) fn print_summary(app: &App) {
+    for line in summary(app) {
+        println!("{line}");
+    }
+}
+
+pub(crate) fn summary(app: &App) -> Vec<String> {
+    let view = GameView::new(app);
+    let mut lines = vec![format!(
+        "Bars: {bars}, prestige points: {current}, claimable: {claimable}",
+        bars = view.bars().len(),
+        current = view.prestige().current,
+        claimable = view.prestige().claimable_prestige(view.bars().len())
+    )];
+    for upgrade in GlobalUpgrade::iter() {
+        lines.push(format!(
+            "Global {upgrade:?}: {}",
+            view.global_upgrade(upgrade)
+        ));
+    }
+    for bar in view.bars() {
+        lines.push(format!(
+            "#{number}: L{level} gathered {gathered}",
+            number = bar.number,
+            level = bar.level,
+            gathered = bar.gathered
+        ));
+    }
+    lines
+}
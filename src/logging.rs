@@ -0,0 +1,95 @@
+use anyhow::Context;
+use chrono::{Local, SecondsFormat};
+use log::{LevelFilter, Log, Metadata, Record};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::sync::Mutex;
+
+/// Appends log records to a file, one `time level target: message` line
+/// each. Nothing is ever written to stdout or stderr, as they belong to
+/// the terminal UI, so records that can't be written are dropped.
+struct FileLogger {
+    file: Mutex<File>,
+    level: LevelFilter,
+}
+
+/// Sends everything logged at `level` or above to the file at `path`.
+pub(crate) fn init(path: &str, level: LevelFilter) -> anyhow::Result<()> {
+    let logger = FileLogger::open(path, level)?;
+    log::set_boxed_logger(Box::new(logger))?;
+    log::set_max_level(level);
+    Ok(())
+}
+
+impl FileLogger {
+    fn open(path: &str, level: LevelFilter) -> anyhow::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Could not open log file {path}"))?;
+        Ok(Self {
+            file: Mutex::new(file),
+            level,
+        })
+    }
+}
+
+impl Log for FileLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format!(
+            "{time} {level:<5} {target}: {message}\n",
+            time = Local::now().to_rfc3339_opts(SecondsFormat::Millis, false),
+            level = record.level(),
+            target = record.target(),
+            message = record.args()
+        );
+        if let Ok(mut file) = self.file.lock() {
+            let _ = file.write_all(line.as_bytes());
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut file) = self.file.lock() {
+            let _ = file.flush();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::Level;
+
+    #[test]
+    fn records_below_the_level_are_dropped() {
+        let path = std::env::temp_dir().join("antsy-test-log.txt");
+        let _ = std::fs::remove_file(&path);
+        let logger = FileLogger::open(path.to_str().unwrap(), LevelFilter::Info).unwrap();
+        for (level, message) in [(Level::Info, "Saved"), (Level::Debug, "Ticked")] {
+            logger.log(
+                &Record::builder()
+                    .level(level)
+                    .target("antsy::app")
+                    .args(format_args!("{message}"))
+                    .build(),
+            );
+        }
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<_> = contents.lines().collect();
+        assert_eq!(lines.len(), 1);
+        assert!(
+            lines[0].ends_with(" INFO  antsy::app: Saved"),
+            "{}",
+            lines[0]
+        );
+    }
+}
@@ -1,14 +1,18 @@
 #[macro_use]
 extern crate derive_more;
 
+use anyhow::Context;
 use crossterm::{
-    self,
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event},
+    self, cursor,
+    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
     terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use std::{
     error::Error,
-    io,
+    io, panic,
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+    sync::Arc,
     time::{Duration, Instant},
 };
 use structopt::StructOpt;
@@ -19,125 +23,429 @@ use tui::{
 };
 
 mod app;
+mod balance;
 mod bar;
+mod challenge;
+mod control;
 mod controls;
+mod crash;
+mod dev;
+mod events;
 mod float;
+mod headless;
+mod logging;
+mod metrics;
 mod opts;
+mod plain;
 mod prestige;
+mod profile;
+mod random_events;
 mod render;
+mod replay;
+mod research;
 mod save;
+mod share;
+mod strategy;
+mod theme;
 mod ui;
 mod upgrade;
 
 use self::app::App;
 use self::bar::Bar;
-use self::controls::Action;
+use self::control::ControlSocket;
+use self::controls::{Action, PickerAction};
+use self::crash::LastGood;
 use self::float::Float;
-use self::opts::Opts;
+use self::opts::{Command, Opts, SaveCommand};
 use self::prestige::PrestigeUpgrade;
+use self::profile::Profiles;
+use self::replay::{Playback, Recorder};
+use self::strategy::Strategy;
+use self::theme::Theme;
+use self::ui::profiles::Picker;
 use self::upgrade::{GlobalUpgrade, Upgrade};
 
 fn main() -> Result<(), Box<dyn Error>> {
     let mut opts = Opts::from_args();
 
-    if opts.new_save && opts.save_file != "save.json" {
-        panic!("Cannot specify both new-save and save-file");
+    if let Some(log_file) = &opts.log_file {
+        logging::init(log_file, opts.log_level)?;
     }
-    if opts.new_save {
-        opts.save_file = format!(
-            "antsy-{}.json",
+    log::info!("Starting antsy {}", env!("CARGO_PKG_VERSION"));
+
+    // Leave the alternate screen before the panic message is printed, so
+    // that it can be read.
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let _ = restore_terminal();
+        log::error!("{info}");
+        default_hook(info);
+    }));
+
+    balance::load(opts.balance_file.as_deref())?;
+
+    let tick_rate = Duration::from_millis(opts.tick_ms);
+    let theme = Theme::from_opts(&opts, theme::no_color())?;
+
+    if let Some(replay) = opts.replay.clone() {
+        opts.no_save = true;
+        let replay_headless = opts.replay_headless;
+        let mut playback = Playback::load(&replay, opts, Instant::now())?;
+        if replay_headless {
+            while playback.step() {}
+            headless::print_summary(&playback.app);
+            return Ok(());
+        }
+        return with_terminal(|terminal, quit| Ok(run_replay(terminal, &theme, playback, quit)?));
+    }
+
+    let profiles = match opts.save_file {
+        Some(_) => None,
+        None => {
+            let profiles = Profiles::open(opts.data_dir.as_deref())?;
+            if profiles.migrate(Path::new("save.json"))? {
+                let message = format!(
+                    "Moved save.json into the {} profile, the original is kept as \
+                     save.json.migrated",
+                    profile::DEFAULT
+                );
+                log::info!("{message}");
+                eprintln!("{message}");
+            }
+            Some(profiles)
+        }
+    };
+    let profile = if opts.new_save {
+        Some(format!(
+            "antsy-{}",
             chrono::Utc::now().format("%Y-%m-%dT%H-%M-%S")
+        ))
+    } else if let Some(profile) = &opts.profile {
+        profile::check_name(profile)?;
+        Some(profile.clone())
+    } else if opts.plain && opts.command.is_none() && profiles.is_some() {
+        // There's no picker without a terminal
+        let message = format!(
+            "Playing the {} profile, choose another with --profile",
+            profile::DEFAULT
         );
+        log::info!("{message}");
+        eprintln!("{message}");
+        Some(profile::DEFAULT.to_owned())
+    } else {
+        None
+    };
+    if let (Some(profiles), Some(profile)) = (&profiles, &profile) {
+        opts.save_file = Some(profiles.path(profile).to_string_lossy().into_owned());
+    }
+
+    if let Some(command) = &opts.command {
+        return Ok(run_command(command, opts.save_file.as_deref())?);
+    }
+
+    let headless_ticks = opts.headless_ticks;
+    let record = opts.record.clone();
+    let strategy_kind = opts.strategy;
+    let mut strategy = opts.strategy.build();
+
+    if let Some(ticks) = headless_ticks {
+        let app = App::load(opts, Instant::now())?;
+        headless::run(app, strategy.as_mut(), ticks, tick_rate);
+        return Ok(());
+    }
+
+    if opts.plain {
+        let app = App::load(opts, Instant::now())?;
+        let control = app
+            .opts
+            .control_socket
+            .as_deref()
+            .map(ControlSocket::bind)
+            .transpose()?;
+        let quit = quit_on_signals()?;
+        let save_file = app.opts.save_file.clone().filter(|_| !app.opts.no_save);
+        crash::catch_panics(save_file.as_deref(), |last_good| {
+            plain::run(app, strategy.as_mut(), control, tick_rate, &quit, last_good)
+        })?;
+        return Ok(());
+    }
+
+    with_terminal(|terminal, quit| {
+        if let (None, Some(profiles)) = (&opts.save_file, &profiles) {
+            match run_picker(terminal, &theme, profiles, quit)? {
+                None => return Ok(()),
+                Some(profile) => {
+                    opts.save_file = Some(profiles.path(&profile).to_string_lossy().into_owned())
+                }
+            }
+        }
+        let app = App::load(opts, Instant::now())?;
+        let recorder = record
+            .map(|path| Recorder::create(&path, &app, strategy_kind))
+            .transpose()?;
+        let control = app
+            .opts
+            .control_socket
+            .as_deref()
+            .map(ControlSocket::bind)
+            .transpose()?;
+        let save_file = app.opts.save_file.clone().filter(|_| !app.opts.no_save);
+        crash::catch_panics(save_file.as_deref(), |last_good| {
+            Ok(run_app(
+                terminal,
+                &theme,
+                app,
+                strategy.as_mut(),
+                recorder,
+                control,
+                tick_rate,
+                quit,
+                last_good,
+            )?)
+        })
+    })
+}
+
+fn run_command(command: &Command, save_file: Option<&str>) -> anyhow::Result<()> {
+    let save_file =
+        || save_file.context("This command needs --save-file or --profile to choose the save");
+    match command {
+        Command::Export => println!("{}", share::export_file(save_file()?)?),
+        Command::Import { code, force } => {
+            let code = match code {
+                Some(code) => code.clone(),
+                None => io::read_to_string(io::stdin())?,
+            };
+            share::import_file(&code, save_file()?, *force)?;
+        }
+        Command::Save(SaveCommand::Inspect) => {
+            print!("{}", save::App::read(save_file()?)?.inspect())
+        }
+        Command::Save(SaveCommand::Diff { old, new }) => {
+            let diff = save::App::read(old)?.diff(&save::App::read(new)?);
+            if diff.is_empty() {
+                println!("The saves are the same");
+            }
+            for line in diff {
+                println!("{line}");
+            }
+        }
+        Command::Save(SaveCommand::Set { assignments }) => {
+            let save_file = save_file()?;
+            let mut save = save::App::read(save_file)?;
+            for assignment in assignments {
+                let Some((path, value)) = assignment.split_once('=') else {
+                    anyhow::bail!("Expected field=value, got {assignment}");
+                };
+                save.set(path.trim_start_matches("$."), value)?;
+            }
+            save.write(save_file)?;
+        }
     }
+    Ok(())
+}
+
+/// Whether the terminal is in raw mode on the alternate screen.
+static TERMINAL_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// A flag that is set when the process is asked to quit by SIGTERM,
+/// SIGHUP or SIGINT.
+fn quit_on_signals() -> io::Result<Arc<AtomicBool>> {
+    let quit = Arc::new(AtomicBool::new(false));
+    for signal in [
+        signal_hook::consts::SIGTERM,
+        signal_hook::consts::SIGHUP,
+        signal_hook::consts::SIGINT,
+    ] {
+        signal_hook::flag::register(signal, Arc::clone(&quit))?;
+    }
+    Ok(quit)
+}
 
-    let app = App::load(opts, Instant::now());
+/// Runs `run` on the alternate screen, passing it the flag from
+/// `quit_on_signals`.
+fn with_terminal(
+    run: impl FnOnce(&mut Terminal<CrosstermBackend<io::Stdout>>, &AtomicBool) -> anyhow::Result<()>,
+) -> Result<(), Box<dyn Error>> {
+    let quit = quit_on_signals()?;
 
     // setup terminal
     terminal::enable_raw_mode()?;
-    log("\nStarting");
+    TERMINAL_ACTIVE.store(true, Ordering::SeqCst);
     let mut stdout = io::stdout();
     crossterm::execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    // create app and run it
-    let tick_rate = Duration::from_millis(40);
-    let res = run_app(&mut terminal, app, tick_rate);
+    let res = run(&mut terminal, &quit);
 
-    // restore terminal
-    terminal::disable_raw_mode()?;
-    crossterm::execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
-    terminal.show_cursor()?;
+    restore_terminal()?;
 
     if let Err(err) = res {
+        log::error!("{err:#}");
         println!("{:?}", err)
     }
 
     Ok(())
 }
 
+/// Leaves raw mode and the alternate screen, unless that already happened.
+fn restore_terminal() -> io::Result<()> {
+    if !TERMINAL_ACTIVE.swap(false, Ordering::SeqCst) {
+        return Ok(());
+    }
+    terminal::disable_raw_mode()?;
+    crossterm::execute!(
+        io::stdout(),
+        LeaveAlternateScreen,
+        DisableMouseCapture,
+        cursor::Show
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
 fn run_app<B: Backend>(
     terminal: &mut Terminal<B>,
+    theme: &Theme,
     mut app: App,
+    strategy: &mut dyn Strategy,
+    mut recorder: Option<Recorder>,
+    mut control: Option<ControlSocket>,
     tick_rate: Duration,
+    quit: &AtomicBool,
+    last_good: &mut LastGood,
 ) -> io::Result<()> {
     let mut last_tick = Instant::now();
     loop {
         let timeout = tick_rate
             .checked_sub(last_tick.elapsed())
             .unwrap_or_else(|| Duration::from_secs(0));
-        if event::poll(timeout)? {
-            if let Event::Key(key) = event::read()? {
-                match app
-                    .ui
-                    .handle_keypress(key, app.bars.len(), PrestigeUpgrade::COUNT)
-                {
-                    Action::PurchaseUpgrade => app.try_purchase_highlighted_upgrade(),
-                    Action::Quit => {
-                        app.save();
-                        return Ok(());
-                    }
-                    Action::Noop => (),
-                    Action::UpgradeAny => app.purchase_any_upgrade(),
-                    Action::Prestige => {
-                        if app.prestige.can_prestige(app.bars.len()) {
-                            app.prestige();
-                        }
-                    }
-                    Action::PurchasePrestigeUpgrade => {
-                        if let Some(highlight) = app.ui.highlighted_prestige_upgrade() {
-                            app.prestige.try_purchase_upgrade(highlight);
-                        }
-                    }
+        let action = if quit.load(Ordering::SeqCst) {
+            log::info!("Quitting on a signal");
+            Action::Quit
+        } else if event::poll(timeout)? {
+            match event::read()? {
+                Event::Key(key) => app.ui.handle_keypress(
+                    key,
+                    app.bars.len(),
+                    PrestigeUpgrade::COUNT,
+                    app.events.len(),
+                ),
+                _ => Action::Noop,
+            }
+        } else {
+            Action::Noop
+        };
+        match action {
+            Action::Quit => {
+                let saved = app.save();
+                if let Some(recorder) = recorder {
+                    recorder.finish()?;
+                }
+                return saved.map_err(io::Error::other);
+            }
+            Action::Noop => (),
+            action => {
+                if let Some(recorder) = &mut recorder {
+                    recorder.record_action(&action);
                 }
+                app.apply_action(action, strategy);
             }
         }
+        if let Some(control) = &mut control {
+            control.poll(&mut app, |app, action| {
+                if let Some(recorder) = &mut recorder {
+                    recorder.record_action(&action);
+                }
+                app.apply_action(action, strategy);
+            });
+        }
 
         if last_tick.elapsed() >= tick_rate {
             last_tick = Instant::now();
             app.on_tick(last_tick);
-            terminal.draw(|f| render::ui(f, &app))?;
+            if let Some(recorder) = &mut recorder {
+                recorder.record_tick(last_tick)?;
+            }
+            app.autosave();
+            app.write_metrics();
+            app.ui.size = terminal.draw(|f| render::ui(f, theme, &app))?.area;
+            last_good.refresh(&app, last_tick);
+            let took = last_tick.elapsed();
+            if took > tick_rate {
+                log::warn!("Tick took {took:?}, longer than the {tick_rate:?} tick rate");
+            }
         }
     }
 }
 
-fn log(s: &str) {
-    use std::fs::*;
-    use std::io::*;
+/// Shows the profile picker until a profile is chosen. Returns its name,
+/// or `None` if the player quit.
+fn run_picker<B: Backend>(
+    terminal: &mut Terminal<B>,
+    theme: &Theme,
+    profiles: &Profiles,
+    quit: &AtomicBool,
+) -> anyhow::Result<Option<String>> {
+    let mut picker = Picker::new();
+    loop {
+        let list = profiles.list()?;
+        picker.highlight = picker.highlight.min(list.len().saturating_sub(1));
+        terminal.draw(|f| render::picker(f, theme, &picker, &list))?;
+
+        while !event::poll(Duration::from_millis(250))? {
+            if quit.load(Ordering::SeqCst) {
+                return Ok(None);
+            }
+        }
+        if let Event::Key(key) = event::read()? {
+            let names: Vec<_> = list.iter().map(|profile| profile.name.clone()).collect();
+            let res = match picker.handle_keypress(key, &names) {
+                PickerAction::Quit => return Ok(None),
+                PickerAction::Play(name) => return Ok(Some(name)),
+                PickerAction::Noop => Ok(()),
+                PickerAction::Create(name) => profiles.create(&name),
+                PickerAction::Rename(from, to) => profiles.rename(&from, &to),
+                PickerAction::Duplicate(from, to) => profiles.duplicate(&from, &to),
+                PickerAction::Delete(name) => profiles.delete(&name),
+            };
+            if let Err(err) = res {
+                picker.message = Some(format!("{err:#}"));
+            }
+        }
+    }
+}
 
-    if std::path::Path::new("log.txt").exists() {
-        let mut file = OpenOptions::new()
-            .write(true)
-            .append(true)
-            .open("log.txt")
-            .unwrap();
+/// Plays the replay back at the speed it was recorded, until it's over
+/// and the player quits.
+fn run_replay<B: Backend>(
+    terminal: &mut Terminal<B>,
+    theme: &Theme,
+    mut playback: Playback,
+    quit: &AtomicBool,
+) -> io::Result<()> {
+    let start = Instant::now();
+    loop {
+        if quit.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        let timeout = playback
+            .next_at()
+            .map_or(Duration::from_secs(1), |at| {
+                at.saturating_sub(start.elapsed())
+            })
+            .min(Duration::from_secs(1));
+        if event::poll(timeout)? {
+            if let Event::Key(key) = event::read()? {
+                if let KeyCode::Char('q') = key.code {
+                    return Ok(());
+                }
+            }
+        }
 
-        if let Err(e) = writeln!(file, "{}", s) {
-            eprintln!("Couldn't write to file: {}", e);
+        if playback.next_at().is_some_and(|at| start.elapsed() >= at) {
+            playback.step();
+            terminal.draw(|f| render::ui(f, theme, &playback.app))?;
         }
     }
 }
@@ -0,0 +1,227 @@
+use anyhow::Context;
+use std::fmt::Write;
+use std::time::Duration;
+use strum::*;
+
+use crate::app::App;
+use crate::bar::Bar;
+use crate::upgrade::GlobalUpgrade;
+
+/// How often `--metrics-file` is rewritten, in game time.
+pub(crate) const WRITE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// What happened since the game started, for the counters in the metrics.
+/// They start over when the process does.
+#[derive(Copy, Clone, Default, Debug, PartialEq)]
+pub(crate) struct Counters {
+    pub(crate) completions: u64,
+    pub(crate) bar_purchases: u64,
+    pub(crate) global_purchases: u64,
+    pub(crate) prestige_purchases: u64,
+}
+
+/// The game state in the Prometheus text format.
+pub(crate) fn render(app: &App) -> String {
+    let mut out = String::new();
+    let global_speed_levels = app.get_global_upgrade_u(GlobalUpgrade::Speed);
+
+    per_bar(
+        &mut out,
+        app,
+        "gathered",
+        "Value gathered by the bar",
+        |bar| bar.gathered.0,
+    );
+    per_bar(&mut out, app, "level", "Level of the bar", |bar| {
+        bar.level as f64
+    });
+    per_bar(&mut out, app, "exp", "Exp towards the next level", |bar| {
+        bar.exp.0
+    });
+    per_bar(
+        &mut out,
+        app,
+        "speed_multiplier",
+        "Speed multiplier from upgrades",
+        |bar| bar.speed_multiplier(global_speed_levels, &app.research).0,
+    );
+    per_bar(
+        &mut out,
+        app,
+        "gain_exponent",
+        "Powers of ten the speed of the bar was scaled down by",
+        |bar| bar.gain_exponent as f64,
+    );
+    metric(
+        &mut out,
+        "antsy_bars",
+        "Number of bars",
+        "gauge",
+        [(String::new(), app.bars.len() as f64)],
+    );
+    metric(
+        &mut out,
+        "antsy_global_upgrade_level",
+        "Level of each global upgrade",
+        "gauge",
+        GlobalUpgrade::iter().map(|upgrade| {
+            (
+                format!("{{upgrade=\"{upgrade:?}\"}}"),
+                app.get_global_upgrade_u(upgrade) as f64,
+            )
+        }),
+    );
+    metric(
+        &mut out,
+        "antsy_prestige_points",
+        "Unspent prestige points",
+        "gauge",
+        [(String::new(), app.prestige.current.0)],
+    );
+    metric(
+        &mut out,
+        "antsy_prestige_claimable",
+        "Prestige points a prestige would give now",
+        "gauge",
+        [(
+            String::new(),
+            app.prestige.claimable_prestige(app.bars.len()).0,
+        )],
+    );
+    metric(
+        &mut out,
+        "antsy_research_points",
+        "Unspent research points",
+        "gauge",
+        [(String::new(), app.research.points.0)],
+    );
+
+    let counters = app.counters;
+    metric(
+        &mut out,
+        "antsy_completions_total",
+        "Times any bar filled up",
+        "counter",
+        [(String::new(), counters.completions as f64)],
+    );
+    metric(
+        &mut out,
+        "antsy_purchases_total",
+        "Upgrades bought, by hand or automatically",
+        "counter",
+        [
+            ("bar", counters.bar_purchases),
+            ("global", counters.global_purchases),
+            ("prestige", counters.prestige_purchases),
+        ]
+        .map(|(kind, count)| (format!("{{kind=\"{kind}\"}}"), count as f64)),
+    );
+    out
+}
+
+/// A gauge labelled with the number of each bar.
+fn per_bar(out: &mut String, app: &App, name: &str, help: &str, value: impl Fn(&Bar) -> f64) {
+    metric(
+        out,
+        &format!("antsy_bar_{name}"),
+        help,
+        "gauge",
+        app.bars
+            .iter()
+            .map(|bar| (format!("{{bar=\"{}\"}}", bar.number), value(bar))),
+    );
+}
+
+/// Writes a metric with its samples, which are the labels, like
+/// `{bar="3"}`, and the value.
+fn metric(
+    out: &mut String,
+    name: &str,
+    help: &str,
+    kind: &str,
+    samples: impl IntoIterator<Item = (String, f64)>,
+) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+    for (labels, value) in samples {
+        let _ = writeln!(out, "{name}{labels} {}", format_value(value));
+    }
+}
+
+/// Formats `value` the way the exposition format expects. Display would
+/// spell out every digit of large values.
+fn format_value(value: f64) -> String {
+    if value == f64::INFINITY {
+        "+Inf".to_owned()
+    } else if value == f64::NEG_INFINITY {
+        "-Inf".to_owned()
+    } else if value.abs() >= 1e15 {
+        format!("{value:e}")
+    } else {
+        value.to_string()
+    }
+}
+
+/// Replaces the file at `path` with the metrics of `app`, without a
+/// collector ever seeing it half written.
+pub(crate) fn write(path: &str, app: &App) -> anyhow::Result<()> {
+    let temp = format!("{path}.tmp");
+    std::fs::write(&temp, render(app)).with_context(|| format!("Could not write {temp}"))?;
+    std::fs::rename(&temp, path).with_context(|| format!("Could not replace {path}"))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::float::Float;
+    use crate::strategy::Purchase;
+    use crate::upgrade::Upgrade;
+
+    #[test]
+    fn metrics_cover_bars_upgrades_and_counters() {
+        let mut app = App::for_test();
+        app.bars_to_spawn = 0;
+        app.spawn_bar();
+        app.bars[0].gathered = Float(1e6);
+        app.apply_action(
+            crate::controls::Action::PurchaseUpgrade(Purchase::Bar {
+                upgrade: Upgrade::Gain,
+                row: 0,
+            }),
+            &mut crate::strategy::Greedy,
+        );
+        app.timewarp(Duration::from_secs(60));
+
+        let metrics = render(&app);
+        let value = |name: &str| -> f64 {
+            let line = metrics
+                .lines()
+                .find(|line| line.starts_with(&format!("{name} ")))
+                .unwrap_or_else(|| panic!("No {name} in\n{metrics}"));
+            line[name.len() + 1..].parse().unwrap()
+        };
+        assert_eq!(
+            value("antsy_bar_level{bar=\"1\"}"),
+            app.bars[0].level as f64
+        );
+        assert_eq!(
+            value("antsy_bar_gathered{bar=\"1\"}"),
+            app.bars[0].gathered.0
+        );
+        assert_eq!(value("antsy_global_upgrade_level{upgrade=\"Speed\"}"), 0.);
+        assert_eq!(value("antsy_purchases_total{kind=\"bar\"}"), 1.);
+        assert!(value("antsy_completions_total") > 0.);
+        assert!(metrics.contains("# TYPE antsy_completions_total counter\n"));
+    }
+
+    #[test]
+    fn values_use_the_exposition_format() {
+        assert_eq!(format_value(f64::INFINITY), "+Inf");
+        assert_eq!(format_value(f64::NEG_INFINITY), "-Inf");
+        assert_eq!(format_value(f64::NAN), "NaN");
+        assert_eq!(format_value(1.5e300), "1.5e300");
+        assert_eq!(format_value(-2e20), "-2e20");
+        assert_eq!(format_value(1234.5), "1234.5");
+    }
+}
@@ -1,15 +1,135 @@
 use structopt::StructOpt;
 
+use crate::strategy::Strategies;
 use crate::ui::UiStates;
 
-#[derive(StructOpt)]
+#[derive(StructOpt, Clone)]
 pub(crate) struct Opts {
-    #[structopt(long)]
+    /// Start a new profile named after the current time
+    #[structopt(long, conflicts_with_all = &["save_file", "profile"])]
     pub(crate) new_save: bool,
-    #[structopt(long, default_value = "save.json")]
-    pub(crate) save_file: String,
+    /// Play this save file instead of a profile
+    #[structopt(long, conflicts_with = "profile")]
+    pub(crate) save_file: Option<String>,
+    /// Play this profile without showing the profile picker, creating it
+    /// if it doesn't exist
+    #[structopt(long)]
+    pub(crate) profile: Option<String>,
+    /// Where profiles are kept, defaults to $XDG_DATA_HOME/antsy/profiles
+    #[structopt(long)]
+    pub(crate) data_dir: Option<String>,
     #[structopt(long, default_value = "0.25")]
     pub(crate) speed_base: f64,
     #[structopt(long)]
     pub(crate) start_state: Option<UiStates>,
+    /// Colors to draw with: dark, light, high-contrast, solarized,
+    /// monochrome, or a theme defined in the themes file
+    #[structopt(long, default_value = "dark")]
+    pub(crate) theme: String,
+    /// Mark the selected button with > and affordable upgrades with [✓],
+    /// and draw bars as text, for terminals where colors and underlines
+    /// don't show. Always on when NO_COLOR is set.
+    #[structopt(long)]
+    pub(crate) accessible: bool,
+    /// File defining custom themes, defaults to
+    /// $XDG_CONFIG_HOME/antsy/themes.json
+    #[structopt(long)]
+    pub(crate) themes_file: Option<String>,
+    /// Milliseconds between redraws. The game runs at the same speed
+    /// regardless.
+    #[structopt(long, default_value = "40", parse(try_from_str = parse_tick_ms))]
+    pub(crate) tick_ms: u64,
+    /// JSON file overriding values of the built-in game balance
+    #[structopt(long)]
+    pub(crate) balance_file: Option<String>,
+    /// Strategy used by the upgrade-any button and headless runs
+    #[structopt(long, default_value = "greedy")]
+    pub(crate) strategy: Strategies,
+    /// Simulate this many ticks without a terminal and print a summary
+    #[structopt(long)]
+    pub(crate) headless_ticks: Option<u64>,
+    /// Fix problems found in the save instead of refusing to load it. The
+    /// original is kept with a .bak extension.
+    #[structopt(long)]
+    pub(crate) repair: bool,
+    /// Enable the developer console, opened with `
+    #[structopt(long)]
+    pub(crate) dev: bool,
+    /// Append timestamped logs to this file
+    #[structopt(long)]
+    pub(crate) log_file: Option<String>,
+    /// Least severe level written to the log file: error, warn, info,
+    /// debug or trace
+    #[structopt(long, default_value = "info")]
+    pub(crate) log_level: log::LevelFilter,
+    /// Play by typing commands, with what happens announced as lines of
+    /// text instead of the full-screen interface, for screen readers
+    #[structopt(long, conflicts_with_all = &["headless_ticks", "record"])]
+    pub(crate) plain: bool,
+    /// Serve JSON-RPC queries and commands on a Unix socket at this path
+    /// while playing, for status bars, bots and dashboards
+    #[structopt(long, conflicts_with = "headless_ticks")]
+    pub(crate) control_socket: Option<String>,
+    /// Keep this file updated with metrics in the Prometheus text format,
+    /// for node_exporter's textfile collector
+    #[structopt(long)]
+    pub(crate) metrics_file: Option<String>,
+    /// Don't start random events like golden bars and market crashes,
+    /// for playing purely idle
+    #[structopt(long)]
+    pub(crate) no_random_events: bool,
+    /// Never write the save file
+    #[structopt(long)]
+    pub(crate) no_save: bool,
+    /// Record every tick and action of this session to a replay file
+    #[structopt(long, conflicts_with = "replay")]
+    pub(crate) record: Option<String>,
+    /// Play back a replay file instead of playing, implies --no-save
+    #[structopt(long)]
+    pub(crate) replay: Option<String>,
+    /// Play back the replay without a terminal and print a summary
+    #[structopt(long, requires = "replay")]
+    pub(crate) replay_headless: bool,
+    #[structopt(subcommand)]
+    pub(crate) command: Option<Command>,
+}
+
+/// Subcommands work on the save chosen with --save-file or --profile.
+#[derive(StructOpt, Clone)]
+pub(crate) enum Command {
+    /// Print the save as a compact save string
+    Export,
+    /// Replace the save with one from a save string made by export
+    Import {
+        /// The save string, read from stdin if not given
+        code: Option<String>,
+        /// Overwrite the save if it already exists
+        #[structopt(long)]
+        force: bool,
+    },
+    /// Inspect and edit saves
+    Save(SaveCommand),
+}
+
+#[derive(StructOpt, Clone)]
+pub(crate) enum SaveCommand {
+    /// Print the bars, upgrades and prestige in the save
+    Inspect,
+    /// Compare two save files field by field
+    Diff { old: String, new: String },
+    /// Change fields of the save, like `prestige.current=100` or
+    /// `bars[0].upgrades.Double=3`. Paths are the ones inspect and diff
+    /// show, and the save has to stay valid.
+    Set {
+        #[structopt(required = true)]
+        assignments: Vec<String>,
+    },
+}
+
+fn parse_tick_ms(s: &str) -> Result<u64, String> {
+    match s.parse() {
+        Ok(0) => Err("should be at least 1".to_owned()),
+        Ok(ms) => Ok(ms),
+        Err(err) => Err(format!("{err}")),
+    }
 }
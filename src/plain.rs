@@ -0,0 +1,587 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+use strum::*;
+
+use crate::app::App;
+use crate::control::ControlSocket;
+use crate::controls::Action;
+use crate::crash::LastGood;
+use crate::events::Event;
+use crate::headless;
+use crate::prestige::PrestigeUpgrade;
+use crate::research::ResearchNode;
+use crate::strategy::{GameView, Purchase, Strategy};
+use crate::upgrade::{GlobalUpgrade, Upgrade};
+
+const HELP: &str = "\
+status: list the bars, upgrades and what you can afford
+buy <bar> <upgrade>: buy speed, gain, double, triple, quadruple or quintuple for a bar, like buy 3 double
+buy global <upgrade>: buy speed, exp-boost, progress-bars, gain, exp-gain or research-gain
+buy prestige <upgrade>: buy a prestige upgrade, like buy prestige level-up-faster
+any: buy what the upgrade-any button would
+transfer <bar> up|down: change how much a bar passes on to the next one
+charge <bar>: boost a bar for a few seconds, which it then has to cool down from
+prestige: start over for prestige points
+research <node>: research efficiency, quintuple, momentum, practice or grants
+challenge <name>: prestige into a run under no-global-upgrades, no-transfer, no-speed-upgrades or slow-spawns, or abandon the current one and keep its bars
+verbose: also announce saves and level-ups, or stop
+quit: save and quit";
+
+/// Plays the game with typed commands, announcing what happens as lines
+/// of text, for players using screen readers. Stops on `quit`, at the end
+/// of input, or once `quit` is set.
+pub(crate) fn run(
+    mut app: App,
+    strategy: &mut dyn Strategy,
+    mut control: Option<ControlSocket>,
+    tick_rate: Duration,
+    quit: &AtomicBool,
+    last_good: &mut LastGood,
+) -> anyhow::Result<()> {
+    // Reading stdin blocks, so it happens on its own thread
+    let (send_line, lines) = mpsc::channel();
+    std::thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let Ok(line) = line else { break };
+            if send_line.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut out = io::stdout();
+    writeln!(out, "Type help for a list of commands")?;
+    let mut announcer = Announcer::new(&app);
+    let mut last_tick = Instant::now();
+    loop {
+        let timeout = tick_rate.saturating_sub(last_tick.elapsed());
+        let line = match lines.recv_timeout(timeout) {
+            Ok(line) => Some(line),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        if quit.load(Ordering::SeqCst) {
+            log::info!("Quitting on a signal");
+            break;
+        }
+        if let Some(line) = line {
+            match handle_line(&mut app, &mut announcer, strategy, &line) {
+                None => break,
+                Some(replies) => write_lines(&mut out, replies)?,
+            }
+        }
+        if let Some(control) = &mut control {
+            control.poll(&mut app, |app, action| app.apply_action(action, strategy));
+        }
+
+        if last_tick.elapsed() >= tick_rate {
+            last_tick = Instant::now();
+            app.on_tick(last_tick);
+            app.autosave();
+            app.write_metrics();
+            last_good.refresh(&app, last_tick);
+        }
+        write_lines(&mut out, announcer.announce(&app))?;
+    }
+
+    let saved = app.save();
+    write_lines(&mut out, announcer.announce(&app))?;
+    saved
+}
+
+fn write_lines(out: &mut impl Write, lines: Vec<String>) -> io::Result<()> {
+    for line in lines {
+        writeln!(out, "{line}")?;
+    }
+    out.flush()
+}
+
+/// What a typed line asks for.
+#[derive(Debug)]
+enum Command {
+    Action(Action),
+    Status,
+    Help,
+    Verbose,
+}
+
+/// Carries out a typed line. Returns what to tell the player, or `None`
+/// to quit.
+fn handle_line(
+    app: &mut App,
+    announcer: &mut Announcer,
+    strategy: &mut dyn Strategy,
+    line: &str,
+) -> Option<Vec<String>> {
+    if line.trim().is_empty() {
+        return Some(vec![]);
+    }
+    Some(match parse(line, app) {
+        Err(err) => vec![err],
+        Ok(Command::Help) => HELP.lines().map(str::to_owned).collect(),
+        Ok(Command::Status) => status(app),
+        Ok(Command::Verbose) => {
+            announcer.verbose = !announcer.verbose;
+            vec![if announcer.verbose {
+                "Announcing saves and level-ups".to_owned()
+            } else {
+                "Not announcing saves and level-ups".to_owned()
+            }]
+        }
+        Ok(Command::Action(Action::Quit)) => return None,
+        Ok(Command::Action(action)) => match refusal(app, &action) {
+            Some(refusal) => vec![refusal],
+            // Whatever happened is announced from the event log
+            None => {
+                app.apply_action(action, strategy);
+                vec![]
+            }
+        },
+    })
+}
+
+fn parse(line: &str, app: &App) -> Result<Command, String> {
+    let line = line.trim().to_lowercase();
+    let words: Vec<_> = line.split_whitespace().collect();
+    Ok(match words[..] {
+        ["help"] => Command::Help,
+        ["status"] => Command::Status,
+        ["verbose"] => Command::Verbose,
+        ["quit"] => Command::Action(Action::Quit),
+        ["prestige"] => Command::Action(Action::Prestige),
+        ["any"] => Command::Action(Action::UpgradeAny),
+        ["research", node] => {
+            Command::Action(Action::UnlockResearch(parse_name("research node", node)?))
+        }
+        ["challenge", challenge] => {
+            Command::Action(Action::ToggleChallenge(parse_name("challenge", challenge)?))
+        }
+        ["buy", "global", upgrade] => Command::Action(Action::PurchaseUpgrade(Purchase::Global {
+            upgrade: parse_name("global upgrade", upgrade)?,
+        })),
+        ["buy", "prestige", upgrade] => Command::Action(Action::PurchasePrestigeUpgrade(
+            parse_name("prestige upgrade", upgrade)?,
+        )),
+        ["buy", bar, upgrade] => Command::Action(Action::PurchaseUpgrade(Purchase::Bar {
+            row: find_bar(app, bar)?,
+            upgrade: parse_name("upgrade", upgrade)?,
+        })),
+        ["transfer", bar, direction @ ("up" | "down")] => Command::Action(Action::AdjustTransfer {
+            row: find_bar(app, bar)?,
+            up: direction == "up",
+        }),
+        ["charge", bar] => Command::Action(Action::ChargeBar {
+            row: find_bar(app, bar)?,
+        }),
+        _ => return Err(format!("Unknown command {line}, type help for a list")),
+    })
+}
+
+/// The row of the bar numbered `number`, which may start with `#`.
+fn find_bar(app: &App, number: &str) -> Result<usize, String> {
+    let number = number.trim_start_matches('#');
+    let parsed: usize = number
+        .parse()
+        .map_err(|_| format!("Expected a bar number, got {number}"))?;
+    app.bars
+        .iter()
+        .position(|bar| bar.number == parsed)
+        .ok_or_else(|| format!("There is no bar #{number}"))
+}
+
+/// Finds the upgrade, research node or challenge named like `exp-boost`
+/// or `expboost`. `kind` names what was looked for in the error.
+fn parse_name<T: IntoEnumIterator + fmt::Debug>(kind: &str, name: &str) -> Result<T, String> {
+    let wanted = name.replace(['-', '_'], "");
+    T::iter()
+        .find(|value| format!("{value:?}").to_lowercase() == wanted)
+        .ok_or_else(|| {
+            let names: Vec<_> = T::iter().map(|value| kebab_case(&value)).collect();
+            format!("Unknown {kind} {name}, expected {}", names.join(", "))
+        })
+}
+
+fn kebab_case(value: &impl fmt::Debug) -> String {
+    let mut name = String::new();
+    for c in format!("{value:?}").chars() {
+        if c.is_uppercase() && !name.is_empty() {
+            name.push('-');
+        }
+        name.push(c.to_ascii_lowercase());
+    }
+    name
+}
+
+/// Why `action` would do nothing right now, which the game itself doesn't
+/// say.
+fn refusal(app: &App, action: &Action) -> Option<String> {
+    match *action {
+        Action::PurchaseUpgrade(purchase) => {
+            let view = GameView::new(app);
+            let name = Affordable::from_purchase(app, purchase);
+            let researched = match purchase {
+                Purchase::Bar { upgrade, .. } => app.research.allows_upgrade(upgrade),
+                Purchase::Global { upgrade } => app.research.allows_global_upgrade(upgrade),
+            };
+            match view.cost(purchase) {
+                _ if view.can_afford(purchase) => None,
+                Some(cost) => Some(format!("Can't afford {name}, it costs {cost}")),
+                None if !researched => Some(format!("{name} needs research first")),
+                None => Some(format!("{name} is locked by the current challenge")),
+            }
+        }
+        Action::PurchasePrestigeUpgrade(upgrade) => {
+            if app.prestige.is_max_level(upgrade) {
+                Some(format!("Prestige {upgrade:?} is already maxed"))
+            } else if !app.prestige.can_afford(upgrade) {
+                Some(format!(
+                    "Can't afford prestige {upgrade:?}, it costs {} points",
+                    app.prestige.cost(upgrade)
+                ))
+            } else {
+                None
+            }
+        }
+        Action::UnlockResearch(node) => {
+            let research = &app.research;
+            if research.is_unlocked(node) {
+                Some(format!("{} is already researched", node.name()))
+            } else if !research.is_available(node) {
+                let requires: Vec<_> = node.requires().iter().map(|node| node.name()).collect();
+                Some(format!(
+                    "{} needs {} first",
+                    node.name(),
+                    requires.join(" and ")
+                ))
+            } else {
+                (!research.can_unlock(node)).then(|| {
+                    format!(
+                        "Can't afford {}, it costs {} research points",
+                        node.name(),
+                        node.cost()
+                    )
+                })
+            }
+        }
+        Action::ChargeBar { row } => {
+            let bar = &app.bars[row];
+            bar.charge_cooldown(app.tick).map(|cooldown| {
+                format!(
+                    "#{} can be charged again in {}s",
+                    bar.number,
+                    cooldown.as_secs_f64().ceil()
+                )
+            })
+        }
+        Action::ToggleChallenge(challenge)
+            if !app.prestige.in_challenge(challenge)
+                && !app.prestige.can_prestige(app.bars.len()) =>
+        {
+            Some(format!(
+                "Challenges start by prestiging, which you can't until you reach {} bars",
+                crate::balance::get().prestige.bar_threshold
+            ))
+        }
+        Action::Prestige => (!app.prestige.can_prestige(app.bars.len())).then(|| {
+            format!(
+                "You can't prestige until you reach {} bars",
+                crate::balance::get().prestige.bar_threshold
+            )
+        }),
+        _ => None,
+    }
+}
+
+fn status(app: &App) -> Vec<String> {
+    let mut lines = headless::summary(app);
+    let researched: Vec<_> = ResearchNode::iter()
+        .filter(|&node| app.research.is_unlocked(node))
+        .map(ResearchNode::name)
+        .collect();
+    lines.push(format!(
+        "Research points: {}, researched: {}",
+        app.research.points,
+        if researched.is_empty() {
+            "nothing".to_owned()
+        } else {
+            researched.join(", ")
+        }
+    ));
+    if let Some(challenge) = app.prestige.challenge {
+        lines.push(format!("Challenge: {}", challenge.rule()));
+    }
+    if let Some((event, remaining)) = app.random_events.remaining(app.tick) {
+        lines.push(format!(
+            "{}, {}s left",
+            event.description(),
+            remaining.as_secs_f64().ceil()
+        ));
+    }
+    let affordable = affordable(app);
+    lines.push(if affordable.is_empty() {
+        "Nothing is affordable".to_owned()
+    } else {
+        format!("Can afford {}", list(&affordable))
+    });
+    lines
+}
+
+/// Something the player could buy.
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+enum Affordable {
+    Bar { number: usize, upgrade: Upgrade },
+    Global(GlobalUpgrade),
+    Prestige(PrestigeUpgrade),
+    Research(ResearchNode),
+}
+
+impl Affordable {
+    fn from_purchase(app: &App, purchase: Purchase) -> Self {
+        match purchase {
+            Purchase::Bar { upgrade, row } => Affordable::Bar {
+                number: app.bars[row].number,
+                upgrade,
+            },
+            Purchase::Global { upgrade } => Affordable::Global(upgrade),
+        }
+    }
+
+    /// The level it's at, so that each level is announced once.
+    fn level(self, app: &App) -> usize {
+        match self {
+            Affordable::Bar { number, upgrade } => app
+                .bars
+                .iter()
+                .find(|bar| bar.number == number)
+                .map_or(0, |bar| bar.get_upgrade_u(upgrade)),
+            Affordable::Global(upgrade) => app.get_global_upgrade_u(upgrade),
+            Affordable::Prestige(upgrade) => app.prestige.level(upgrade),
+            Affordable::Research(_) => 0,
+        }
+    }
+}
+
+impl fmt::Display for Affordable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Affordable::Bar { number, upgrade } => write!(f, "{upgrade:?} for #{number}"),
+            Affordable::Global(upgrade) => write!(f, "global {upgrade:?}"),
+            Affordable::Prestige(upgrade) => write!(f, "prestige {upgrade:?}"),
+            Affordable::Research(node) => write!(f, "research {}", node.name()),
+        }
+    }
+}
+
+fn affordable(app: &App) -> Vec<Affordable> {
+    let view = GameView::new(app);
+    view.purchases()
+        .filter(|&purchase| view.can_afford(purchase))
+        .map(|purchase| Affordable::from_purchase(app, purchase))
+        .chain(
+            PrestigeUpgrade::iter()
+                .filter(|&upgrade| app.prestige.can_afford(upgrade))
+                .map(Affordable::Prestige),
+        )
+        .chain(
+            ResearchNode::iter()
+                .filter(|&node| app.research.can_unlock(node))
+                .map(Affordable::Research),
+        )
+        .collect()
+}
+
+fn list(affordable: &[Affordable]) -> String {
+    let names: Vec<_> = affordable.iter().map(Affordable::to_string).collect();
+    names.join(", ")
+}
+
+/// Tells the player what changed since the last announcement: new events,
+/// upgrades that became affordable, and being able to prestige. Saves and
+/// level-ups are only announced when `verbose`.
+struct Announcer {
+    events_seen: usize,
+    /// Upgrade levels already announced as affordable in this run
+    announced: HashSet<(Affordable, usize)>,
+    last_bar_number: usize,
+    could_prestige: bool,
+    verbose: bool,
+}
+
+impl Announcer {
+    fn new(app: &App) -> Self {
+        Self {
+            events_seen: app.events.pushed(),
+            announced: affordable(app)
+                .into_iter()
+                .map(|affordable| (affordable, affordable.level(app)))
+                .collect(),
+            last_bar_number: app.last_bar_number,
+            could_prestige: app.prestige.can_prestige(app.bars.len()),
+            verbose: false,
+        }
+    }
+
+    fn announce(&mut self, app: &App) -> Vec<String> {
+        let pushed = app.events.pushed();
+        // Importing a save starts a new event log
+        let unseen = pushed.saturating_sub(self.events_seen);
+        self.events_seen = pushed;
+        let mut lines: Vec<_> = app
+            .events
+            .latest(unseen)
+            .filter(|event| self.verbose || !matches!(event, Event::Saved | Event::LevelUp { .. }))
+            .map(|e| e.to_string())
+            .collect();
+
+        // A new run numbers its bars from 1 again
+        if app.last_bar_number < self.last_bar_number {
+            self.announced.clear();
+        }
+        self.last_bar_number = app.last_bar_number;
+        let newly_affordable: Vec<_> = affordable(app)
+            .into_iter()
+            .filter(|&affordable| self.announced.insert((affordable, affordable.level(app))))
+            .collect();
+        if !newly_affordable.is_empty() {
+            lines.push(format!("Can afford {}", list(&newly_affordable)));
+        }
+
+        let can_prestige = app.prestige.can_prestige(app.bars.len());
+        if can_prestige && !self.could_prestige {
+            lines.push(format!(
+                "You can prestige for {} points",
+                app.prestige.claimable_prestige(app.bars.len())
+            ));
+        }
+        self.could_prestige = can_prestige;
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bar::Bar;
+    use crate::float::Float;
+    use crate::strategy::Strategies;
+
+    fn app_with_bars(count: usize) -> App {
+        let mut app = App::for_test();
+        app.bars_to_spawn = 0;
+        for _ in 0..count {
+            app.spawn_bar();
+        }
+        app
+    }
+
+    #[test]
+    fn commands_refer_to_bars_by_number() {
+        let app = app_with_bars(3);
+        assert!(matches!(
+            parse("buy 1 double", &app),
+            Ok(Command::Action(Action::PurchaseUpgrade(Purchase::Bar {
+                upgrade: Upgrade::Double,
+                row: 2
+            })))
+        ));
+        assert!(matches!(
+            parse(" Buy global Exp-Boost ", &app),
+            Ok(Command::Action(Action::PurchaseUpgrade(Purchase::Global {
+                upgrade: GlobalUpgrade::ExpBoost
+            })))
+        ));
+        assert!(matches!(
+            parse("buy prestige level-up-faster", &app),
+            Ok(Command::Action(Action::PurchasePrestigeUpgrade(
+                PrestigeUpgrade::LevelUpFaster
+            )))
+        ));
+        assert!(matches!(
+            parse("transfer #3 down", &app),
+            Ok(Command::Action(Action::AdjustTransfer {
+                row: 0,
+                up: false
+            }))
+        ));
+        assert!(matches!(
+            parse("charge 2", &app),
+            Ok(Command::Action(Action::ChargeBar { row: 1 }))
+        ));
+        assert_eq!(parse("buy 4 gain", &app).unwrap_err(), "There is no bar #4");
+        assert!(parse("buy 1 sextuple", &app)
+            .unwrap_err()
+            .ends_with("expected speed, gain, double, triple, quadruple, quintuple"));
+        let Err(err) = parse("research telepathy", &app) else {
+            panic!("Unknown research node was parsed");
+        };
+        assert!(err.starts_with("Unknown research node telepathy"), "{err}");
+        let Err(err) = parse("challenge speedrun", &app) else {
+            panic!("Unknown challenge was parsed");
+        };
+        assert!(err.starts_with("Unknown challenge speedrun"), "{err}");
+    }
+
+    #[test]
+    fn purchases_are_refused_or_announced() {
+        let mut app = app_with_bars(2);
+        let mut strategy = Strategies::Greedy.build();
+        let mut announcer = Announcer::new(&app);
+
+        let replies =
+            handle_line(&mut app, &mut announcer, strategy.as_mut(), "buy 2 gain").unwrap();
+        assert_eq!(replies.len(), 1);
+        assert!(replies[0].starts_with("Can't afford Gain for #2"));
+        assert!(announcer.announce(&app).is_empty());
+
+        app.bars[0].gathered = Float(1e6);
+        assert!(announcer
+            .announce(&app)
+            .iter()
+            .any(|line| line.starts_with("Can afford ") && line.contains("Gain for #2")));
+
+        let replies =
+            handle_line(&mut app, &mut announcer, strategy.as_mut(), "buy 2 gain").unwrap();
+        assert!(replies.is_empty());
+        assert_eq!(announcer.announce(&app)[0], "Bought Gain level 1 for #2");
+        assert!(handle_line(&mut app, &mut announcer, strategy.as_mut(), "quit").is_none());
+    }
+
+    #[test]
+    fn upgrade_levels_are_announced_once() {
+        let mut app = app_with_bars(2);
+        let mut strategy = Strategies::Greedy.build();
+        let mut announcer = Announcer::new(&app);
+
+        app.bars[0].gathered = Float(1e6);
+        assert!(!announcer.announce(&app).is_empty());
+        app.bars[0].gathered = Float(0.);
+        assert!(announcer.announce(&app).is_empty());
+        app.bars[0].gathered = Float(1e6);
+        assert!(announcer.announce(&app).is_empty());
+
+        app.events.push(Event::Saved);
+        assert!(announcer.announce(&app).is_empty());
+        let replies = handle_line(&mut app, &mut announcer, strategy.as_mut(), "verbose");
+        assert_eq!(replies.unwrap(), ["Announcing saves and level-ups"]);
+        app.events.push(Event::Saved);
+        assert_eq!(announcer.announce(&app), [Event::Saved.to_string()]);
+    }
+
+    #[test]
+    fn prestige_is_announced_once() {
+        let mut app = app_with_bars(0);
+        let mut announcer = Announcer::new(&app);
+        for number in 1..=crate::balance::get().prestige.bar_threshold {
+            app.bars.push_front(Bar::new(number));
+        }
+        let lines = announcer.announce(&app);
+        assert!(lines
+            .iter()
+            .any(|line| line.starts_with("You can prestige")));
+        assert!(announcer.announce(&app).is_empty());
+    }
+}
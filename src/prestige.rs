@@ -1,11 +1,16 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use strum::*;
 
+use crate::balance;
+use crate::challenge::Challenge;
 use crate::float::Float;
 
 pub(crate) struct Prestige {
     pub(crate) current: Float,
     pub(crate) upgrades: HashMap<PrestigeUpgrade, usize>,
+    /// The challenge this run is played under
+    pub(crate) challenge: Option<Challenge>,
+    pub(crate) completed_challenges: HashSet<Challenge>,
 }
 
 impl Prestige {
@@ -13,27 +18,53 @@ impl Prestige {
         Self {
             current: 0.into(),
             upgrades: PrestigeUpgrade::iter().map(|u| (u, 0)).collect(),
+            challenge: None,
+            completed_challenges: HashSet::new(),
         }
     }
 
     pub(crate) fn can_prestige(&self, bar_len: usize) -> bool {
-        bar_len >= 10
+        bar_len >= balance::get().prestige.bar_threshold
     }
 
     pub(crate) fn claimable_prestige(&self, bar_len: usize) -> Float {
         if !self.can_prestige(bar_len) {
             0.into()
         } else {
-            Float((bar_len as f64) / 10.)
+            let points = bar_len as f64 * balance::get().prestige.points_per_bar;
+            if self.has_reward(Challenge::NoTransfer) {
+                Float(points * balance::get().challenge.prestige_bonus)
+            } else {
+                Float(points)
+            }
         }
     }
 
-    pub(crate) fn prestige(&mut self, bar_len: usize) {
+    /// Claims the prestige points, completing the current challenge.
+    /// Returns the challenge if there was one.
+    pub(crate) fn prestige(&mut self, bar_len: usize) -> Option<Challenge> {
         self.current += self.claimable_prestige(bar_len);
+        let completed = self.challenge.take()?;
+        self.completed_challenges.insert(completed);
+        Some(completed)
+    }
+
+    /// Whether the rule of `challenge` applies right now.
+    pub(crate) fn in_challenge(&self, challenge: Challenge) -> bool {
+        self.challenge == Some(challenge)
+    }
+
+    pub(crate) fn has_reward(&self, challenge: Challenge) -> bool {
+        self.completed_challenges.contains(&challenge)
     }
 
     pub(crate) fn cost(&self, upgrade: PrestigeUpgrade) -> Float {
-        Float::from(2_usize.pow(self.get_level(upgrade) as u32))
+        Float::from(
+            balance::get()
+                .prestige
+                .upgrade_cost_base
+                .pow(self.get_level(upgrade) as u32),
+        )
     }
 
     fn get_level(&self, upgrade: PrestigeUpgrade) -> usize {
@@ -43,7 +74,7 @@ impl Prestige {
     pub(crate) fn is_max_level(&self, upgrade: PrestigeUpgrade) -> bool {
         upgrade
             .max_level()
-            .map_or(false, |max| self.get_level(upgrade) >= max)
+            .is_some_and(|max| self.get_level(upgrade) >= max)
     }
 
     pub(crate) fn can_afford(&self, upgrade: PrestigeUpgrade) -> bool {
@@ -94,7 +125,7 @@ pub(crate) enum PrestigeUpgrade {
 }
 
 impl PrestigeUpgrade {
-    fn max_level(self) -> Option<usize> {
+    pub(crate) fn max_level(self) -> Option<usize> {
         use PrestigeUpgrade::*;
         match self {
             CompleteFaster => None,
@@ -111,3 +142,49 @@ impl PrestigeUpgrade {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn claimable_prestige_requires_threshold() {
+        let prestige = Prestige::new();
+        assert_eq!(prestige.claimable_prestige(9), Float(0.));
+        assert_eq!(prestige.claimable_prestige(15), Float(1.5));
+    }
+
+    #[test]
+    fn prestige_completes_the_challenge() {
+        let mut prestige = Prestige::new();
+        prestige.challenge = Some(Challenge::NoTransfer);
+        assert_eq!(prestige.prestige(10), Some(Challenge::NoTransfer));
+        assert_eq!(prestige.challenge, None);
+        assert!(prestige.has_reward(Challenge::NoTransfer));
+        assert_eq!(prestige.claimable_prestige(10), Float(1.1));
+        assert_eq!(prestige.prestige(10), None);
+    }
+
+    #[test]
+    fn purchase_doubles_cost() {
+        let mut prestige = Prestige::new();
+        prestige.current = Float(3.);
+        assert!(prestige.try_purchase_upgrade(PrestigeUpgrade::CompleteFaster));
+        assert_eq!(prestige.cost(PrestigeUpgrade::CompleteFaster), Float(2.));
+        assert!(prestige.try_purchase_upgrade(PrestigeUpgrade::CompleteFaster));
+        assert_eq!(prestige.current, Float(0.));
+        assert!(!prestige.try_purchase_upgrade(PrestigeUpgrade::CompleteFaster));
+        assert_eq!(prestige.level(PrestigeUpgrade::CompleteFaster), 2);
+    }
+
+    #[test]
+    fn cannot_purchase_past_max_level() {
+        let mut prestige = Prestige::new();
+        prestige.current = Float(1e9);
+        prestige
+            .upgrades
+            .insert(PrestigeUpgrade::UpgradeAnyButton, 8);
+        assert!(prestige.is_max_level(PrestigeUpgrade::UpgradeAnyButton));
+        assert!(!prestige.try_purchase_upgrade(PrestigeUpgrade::UpgradeAnyButton));
+    }
+}
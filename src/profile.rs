@@ -0,0 +1,233 @@
+use anyhow::Context;
+use chrono::{DateTime, Local};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::save;
+
+/// The profile played in plain mode, and the one an old `save.json` is
+/// moved to.
+pub(crate) const DEFAULT: &str = "default";
+
+/// Save files kept as `<name>.json` in one directory, by default
+/// `$XDG_DATA_HOME/antsy/profiles`.
+pub(crate) struct Profiles {
+    dir: PathBuf,
+}
+
+/// What the picker shows about a profile.
+#[derive(Clone, Debug)]
+pub(crate) struct ProfileInfo {
+    pub(crate) name: String,
+    pub(crate) summary: Option<Summary>,
+    pub(crate) last_played: Option<DateTime<Local>>,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct Summary {
+    pub(crate) bars: usize,
+    pub(crate) prestige_points: f64,
+}
+
+impl Profiles {
+    /// Opens `dir`, or the default directory if it's `None`, creating it
+    /// if needed.
+    pub(crate) fn open(dir: Option<&str>) -> anyhow::Result<Self> {
+        let dir = match dir {
+            Some(dir) => PathBuf::from(dir),
+            None => dirs::data_dir()
+                .context("Could not find a data directory, pass --data-dir")?
+                .join("antsy")
+                .join("profiles"),
+        };
+        fs::create_dir_all(&dir).with_context(|| format!("Could not create {dir:?}"))?;
+        Ok(Self { dir })
+    }
+
+    pub(crate) fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{name}.json"))
+    }
+
+    pub(crate) fn exists(&self, name: &str) -> bool {
+        self.path(name).exists()
+    }
+
+    /// Copies the save file of versions before profiles into the default
+    /// profile, unless there already is one, and renames it to
+    /// `<legacy>.migrated` so that it's only copied once. Returns whether
+    /// it did.
+    pub(crate) fn migrate(&self, legacy: &Path) -> anyhow::Result<bool> {
+        if !legacy.is_file() || self.exists(DEFAULT) {
+            return Ok(false);
+        }
+        fs::copy(legacy, self.path(DEFAULT))
+            .with_context(|| format!("Could not copy {legacy:?} into the {DEFAULT} profile"))?;
+        let mut migrated = legacy.as_os_str().to_owned();
+        migrated.push(".migrated");
+        fs::rename(legacy, &migrated)
+            .with_context(|| format!("Could not rename {legacy:?} to {migrated:?}"))?;
+        Ok(true)
+    }
+
+    /// Every profile, most recently played first.
+    pub(crate) fn list(&self) -> anyhow::Result<Vec<ProfileInfo>> {
+        let mut profiles = vec![];
+        let entries =
+            fs::read_dir(&self.dir).with_context(|| format!("Could not read {:?}", self.dir))?;
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            let Some(name) = path.file_stem().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            // A profile that doesn't parse is still listed, so it can be
+            // renamed or deleted from the picker.
+            let summary = fs::read_to_string(&path)
+                .ok()
+                .and_then(|contents| serde_json::from_str::<save::App>(&contents).ok())
+                .map(|save| Summary {
+                    bars: save.bar_count(),
+                    prestige_points: save.prestige_points(),
+                });
+            let last_played = fs::metadata(&path)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .map(DateTime::from);
+            profiles.push(ProfileInfo {
+                name: name.to_owned(),
+                summary,
+                last_played,
+            });
+        }
+        profiles.sort_by(|a, b| {
+            b.last_played
+                .cmp(&a.last_played)
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        Ok(profiles)
+    }
+
+    pub(crate) fn create(&self, name: &str) -> anyhow::Result<()> {
+        self.check_new_name(name)?;
+        save::App::fresh().write(&self.path(name).to_string_lossy())
+    }
+
+    pub(crate) fn rename(&self, from: &str, to: &str) -> anyhow::Result<()> {
+        self.check_new_name(to)?;
+        fs::rename(self.path(from), self.path(to))
+            .with_context(|| format!("Could not rename {from} to {to}"))
+    }
+
+    pub(crate) fn duplicate(&self, from: &str, to: &str) -> anyhow::Result<()> {
+        self.check_new_name(to)?;
+        fs::copy(self.path(from), self.path(to))
+            .with_context(|| format!("Could not copy {from} to {to}"))?;
+        Ok(())
+    }
+
+    pub(crate) fn delete(&self, name: &str) -> anyhow::Result<()> {
+        fs::remove_file(self.path(name)).with_context(|| format!("Could not delete {name}"))
+    }
+
+    fn check_new_name(&self, name: &str) -> anyhow::Result<()> {
+        check_name(name)?;
+        if self.exists(name) {
+            anyhow::bail!("Profile {name} already exists");
+        }
+        Ok(())
+    }
+}
+
+/// Checks that `name` stays a file in the profiles directory.
+pub(crate) fn check_name(name: &str) -> anyhow::Result<()> {
+    if name.is_empty() {
+        anyhow::bail!("Profile name can't be empty");
+    }
+    if name.starts_with('.') || name.contains(['/', '\\']) {
+        anyhow::bail!("Profile name can't start with . or contain slashes");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profiles(test: &str) -> Profiles {
+        let dir = std::env::temp_dir().join(format!("antsy-test-profiles-{test}"));
+        let _ = fs::remove_dir_all(&dir);
+        Profiles::open(dir.to_str()).unwrap()
+    }
+
+    fn names(profiles: &Profiles) -> Vec<String> {
+        let mut names: Vec<_> = profiles
+            .list()
+            .unwrap()
+            .into_iter()
+            .map(|info| info.name)
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn create_rename_duplicate_delete() {
+        let profiles = profiles("manage");
+        profiles.create("adam").unwrap();
+        profiles.duplicate("adam", "eve").unwrap();
+        profiles.rename("adam", "abel").unwrap();
+        assert_eq!(names(&profiles), ["abel", "eve"]);
+
+        profiles.delete("eve").unwrap();
+        assert_eq!(names(&profiles), ["abel"]);
+    }
+
+    #[test]
+    fn new_profiles_have_a_summary() {
+        let profiles = profiles("summary");
+        profiles.create("new").unwrap();
+        let info = &profiles.list().unwrap()[0];
+        let summary = info.summary.unwrap();
+        assert_eq!(summary.bars, 0);
+        assert_eq!(summary.prestige_points, 0.);
+        assert!(info.last_played.is_some());
+    }
+
+    #[test]
+    fn broken_saves_are_listed() {
+        let profiles = profiles("broken");
+        fs::write(profiles.path("broken"), "{").unwrap();
+        let info = &profiles.list().unwrap()[0];
+        assert_eq!(info.name, "broken");
+        assert!(info.summary.is_none());
+    }
+
+    #[test]
+    fn names_are_checked() {
+        let profiles = profiles("names");
+        profiles.create("taken").unwrap();
+        assert!(profiles.create("").is_err());
+        assert!(profiles.create("../escape").is_err());
+        assert!(profiles.create(".hidden").is_err());
+        assert!(profiles.create("taken").is_err());
+        assert!(profiles.duplicate("taken", "taken").is_err());
+    }
+
+    #[test]
+    fn old_save_moves_to_the_default_profile() {
+        let profiles = profiles("migrate");
+        let legacy = profiles.dir.with_extension("save.json");
+        fs::write(&legacy, "{}").unwrap();
+        assert!(profiles.migrate(&legacy).unwrap());
+        assert_eq!(fs::read_to_string(profiles.path(DEFAULT)).unwrap(), "{}");
+        assert!(!legacy.exists());
+        fs::remove_file(profiles.dir.with_extension("save.json.migrated")).unwrap();
+
+        fs::write(&legacy, "[]").unwrap();
+        assert!(!profiles.migrate(&legacy).unwrap());
+        assert_eq!(fs::read_to_string(profiles.path(DEFAULT)).unwrap(), "{}");
+        fs::remove_file(&legacy).unwrap();
+    }
+}
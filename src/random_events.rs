@@ -0,0 +1,233 @@
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::balance;
+use crate::float::Float;
+
+/// A splitmix64 generator. Its whole state is one number, so that it can
+/// be kept in the save and replays roll the same events.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Rng(pub(crate) u64);
+
+impl Rng {
+    /// Seeded from the current time, for new games.
+    pub(crate) fn from_time() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_nanos() as u64);
+        Self(nanos)
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniformly picked from [0, 1).
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniformly picked from [0, n).
+    pub(crate) fn below(&mut self, n: usize) -> usize {
+        (self.next_f64() * n as f64) as usize
+    }
+}
+
+/// Something that happens by chance now and then, for a limited time.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum RandomEvent {
+    /// The bar's next completion gains more
+    GoldenBar { number: usize },
+    /// All bars are faster
+    SpeedSurge,
+    /// Global upgrades are cheaper
+    MarketCrash,
+}
+
+impl RandomEvent {
+    pub(crate) fn description(self) -> String {
+        let balance = &balance::get().random_events;
+        match self {
+            RandomEvent::GoldenBar { number } => format!(
+                "Golden bar: #{number} gains x{} on its next completion",
+                balance.golden_bar_gain_multiplier
+            ),
+            RandomEvent::SpeedSurge => format!(
+                "Speed surge: bars are x{} faster",
+                balance.speed_surge_multiplier
+            ),
+            RandomEvent::MarketCrash => format!(
+                "Market crash: global upgrades are {}% off",
+                ((1. - balance.market_crash_discount) * 100.).round()
+            ),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct ActiveEvent {
+    pub(crate) event: RandomEvent,
+    pub(crate) until: Instant,
+}
+
+/// The random event going on, if any, and when the next one starts. One
+/// is scheduled when the previous one ends.
+pub(crate) struct RandomEvents {
+    pub(crate) rng: Rng,
+    pub(crate) next_at: Instant,
+    pub(crate) active: Option<ActiveEvent>,
+}
+
+impl RandomEvents {
+    pub(crate) fn new(mut rng: Rng, now: Instant) -> Self {
+        let next_at = now + Self::interval(&mut rng);
+        Self {
+            rng,
+            next_at,
+            active: None,
+        }
+    }
+
+    fn interval(rng: &mut Rng) -> Duration {
+        balance::get()
+            .random_events
+            .mean_interval()
+            .mul_f64(0.5 + rng.next_f64())
+    }
+
+    /// Ends the active event once its time is up, and starts the next one
+    /// when it's due if events are `enabled`. Returns the started event.
+    pub(crate) fn tick(
+        &mut self,
+        now: Instant,
+        bar_numbers: &[usize],
+        enabled: bool,
+    ) -> Option<RandomEvent> {
+        if self.active.is_some_and(|active| active.until <= now) {
+            self.end(now);
+        }
+        if !enabled || self.active.is_some() || now < self.next_at {
+            return None;
+        }
+        let event = match self.rng.below(3) {
+            0 if !bar_numbers.is_empty() => RandomEvent::GoldenBar {
+                number: bar_numbers[self.rng.below(bar_numbers.len())],
+            },
+            0 | 1 => RandomEvent::SpeedSurge,
+            _ => RandomEvent::MarketCrash,
+        };
+        self.active = Some(ActiveEvent {
+            event,
+            until: now + balance::get().random_events.duration(event),
+        });
+        Some(event)
+    }
+
+    /// Ends the active event, if any, and schedules the next one.
+    pub(crate) fn end(&mut self, now: Instant) {
+        self.active = None;
+        self.next_at = now + Self::interval(&mut self.rng);
+    }
+
+    /// How long the active event has left.
+    pub(crate) fn remaining(&self, now: Instant) -> Option<(RandomEvent, Duration)> {
+        self.active
+            .map(|active| (active.event, active.until.saturating_duration_since(now)))
+    }
+
+    fn is_active(&self, event: RandomEvent) -> bool {
+        self.active.is_some_and(|active| active.event == event)
+    }
+
+    /// What the gain of bar `number` is multiplied by.
+    pub(crate) fn gain_multiplier(&self, number: usize) -> Float {
+        if self.is_active(RandomEvent::GoldenBar { number }) {
+            Float(balance::get().random_events.golden_bar_gain_multiplier)
+        } else {
+            Float(1.)
+        }
+    }
+
+    /// Ends the golden bar event once bar `number` has completed.
+    pub(crate) fn completed(&mut self, number: usize, now: Instant) {
+        if self.is_active(RandomEvent::GoldenBar { number }) {
+            self.end(now);
+        }
+    }
+
+    pub(crate) fn speed_multiplier(&self) -> Float {
+        if self.is_active(RandomEvent::SpeedSurge) {
+            Float(balance::get().random_events.speed_surge_multiplier)
+        } else {
+            Float(1.)
+        }
+    }
+
+    /// What global upgrade costs are multiplied by.
+    pub(crate) fn cost_multiplier(&self) -> Float {
+        if self.is_active(RandomEvent::MarketCrash) {
+            Float(balance::get().random_events.market_crash_discount)
+        } else {
+            Float(1.)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_same_seed_rolls_the_same_events() {
+        let now = Instant::now();
+        let roll = || {
+            let mut events = RandomEvents::new(Rng(42), now);
+            (0..20)
+                .filter_map(|minute| {
+                    let now = now + Duration::from_secs(60 * minute);
+                    events.tick(now, &[1, 2, 3], true)
+                })
+                .collect::<Vec<_>>()
+        };
+        let events = roll();
+        assert!(!events.is_empty());
+        assert_eq!(events, roll());
+    }
+
+    #[test]
+    fn golden_bar_ends_when_it_completes() {
+        let now = Instant::now();
+        let mut events = RandomEvents::new(Rng(1), now);
+        events.active = Some(ActiveEvent {
+            event: RandomEvent::GoldenBar { number: 2 },
+            until: now + Duration::from_secs(60),
+        });
+        assert_eq!(events.gain_multiplier(1), Float(1.));
+        assert_eq!(events.gain_multiplier(2), Float(10.));
+
+        events.completed(2, now);
+        assert_eq!(events.gain_multiplier(2), Float(1.));
+        assert!(events.next_at > now);
+        assert_eq!(events.tick(now, &[2], true), None);
+    }
+
+    #[test]
+    fn disabled_events_only_run_out() {
+        let now = Instant::now();
+        let mut events = RandomEvents::new(Rng(7), now);
+        events.active = Some(ActiveEvent {
+            event: RandomEvent::MarketCrash,
+            until: now + Duration::from_secs(1),
+        });
+        let later = now + Duration::from_secs(3600);
+        assert_eq!(events.tick(later, &[1], false), None);
+        assert_eq!(events.active, None);
+        assert_eq!(
+            events.tick(later + Duration::from_secs(3600), &[1], false),
+            None
+        );
+    }
+}
@@ -1,15 +1,335 @@
-use tui::{backend::Backend, Frame};
+use tui::{backend::Backend, layout::Rect, Frame};
 
 use crate::app::App;
+use crate::profile::ProfileInfo;
+use crate::theme::Theme;
+use crate::ui::profiles::Picker;
 use crate::ui::UiState;
 
+mod console;
 mod normal;
 mod prestige;
+mod profiles;
+mod research;
+mod share;
 mod util;
 
-pub(crate) fn ui<B: Backend>(f: &mut Frame<B>, app: &App) {
-    match app.ui.state {
-        UiState::Normal(n) => normal::render(f, app, n),
-        UiState::Prestige(p) => prestige::render(f, app, p),
+pub(crate) fn ui<B: Backend>(f: &mut Frame<B>, theme: &Theme, app: &App) {
+    match &app.ui.state {
+        UiState::Normal(n) => normal::render(f, theme, app, *n),
+        UiState::Prestige(p) => prestige::render(f, theme, app, *p),
+        UiState::Research(r) => research::render(f, theme, app, *r),
+        UiState::Share(s) => share::render(f, theme, s),
+    }
+    if let Some(console) = app.ui.console.as_ref().filter(|console| console.open) {
+        console::render(f, theme, console);
+    }
+}
+
+/// Whether `exported` can be shown whole on the share screen of a
+/// terminal of `size`.
+pub(crate) fn export_fits(size: Rect, exported: &str) -> bool {
+    share::fits(size, exported, 0)
+}
+
+pub(crate) fn picker<B: Backend>(
+    f: &mut Frame<B>,
+    theme: &Theme,
+    picker: &Picker,
+    profiles: &[ProfileInfo],
+) {
+    profiles::render(f, theme, picker, profiles)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use strum::*;
+    use tui::{backend::TestBackend, Terminal};
+
+    use super::*;
+    use crate::bar::Bar;
+    use crate::challenge::Challenge;
+    use crate::float::Float;
+    use crate::prestige::PrestigeUpgrade;
+    use crate::research::ResearchNode;
+    use crate::theme::Themes;
+    use crate::ui::{self, Ui};
+    use crate::upgrade::{GlobalUpgrade, Upgrade};
+
+    const SIZES: [(u16, u16); 3] = [(80, 24), (160, 40), (240, 60)];
+
+    /// Renders `app` at every size in `SIZES` and compares against the
+    /// files in `src/render/golden`. Run with `UPDATE_GOLDEN=1` to
+    /// rewrite them after an intended change.
+    fn assert_golden(name: &str, app: &App) {
+        assert_golden_with(name, |f| ui(f, &Themes::Dark.build(), app))
+    }
+
+    fn assert_golden_with(name: &str, draw: impl Fn(&mut Frame<TestBackend>)) {
+        for (width, height) in SIZES {
+            let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+            terminal.draw(&draw).unwrap();
+            let buffer = terminal.backend().buffer();
+            let mut actual = String::new();
+            for y in 0..height {
+                let line: String = (0..width)
+                    .map(|x| buffer.get(x, y).symbol.as_str())
+                    .collect();
+                actual.push_str(line.trim_end());
+                actual.push('\n');
+            }
+
+            let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                .join("src/render/golden")
+                .join(format!("{name}-{width}x{height}.txt"));
+            if std::env::var_os("UPDATE_GOLDEN").is_some() {
+                std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+                std::fs::write(&path, &actual).unwrap();
+                continue;
+            }
+            let expected = std::fs::read_to_string(&path).unwrap_or_else(|_| {
+                panic!("Missing {path:?}, run with UPDATE_GOLDEN=1 to create it")
+            });
+            assert!(
+                expected == actual,
+                "{path:?} differs, got:\n{actual}\nRun with UPDATE_GOLDEN=1 if this is intended"
+            );
+        }
+    }
+
+    fn app_with_bars(count: usize) -> App {
+        let mut app = App::for_test();
+        app.bars_to_spawn = 0;
+        for i in 0..count {
+            let mut bar = Bar::new(count - i);
+            bar.progress = Float((i * 7 % 100) as f64);
+            bar.gathered = Float(1.7_f64.powi(i as i32));
+            bar.level = 1 + i;
+            bar.exp = Float(i as f64);
+            bar.level_speed = Float(1. + i as f64 / 10.);
+            for (n, upgrade) in Upgrade::iter().enumerate() {
+                bar.upgrades.insert(upgrade, (i + n) / (n + 1));
+            }
+            app.bars.push_back(bar);
+        }
+        app.last_bar_number = count;
+        app
+    }
+
+    #[test]
+    fn no_bars() {
+        assert_golden("no-bars", &App::for_test());
+    }
+
+    #[test]
+    fn forty_bars() {
+        let mut app = app_with_bars(40);
+        app.bars[3].transfer_ratio = Float(0.25);
+        app.bars[5].charge_ready_at = Some(app.tick + std::time::Duration::from_secs(12));
+        app.ui.state = ui::UiState::Normal(ui::Normal {
+            highlight: ui::normal::Highlight::Bar {
+                upgrade: Upgrade::Double,
+                row: 3,
+            },
+            ..ui::Normal::new()
+        });
+        assert_golden("forty-bars", &app);
+    }
+
+    #[test]
+    fn highlighted_global_upgrade() {
+        let mut app = app_with_bars(10);
+        app.global_upgrades.insert(GlobalUpgrade::Gain, 2);
+        app.ui.state = ui::UiState::Normal(ui::Normal {
+            highlight: ui::normal::Highlight::Global {
+                upgrade: GlobalUpgrade::Gain,
+            },
+            ..ui::Normal::new()
+        });
+        assert_golden("highlighted-global-upgrade", &app);
+    }
+
+    #[test]
+    fn event_log() {
+        use crate::events::Event;
+        use std::time::Duration;
+
+        let mut app = app_with_bars(6);
+        for event in [
+            Event::BarSpawned { number: 6 },
+            Event::LevelUp {
+                number: 2,
+                level: 7,
+                boost: Duration::from_secs(12),
+            },
+            Event::UpgradePurchased {
+                number: 3,
+                upgrade: Upgrade::Triple,
+                level: 2,
+            },
+            Event::GlobalUpgradePurchased {
+                upgrade: GlobalUpgrade::Speed,
+                level: 4,
+                automated: true,
+            },
+            Event::Saved,
+            Event::SaveFailed("Permission denied".to_owned()),
+        ] {
+            app.events.push(event);
+        }
+        assert_golden("event-log", &app);
+    }
+
+    #[test]
+    fn maxed_prestige_upgrade() {
+        let mut app = app_with_bars(12);
+        app.prestige.current = Float(5.);
+        app.prestige
+            .upgrades
+            .insert(PrestigeUpgrade::UpgradeAnyButton, 8);
+        app.ui = Ui::new(Some(ui::UiStates::Prestige), false);
+        app.ui.state = ui::UiState::Prestige(ui::Prestige {
+            highlight: ui::prestige::Highlight::Upgrade(4),
+        });
+        assert_golden("maxed-prestige-upgrade", &app);
+    }
+
+    #[test]
+    fn challenges() {
+        let mut app = app_with_bars(12);
+        app.prestige.challenge = Some(Challenge::SlowSpawns);
+        app.prestige
+            .completed_challenges
+            .insert(Challenge::NoTransfer);
+        app.ui = Ui::new(Some(ui::UiStates::Prestige), false);
+        app.ui.state = ui::UiState::Prestige(ui::Prestige {
+            highlight: ui::prestige::Highlight::Challenge(1),
+        });
+        assert_golden("challenges", &app);
+    }
+
+    #[test]
+    fn research_tree() {
+        let mut app = app_with_bars(3);
+        app.research.points = Float(12.);
+        app.research.unlocked.insert(ResearchNode::Efficiency);
+        app.ui = Ui::new(Some(ui::UiStates::Research), false);
+        app.ui.state = ui::UiState::Research(ui::Research {
+            highlight: ResearchNode::Momentum,
+        });
+        assert_golden("research-tree", &app);
+    }
+
+    #[test]
+    fn random_event() {
+        use crate::random_events::{ActiveEvent, RandomEvent};
+
+        let mut app = app_with_bars(6);
+        app.random_events.active = Some(ActiveEvent {
+            event: RandomEvent::MarketCrash,
+            until: app.tick + std::time::Duration::from_secs(23),
+        });
+        app.events.push(crate::events::Event::RandomEventStarted {
+            event: RandomEvent::MarketCrash,
+        });
+        assert_golden("random-event", &app);
+    }
+
+    #[test]
+    fn share_screen() {
+        let mut app = app_with_bars(3);
+        app.ui.state = ui::UiState::Share(ui::Share {
+            input: "antsy1.".to_owned(),
+            exported: Some(format!("antsy1.{}.0123abcd", "0123456789-_".repeat(40))),
+            ..Default::default()
+        });
+        assert_golden("share", &app);
+    }
+
+    #[test]
+    fn long_export() {
+        let mut app = app_with_bars(3);
+        app.ui.state = ui::UiState::Share(ui::Share {
+            exported: Some(format!("antsy1.{}.0123abcd", "0123456789-_".repeat(160))),
+            export_file: Some("profiles/default.export.txt".to_owned()),
+            ..Default::default()
+        });
+        assert_golden("long-export", &app);
+    }
+
+    #[test]
+    fn dev_console() {
+        let mut app = app_with_bars(5);
+        app.ui = Ui::new(None, true);
+        let console = app.ui.console.as_mut().unwrap();
+        console.open = true;
+        console.input = "timewarp 1".to_owned();
+        console.output = "Spawned 5 bars".to_owned();
+        assert_golden("dev-console", &app);
+    }
+
+    #[test]
+    fn accessible_markers() {
+        let mut app = app_with_bars(12);
+        app.bars[1].boost_until = Some(app.tick + std::time::Duration::from_secs(1));
+        app.bars[1].charge_ready_at = Some(app.tick + std::time::Duration::from_secs(25));
+        app.ui.state = ui::UiState::Normal(ui::Normal {
+            highlight: ui::normal::Highlight::Bar {
+                upgrade: Upgrade::Gain,
+                row: 8,
+            },
+            ..ui::Normal::new()
+        });
+        assert_golden_with("accessible-markers", |f| {
+            ui(f, &Themes::Monochrome.build(), &app)
+        });
+    }
+
+    #[test]
+    fn theme_colors_are_used() {
+        let mut app = app_with_bars(2);
+        app.bars[0].boost_until = Some(app.tick + std::time::Duration::from_secs(1));
+        let theme = Themes::Solarized.build();
+        let mut terminal = Terminal::new(TestBackend::new(240, 60)).unwrap();
+        terminal.draw(|f| ui(f, &theme, &app)).unwrap();
+        let buffer = terminal.backend().buffer();
+        let bars_x = (0..buffer.area.width)
+            .find(|&x| buffer.get(x, 0).symbol == "B")
+            .unwrap();
+        assert_eq!(buffer.get(0, 0).bg, theme.background);
+        // Far enough right to be past the filled part of each gauge
+        let empty_x = bars_x + 30;
+        assert_eq!(buffer.get(empty_x, 1).fg, theme.boosted);
+        assert_eq!(buffer.get(empty_x, 2).fg, theme.bars[1]);
+    }
+
+    #[test]
+    fn profile_picker() {
+        use crate::profile::Summary;
+        use chrono::TimeZone;
+
+        let profiles = [
+            ProfileInfo {
+                name: "main".to_owned(),
+                summary: Some(Summary {
+                    bars: 42,
+                    prestige_points: 1234.5,
+                }),
+                last_played: Some(chrono::Local.ymd(2026, 10, 18).and_hms(12, 30, 0)),
+            },
+            ProfileInfo {
+                name: "broken".to_owned(),
+                summary: None,
+                last_played: None,
+            },
+        ];
+        let mut picker = Picker::new();
+        picker.prompt = Some(ui::profiles::Prompt::Rename {
+            name: "main2".to_owned(),
+        });
+        assert_golden_with("profile-picker", |f| {
+            super::picker(f, &Themes::Dark.build(), &picker, &profiles)
+        });
     }
 }
@@ -0,0 +1,23 @@
+use tui::{backend::Backend, layout::*, widgets::Clear, Frame};
+
+use crate::render::util::*;
+use crate::theme::Theme;
+use crate::ui::Console;
+
+pub(crate) fn render<B: Backend>(f: &mut Frame<B>, theme: &Theme, console: &Console) {
+    let size = f.size();
+    let height = 4.min(size.height);
+    let chunk = Rect {
+        y: size.y + size.height - height,
+        height,
+        ..size
+    };
+    f.render_widget(Clear, chunk);
+    let lines = rect_to_lines(render_border(f, theme, chunk, "Console"));
+    if let Some(chunk) = lines.first() {
+        render_left_text(f, theme, *chunk, &console.output);
+    }
+    if let Some(chunk) = lines.get(1) {
+        render_left_text(f, theme, *chunk, &format!("> {}_", console.input));
+    }
+}
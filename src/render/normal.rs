@@ -1,44 +1,97 @@
 use format_num::format_num;
+use std::time::Duration;
 use strum::*;
 use tui::{backend::Backend, layout::*, style::*, widgets::*, Frame};
 
 use crate::app::App;
+use crate::balance;
 use crate::bar::Bar;
 use crate::float::Float;
 use crate::render::util::*;
-use crate::ui::normal::Highlight;
+use crate::theme::Theme;
+use crate::ui::normal::{Highlight, LOG_LINES};
 use crate::ui::Normal;
 use crate::upgrade::{GlobalUpgrade, Upgrade};
 
-const UPGRADE_0_WIDTH: u16 = "| x1.3 SPD: DD.DM |".len() as u16;
-const UPGRADE_1_WIDTH: u16 = "| +1: DD.DM |".len() as u16;
-const UPGRADE_2_WIDTH: u16 = "| x2: DD.DM from #NNN |".len() as u16;
-const UPGRADE_3_WIDTH: u16 = "| x3: DD.DM from #NNN |".len() as u16;
-const UPGRADE_4_WIDTH: u16 = "| x4: DD.DM from #NNN |".len() as u16;
+/// One per upgrade, in the order of `Upgrade::iter`.
+const UPGRADE_WIDTHS: [u16; Upgrade::COUNT] = [
+    "| x1.3 SPD: DD.DM |".len() as u16,
+    "| +1: DD.DM |".len() as u16,
+    "| x2: DD.DM from #NNN |".len() as u16,
+    "| x3: DD.DM from #NNN |".len() as u16,
+    "| x4: DD.DM from #NNN |".len() as u16,
+    "| x5: DD.DM from #NNN |".len() as u16,
+];
 
-pub(crate) fn render<B: Backend>(f: &mut Frame<B>, app: &App, ui_state: Normal) {
+/// The same with short labels on tight buttons, which leave out the speed
+/// multiplier and the bar that pays.
+const COMPACT_UPGRADE_WIDTHS: [u16; Upgrade::COUNT] = [
+    "|SPD: DD.DM|".len() as u16,
+    "|+1: DD.DM|".len() as u16,
+    "|x2: DD.DM|".len() as u16,
+    "|x3: DD.DM|".len() as u16,
+    "|x4: DD.DM|".len() as u16,
+    "|x5: locked|".len() as u16,
+];
+
+/// The Bars column is never narrower than this, lower priority columns
+/// are left out instead. It fits the charge cooldown, which isn't shown
+/// anywhere else.
+const MIN_BAR_WIDTH: u16 = " 100% charge in NNs ".len() as u16;
+
+fn upgrade_width(theme: &Theme, compact: bool, i: usize) -> u16 {
+    if compact {
+        COMPACT_UPGRADE_WIDTHS[i]
+    } else if theme.markers {
+        UPGRADE_WIDTHS[i] + MARKERS_WIDTH
+    } else {
+        UPGRADE_WIDTHS[i]
+    }
+}
+
+pub(crate) fn render<B: Backend>(f: &mut Frame<B>, theme: &Theme, app: &App, ui_state: Normal) {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Percentage(80), Constraint::Length(10)])
+        .constraints([
+            Constraint::Min(0),
+            Constraint::Length(4),
+            Constraint::Length(LOG_LINES as u16 + 2),
+        ])
         .split(f.size());
 
     let top = chunks[0];
     let bottom = chunks[1];
+    let log = chunks[2];
 
     const BORDERS: u16 = 2;
     const VALUES_WIDTH: u16 = " DD.DM ".len() as u16;
-    const TRANSFERRED_WIDTH: u16 = " +DD.DM / vDD.DM ".len() as u16;
+    const TRANSFERRED_WIDTH: u16 = " NN% +DD.DM / vDD.DM ".len() as u16;
     const LEVEL_WIDTH: u16 = 18;
     const SPEED_WIDTH: u16 = 12;
-    const UPGRADES_WIDTH: u16 =
-        UPGRADE_0_WIDTH + UPGRADE_1_WIDTH + UPGRADE_2_WIDTH + UPGRADE_3_WIDTH + UPGRADE_4_WIDTH;
-    let bar_width: u16 = top.width
-        - VALUES_WIDTH
-        - TRANSFERRED_WIDTH
-        - LEVEL_WIDTH
-        - SPEED_WIDTH
-        - UPGRADES_WIDTH
-        - BORDERS * 5;
+    let upgrades_width = |compact| {
+        (0..Upgrade::COUNT)
+            .map(|i| upgrade_width(theme, compact, i))
+            .sum::<u16>()
+            + BORDERS
+    };
+    let transferred_width = TRANSFERRED_WIDTH + BORDERS;
+    // Bars, values, level and speed are always shown. Narrow terminals
+    // shorten the upgrade labels, then leave out the upgrades, then the
+    // transfer column. Markers need the usual padding to be read.
+    let shown_width = MIN_BAR_WIDTH + VALUES_WIDTH + LEVEL_WIDTH + SPEED_WIDTH + BORDERS * 4;
+    let fits = |width: u16| shown_width + width <= top.width;
+    let compact = !fits(transferred_width + upgrades_width(false)) && !theme.markers;
+    let upgrades_width = upgrades_width(compact);
+    let (transferred_width, upgrades_width) = if fits(transferred_width + upgrades_width) {
+        (transferred_width, upgrades_width)
+    } else if fits(transferred_width) {
+        (transferred_width, 0)
+    } else {
+        (0, 0)
+    };
+    let bar_width = top.width.saturating_sub(
+        VALUES_WIDTH + LEVEL_WIDTH + SPEED_WIDTH + BORDERS * 3 + transferred_width + upgrades_width,
+    );
 
     let chunks = Layout::default()
         .direction(Direction::Horizontal)
@@ -46,10 +99,10 @@ pub(crate) fn render<B: Backend>(f: &mut Frame<B>, app: &App, ui_state: Normal)
             [
                 Constraint::Length(bar_width),
                 Constraint::Length(VALUES_WIDTH + BORDERS),
-                Constraint::Length(TRANSFERRED_WIDTH + BORDERS),
+                Constraint::Length(transferred_width),
                 Constraint::Length(LEVEL_WIDTH + BORDERS),
                 Constraint::Length(SPEED_WIDTH + BORDERS),
-                Constraint::Length(UPGRADES_WIDTH + BORDERS),
+                Constraint::Length(upgrades_width),
             ]
             .as_ref(),
         )
@@ -61,131 +114,255 @@ pub(crate) fn render<B: Backend>(f: &mut Frame<B>, app: &App, ui_state: Normal)
     let level = chunks[3];
     let speed = chunks[4];
     let bar_upgrades = chunks[5];
-    assert_eq!(bar_upgrades.width, 103);
 
-    render_bars(f, app, bars);
+    render_bars(f, theme, app, bars);
 
-    render_bar_values(f, app, values);
-    render_transferred(f, app, transferred);
-    render_level(f, app, level);
-    render_speed(f, app, speed);
-    render_bar_upgrades(f, app, ui_state, bar_upgrades);
-    render_global_upgrades(f, app, ui_state, bottom);
+    render_bar_values(f, theme, app, values);
+    if transferred.width > 0 {
+        render_transferred(f, theme, app, ui_state, transferred);
+    }
+    render_level(f, theme, app, level);
+    render_speed(f, theme, app, speed);
+    if bar_upgrades.width > 0 {
+        render_bar_upgrades(f, theme, app, ui_state, compact, bar_upgrades);
+    }
+    render_global_upgrades(f, theme, app, ui_state, bottom);
+    render_log(f, theme, app, ui_state, log);
 }
 
-fn render_transferred<B: Backend>(f: &mut Frame<B>, app: &App, chunk: Rect) {
-    let chunk = render_border(f, chunk, "Transfer");
+/// The random event going on, if any, above the latest events, or older
+/// ones when scrolled back with PgUp.
+fn render_log<B: Backend>(
+    f: &mut Frame<B>,
+    theme: &Theme,
+    app: &App,
+    ui_state: Normal,
+    chunk: Rect,
+) {
+    let title = match ui_state.log_scroll {
+        0 => "Log".to_owned(),
+        scroll => format!("Log ({scroll} newer, PgDn)"),
+    };
+    let chunk = render_border(f, theme, chunk, &title);
+    let mut chunks = rect_to_lines(chunk);
+    if let Some((event, remaining)) = app.random_events.remaining(app.tick) {
+        if !chunks.is_empty() {
+            let text = format!(
+                "{}, {}s left",
+                event.description(),
+                remaining.as_secs_f64().ceil()
+            );
+            let line = mk_text_line_fg(theme, theme.boosted, &text).alignment(Alignment::Left);
+            f.render_widget(line, chunks.remove(0));
+        }
+    }
+    let events = app.events.page(chunks.len(), ui_state.log_scroll);
+    for (event, chunk) in events.zip(chunks) {
+        render_left_text(f, theme, chunk, &event.to_string());
+    }
+}
+
+/// Each bar's transfer ratio, which the highlighted bar's row can change,
+/// and what its last completion gained and passed on.
+fn render_transferred<B: Backend>(
+    f: &mut Frame<B>,
+    theme: &Theme,
+    app: &App,
+    ui_state: Normal,
+    chunk: Rect,
+) {
+    let chunk = render_border(f, theme, chunk, "Transfer");
     let chunks = rect_to_lines(chunk);
-    for (bar, chunk) in app.bars.iter().zip(chunks.into_iter()) {
-        if let Some(completion) = bar.recent_completion(app.tick) {
-            let gain = completion.gain;
-            match completion.transferred {
-                None => render_text(f, chunk, &format_num!("+.3s", gain)),
-                Some(transferred) => render_text(
-                    f,
-                    chunk,
-                    &format!(
-                        "{gain} / ↓{transferred}",
-                        gain = format_num!("+.3s", gain),
-                        transferred = format_num!(".3s", transferred)
-                    ),
+    let last = app.bars.len().saturating_sub(1);
+    for (i, (bar, chunk)) in app.bars.iter().zip(chunks).enumerate() {
+        let ratio = if i == last {
+            "".to_owned()
+        } else {
+            format!("{:.0}% ", app.transfer_ratio(i).0 * 100.)
+        };
+        let completion = match bar.recent_completion(app.tick) {
+            None => "".to_owned(),
+            Some(completion) => match completion.transferred {
+                None => format_num!("+.3s", completion.gain),
+                Some(transferred) => format!(
+                    "{gain} / ↓{transferred}",
+                    gain = format_num!("+.3s", completion.gain),
+                    transferred = format_num!(".3s", transferred)
                 ),
-            }
-        }
+            },
+        };
+        let color = match ui_state.highlight {
+            Highlight::Bar { row, .. } if row == i => theme.highlight,
+            _ => theme.text,
+        };
+        f.render_widget(
+            mk_text_line_fg(theme, color, &format!("{ratio}{completion}")),
+            chunk,
+        );
     }
 }
 
-fn render_bars<B: Backend>(f: &mut Frame<B>, app: &App, chunk: Rect) {
-    let chunk = render_border(f, chunk, "Bars");
-    let colors = vec![Color::Blue, Color::White, Color::Green, Color::Red];
+fn render_bars<B: Backend>(f: &mut Frame<B>, theme: &Theme, app: &App, chunk: Rect) {
+    let chunk = render_border(f, theme, chunk, "Bars");
     let chunks: Vec<_> = rect_to_lines(chunk);
-    for (i, (bar, chunk)) in app.bars.iter().zip(chunks.into_iter()).enumerate() {
-        let color = if bar.is_boosted(app.tick) {
-            Color::Yellow
+    for (i, (bar, chunk)) in app.bars.iter().zip(chunks).enumerate() {
+        let boosted = bar.is_boosted(app.tick);
+        let color = if boosted {
+            theme.boosted
         } else {
-            colors[i % colors.len()]
+            theme.bars[i % theme.bars.len()]
         };
-        f.render_widget(mk_gauge(bar, color), chunk);
+        let cooldown = bar.charge_cooldown(app.tick);
+        if theme.markers {
+            f.render_widget(
+                mk_text_gauge(theme, bar, color, boosted, cooldown, chunk.width),
+                chunk,
+            );
+        } else {
+            f.render_widget(mk_gauge(theme, bar, color, cooldown), chunk);
+        }
     }
 }
 
+/// A gauge that doesn't need colors to show progress, like
+/// `█████░░░░░  52% boosted`.
+fn mk_text_gauge(
+    theme: &Theme,
+    bar: &Bar,
+    color: Color,
+    boosted: bool,
+    cooldown: Option<Duration>,
+    width: u16,
+) -> Paragraph<'static> {
+    let percent = bar.progress.0.clamp(0., 100.) as usize;
+    let label = if boosted {
+        format!(" {percent:>3}% boosted{}", cooldown_label(cooldown))
+    } else {
+        format!(" {percent:>3}%{}", cooldown_label(cooldown))
+    };
+    let width = (width as usize).saturating_sub(label.len());
+    let filled = width * percent / 100;
+    let text = format!(
+        "{}{}{label}",
+        "█".repeat(filled),
+        "░".repeat(width - filled)
+    );
+    Paragraph::new(text).style(Style::default().fg(color).bg(theme.background))
+}
+
 impl Upgrade {
-    fn label(self, number: usize, level: usize) -> String {
+    fn label(self, number: usize, cost: Option<Float>) -> String {
+        use Upgrade::*;
+        let cost = cost_label(cost);
+        match self {
+            Speed => format!(
+                "x{} SPD: {cost}",
+                (balance::get().bar.speed_upgrade_multiplier * 10.).round() / 10.
+            ),
+            Gain => format!("+1: {cost}"),
+            Double => format!("x2: {cost} from #{}", number as i64 + Double.cost_target()),
+            Triple => format!("x3: {cost} from #{}", number as i64 + Triple.cost_target()),
+            Quadruple => format!(
+                "x4: {cost} from #{}",
+                number as i64 + Quadruple.cost_target()
+            ),
+            Quintuple => format!(
+                "x5: {cost} from #{}",
+                number as i64 + Quintuple.cost_target()
+            ),
+        }
+    }
+
+    fn short_label(self, cost: Option<Float>) -> String {
         use Upgrade::*;
-        let cost = self.cost(level);
+        let cost = cost_label(cost);
         match self {
-            Speed => format!("x1.3 SPD: {cost}"),
+            Speed => format!("SPD: {cost}"),
             Gain => format!("+1: {cost}"),
-            Double => format!("x2: {cost} from #{}", number + 1),
-            Triple => format!("x3: {cost} from #{}", number + 4),
-            Quadruple => format!("x4: {cost} from #{}", number + 7),
+            Double => format!("x2: {cost}"),
+            Triple => format!("x3: {cost}"),
+            Quadruple => format!("x4: {cost}"),
+            Quintuple => format!("x5: {cost}"),
         }
     }
 }
 
 impl GlobalUpgrade {
-    fn label(self, level: usize) -> String {
+    fn label(self, cost: Option<Float>) -> String {
         use GlobalUpgrade::*;
-        let cost = self.cost(level);
+        let cost = cost_label(cost);
         match self {
-            Speed => format!("+5% SPD | {cost} "),
+            Speed => format!(
+                "+{:.0}% SPD | {cost} ",
+                (balance::get().bar.global_speed_multiplier - 1.) * 100.
+            ),
             ExpBoost => format!("+1s Level Up Boost | {cost}"),
-            ProgressBars => format!("2 Progress Bars | {cost}"),
+            ProgressBars => format!(
+                "{} Progress Bars | {cost}",
+                balance::get().app.bars_per_progress_bars_upgrade
+            ),
             Gain => format!("+1 Gain | {cost}"),
             ExpGain => format!("+1 Exp Gain | {cost}"),
+            ResearchGain => format!("+1 Research Gain | {cost}"),
         }
     }
 }
 
-fn render_speed<B: Backend>(f: &mut Frame<B>, app: &App, chunk: Rect) {
-    let chunk = render_border(f, chunk, "Speed");
+/// The cost on an upgrade button, or why it can't be bought.
+fn cost_label(cost: Option<Float>) -> String {
+    match cost {
+        Some(cost) => cost.to_string(),
+        None => "locked".to_owned(),
+    }
+}
+
+fn render_speed<B: Backend>(f: &mut Frame<B>, theme: &Theme, app: &App, chunk: Rect) {
+    let chunk = render_border(f, theme, chunk, "Speed");
     let chunks = rect_to_lines(chunk);
-    for (_i, (bar, chunk)) in app.bars.iter().zip(chunks.into_iter()).enumerate() {
-        let speed = bar.speed_multiplier(app.global_upgrades[&GlobalUpgrade::Speed]);
+    for (bar, chunk) in app.bars.iter().zip(chunks) {
+        let speed = bar.speed_multiplier(app.global_upgrades[&GlobalUpgrade::Speed], &app.research);
         let speed = ((speed.0 * 100.) as usize as f64) / 100.;
         let num = format_num::NumberFormat::new();
         render_text(
             f,
+            theme,
             chunk,
             &format!("x{} ({})", &num.format(".2", speed), bar.gain_exponent),
         );
     }
 }
 
-fn render_level<B: Backend>(f: &mut Frame<B>, app: &App, chunk: Rect) {
-    let chunk = render_border(f, chunk, "Level");
+fn render_level<B: Backend>(f: &mut Frame<B>, theme: &Theme, app: &App, chunk: Rect) {
+    let chunk = render_border(f, theme, chunk, "Level");
     let chunks = rect_to_lines(chunk);
-    for (_i, (bar, chunk)) in app.bars.iter().zip(chunks.into_iter()).enumerate() {
+    for (bar, chunk) in app.bars.iter().zip(chunks) {
         let level = bar.level;
-        let exp = Float::from(bar.exp);
-        let to_level = Float::from(bar.exp_for_next_level());
-        render_text(f, chunk, &format!("L{level} {exp}/{to_level}"));
+        let exp = bar.exp;
+        let to_level = bar.exp_for_next_level();
+        render_text(f, theme, chunk, &format!("L{level} {exp}/{to_level}"));
     }
 }
 
-fn render_bar_upgrades<B: Backend>(f: &mut Frame<B>, app: &App, ui_state: Normal, chunk: Rect) {
-    let chunk = render_border(f, chunk, "Upgrades");
+fn render_bar_upgrades<B: Backend>(
+    f: &mut Frame<B>,
+    theme: &Theme,
+    app: &App,
+    ui_state: Normal,
+    compact: bool,
+    chunk: Rect,
+) {
+    let chunk = render_border(f, theme, chunk, "Upgrades");
     let chunks = rect_to_lines(chunk);
-    assert_eq!(Upgrade::COUNT, 5);
-    for (i, (bar, chunk)) in app.bars.iter().zip(chunks.into_iter()).enumerate() {
+    for (i, (bar, chunk)) in app.bars.iter().zip(chunks).enumerate() {
         let chunks = Layout::default()
             .direction(Direction::Horizontal)
             .constraints(
                 (0..Upgrade::COUNT)
-                    .map(|i| {
-                        Constraint::Length(match i {
-                            0 => UPGRADE_0_WIDTH,
-                            1 => UPGRADE_1_WIDTH,
-                            2 => UPGRADE_2_WIDTH,
-                            3 => UPGRADE_3_WIDTH,
-                            4 => UPGRADE_4_WIDTH,
-                            _ => unreachable!(),
-                        })
-                    })
+                    .map(|i| Constraint::Length(upgrade_width(theme, compact, i)))
                     .collect::<Vec<_>>(),
             )
             .split(chunk);
-        for (upgrade, chunk) in Upgrade::iter().zip(chunks.into_iter()) {
+        for (upgrade, chunk) in Upgrade::iter().zip(chunks) {
             let highlight = match ui_state.highlight {
                 Highlight::None | Highlight::Global { .. } => false,
                 Highlight::Bar {
@@ -194,28 +371,45 @@ fn render_bar_upgrades<B: Backend>(f: &mut Frame<B>, app: &App, ui_state: Normal
                 } => i == highlight_row && upgrade == highlight_upgrade,
             };
             let can_afford = app.can_afford(i, upgrade);
-            let button = mk_button(
-                &upgrade.label(bar.number, bar.get_upgrade_u(upgrade)),
-                highlight,
-                can_afford,
-            );
+            let cost = app.upgrade_cost(i, upgrade);
+            let button = if compact {
+                mk_tight_button(theme, &upgrade.short_label(cost), highlight, can_afford)
+            } else {
+                mk_button(
+                    theme,
+                    &upgrade.label(bar.number, cost),
+                    highlight,
+                    can_afford,
+                )
+            };
             f.render_widget(button, chunk);
         }
     }
 }
 
-fn render_global_upgrades<B: Backend>(f: &mut Frame<B>, app: &App, ui_state: Normal, chunk: Rect) {
-    let chunk = render_border(f, chunk, "Global upgrades");
+fn render_global_upgrades<B: Backend>(
+    f: &mut Frame<B>,
+    theme: &Theme,
+    app: &App,
+    ui_state: Normal,
+    chunk: Rect,
+) {
+    let chunk = render_border(f, theme, chunk, "Global upgrades");
+    // Exact lengths, as equal ratios that don't divide the width evenly
+    // leave the rounding up to the solver, which isn't deterministic
+    let count = GlobalUpgrade::COUNT as u16;
     let chunks = Layout::default()
         .direction(Direction::Horizontal)
         .constraints(
-            (0..GlobalUpgrade::COUNT)
-                .map(|_| Constraint::Ratio(1, GlobalUpgrade::COUNT as u32))
+            (0..count)
+                .map(|i| {
+                    Constraint::Length(chunk.width / count + u16::from(i < chunk.width % count))
+                })
                 .collect::<Vec<_>>(),
         )
         .split(chunk);
 
-    for (upgrade, chunk) in GlobalUpgrade::iter().zip(chunks.into_iter()) {
+    for (upgrade, chunk) in GlobalUpgrade::iter().zip(chunks) {
         let highlight = match ui_state.highlight {
             Highlight::None | Highlight::Bar { .. } => false,
             Highlight::Global {
@@ -224,7 +418,8 @@ fn render_global_upgrades<B: Backend>(f: &mut Frame<B>, app: &App, ui_state: Nor
         };
         let can_afford = app.can_afford_global(upgrade);
         let button = mk_button(
-            &upgrade.label(app.global_upgrades[&upgrade]),
+            theme,
+            &upgrade.label(app.global_upgrade_cost(upgrade)),
             highlight,
             can_afford,
         );
@@ -232,14 +427,24 @@ fn render_global_upgrades<B: Backend>(f: &mut Frame<B>, app: &App, ui_state: Nor
     }
 }
 
-fn mk_gauge(bar: &Bar, color: Color) -> Gauge<'static> {
+fn mk_gauge(theme: &Theme, bar: &Bar, color: Color, cooldown: Option<Duration>) -> Gauge<'static> {
+    let percent = bar.progress.0 as u16;
     Gauge::default()
-        .gauge_style(Style::default().fg(color).bg(Color::Black))
-        .percent(bar.progress.0 as u16)
+        .gauge_style(Style::default().fg(color).bg(theme.background))
+        .percent(percent)
+        .label(format!("{percent}%{}", cooldown_label(cooldown)))
 }
 
-fn render_bar_values<B: Backend>(f: &mut Frame<B>, app: &App, chunk: Rect) {
-    let chunk = render_border(f, chunk, "Values");
+/// How long until a bar that was charged by hand can be charged again.
+fn cooldown_label(cooldown: Option<Duration>) -> String {
+    match cooldown {
+        None => "".to_owned(),
+        Some(cooldown) => format!(" charge in {}s", cooldown.as_secs_f64().ceil()),
+    }
+}
+
+fn render_bar_values<B: Backend>(f: &mut Frame<B>, theme: &Theme, app: &App, chunk: Rect) {
+    let chunk = render_border(f, theme, chunk, "Values");
     let chunks = rect_to_lines(chunk);
     let highlight_cost_target = app.highlight_cost_target();
     for (i, chunk) in chunks.into_iter().enumerate() {
@@ -247,14 +452,17 @@ fn render_bar_values<B: Backend>(f: &mut Frame<B>, app: &App, chunk: Rect) {
             break;
         }
 
-        let color = if highlight_cost_target.map_or(false, |t| t == i as i64) {
-            Color::Yellow
+        let color = if highlight_cost_target == Some(i as i64) {
+            theme.highlight
         } else {
-            Color::White
+            theme.text
         };
 
         let bar = &app.bars[i];
 
-        f.render_widget(mk_text_line_fg(color, &format!("{}", bar.gathered)), chunk);
+        f.render_widget(
+            mk_text_line_fg(theme, color, &format!("{}", bar.gathered)),
+            chunk,
+        );
     }
 }
@@ -9,13 +9,16 @@ use tui::{
 };
 
 use crate::app::App;
+use crate::balance;
+use crate::challenge::Challenge;
 use crate::prestige::PrestigeUpgrade;
 use crate::render::util::*;
+use crate::theme::Theme;
 use crate::ui::prestige::Highlight;
 use crate::ui::Prestige;
 
-pub(crate) fn render<B: Backend>(f: &mut Frame<B>, app: &App, ui_state: Prestige) {
-    let chunks = render_border(f, f.size(), "Prestige");
+pub(crate) fn render<B: Backend>(f: &mut Frame<B>, theme: &Theme, app: &App, ui_state: Prestige) {
+    let chunks = render_border(f, theme, f.size(), "Prestige");
     let chunks = Layout::default()
         .direction(Direction::Horizontal)
         .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
@@ -24,12 +27,13 @@ pub(crate) fn render<B: Backend>(f: &mut Frame<B>, app: &App, ui_state: Prestige
     let left = chunks[0];
     let right = chunks[1];
 
-    render_prestige_stats(f, app, ui_state, left);
-    render_prestige_upgrades(f, app, ui_state, right);
+    render_prestige_stats(f, theme, app, ui_state, left);
+    render_prestige_upgrades(f, theme, app, ui_state, right);
 }
 
 fn render_prestige_stats<B: Backend>(
     f: &mut Frame<B>,
+    theme: &Theme,
     app: &App,
     ui_state: Prestige,
     chunks: Rect,
@@ -39,11 +43,13 @@ fn render_prestige_stats<B: Backend>(
     if can_prestige {
         render_left_text(
             f,
+            theme,
             chunks[0],
             &format!("Current prestige points: {}", app.prestige.current),
         );
         render_left_text(
             f,
+            theme,
             chunks[1],
             &format!(
                 "Points to claim on prestige: {}",
@@ -51,13 +57,22 @@ fn render_prestige_stats<B: Backend>(
             ),
         );
     } else {
-        render_text(f, chunks[0], "You cannot prestige until you reach 10 bars");
+        render_text(
+            f,
+            theme,
+            chunks[0],
+            &format!(
+                "You cannot prestige until you reach {} bars",
+                balance::get().prestige.bar_threshold
+            ),
+        );
     }
 
     // chunks[2]
 
     f.render_widget(
         mk_button(
+            theme,
             "Prestige",
             Highlight::PrestigeButton == ui_state.highlight,
             app.prestige.can_prestige(app.bars.len()),
@@ -69,22 +84,74 @@ fn render_prestige_stats<B: Backend>(
 
     render_text(
         f,
+        theme,
         chunks[5],
         &format!(
             "Current prestige points: {points}",
             points = app.prestige.current
         ),
     );
+
+    // chunks[6]
+
+    render_challenges(f, theme, app, ui_state, &chunks[7..]);
+}
+
+/// Each challenge takes a button with its rule and a line with its reward.
+fn render_challenges<B: Backend>(
+    f: &mut Frame<B>,
+    theme: &Theme,
+    app: &App,
+    ui_state: Prestige,
+    chunks: &[Rect],
+) {
+    if let Some(chunk) = chunks.first() {
+        render_left_text(f, theme, *chunk, "Challenges:");
+    }
+    let can_start = app.prestige.can_prestige(app.bars.len());
+    let rows = chunks.get(1..).unwrap_or_default().chunks(2);
+    for (i, (challenge, rows)) in Challenge::iter().zip(rows).enumerate() {
+        let status = if app.prestige.in_challenge(challenge) {
+            " (active)"
+        } else if app.prestige.has_reward(challenge) {
+            " (done)"
+        } else {
+            ""
+        };
+        let button = mk_button_align(
+            theme,
+            &format!("{}{status}: {}", challenge.name(), challenge.rule()),
+            Highlight::Challenge(i) == ui_state.highlight,
+            can_start || app.prestige.in_challenge(challenge),
+            Alignment::Left,
+        );
+        f.render_widget(button, rows[0]);
+        if let Some(row) = rows.get(1) {
+            render_left_text(f, theme, *row, &format!("  Reward: {}", challenge.reward()));
+        }
+    }
+
+    let note = if app.prestige.challenge.is_some() {
+        "Abandoning a challenge keeps your bars"
+    } else if !can_start {
+        "Challenges start when you prestige"
+    } else {
+        return;
+    };
+    if let Some(chunk) = chunks.get(1 + 2 * Challenge::COUNT) {
+        render_left_text(f, theme, *chunk, note);
+    }
 }
 
 fn render_prestige_upgrades<B: Backend>(
     f: &mut Frame<B>,
+    theme: &Theme,
     app: &App,
     ui_state: Prestige,
     chunks: Rect,
 ) {
     let chunks = rect_to_lines(chunks);
-    for (i, (upgrade, chunk)) in PrestigeUpgrade::iter().zip(chunks.into_iter()).enumerate() {
+    for (i, (upgrade, chunk)) in PrestigeUpgrade::iter().zip(chunks).enumerate() {
         let cost = if app.prestige.is_max_level(upgrade) {
             "MAXED".to_owned()
         } else {
@@ -92,6 +159,7 @@ fn render_prestige_upgrades<B: Backend>(
         };
         let text = format!("{label}: {cost}", label = prestige_upgrade_label(upgrade),);
         let button = mk_button_align(
+            theme,
             &text,
             Highlight::Upgrade(i) == ui_state.highlight,
             app.prestige.can_afford(upgrade),
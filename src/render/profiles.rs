@@ -0,0 +1,76 @@
+use tui::{backend::Backend, layout::*, Frame};
+
+use crate::float::Float;
+use crate::profile::ProfileInfo;
+use crate::render::util::*;
+use crate::theme::Theme;
+use crate::ui::profiles::{Picker, Prompt};
+
+pub(crate) fn render<B: Backend>(
+    f: &mut Frame<B>,
+    theme: &Theme,
+    picker: &Picker,
+    profiles: &[ProfileInfo],
+) {
+    let chunk = render_border(f, theme, f.size(), "Profiles");
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(3)])
+        .split(chunk);
+    let list = chunks[0];
+    let footer = rect_to_lines(chunks[1]);
+
+    if profiles.is_empty() {
+        render_text(f, theme, list, "No profiles yet, press n to create one");
+    }
+    for (i, (profile, chunk)) in profiles.iter().zip(rect_to_lines(list)).enumerate() {
+        let button = mk_button_align(
+            theme,
+            &profile_label(profile),
+            i == picker.highlight,
+            profile.summary.is_some(),
+            Alignment::Left,
+        );
+        f.render_widget(button, chunk);
+    }
+
+    let status = match &picker.prompt {
+        Some(Prompt::Create { name }) => format!("New profile name: {name}_"),
+        Some(Prompt::Rename { name }) => format!("Rename to: {name}_"),
+        Some(Prompt::Duplicate { name }) => format!("Name of the copy: {name}_"),
+        Some(Prompt::ConfirmDelete) => match profiles.get(picker.highlight) {
+            Some(profile) => format!("Delete {}? y/n", profile.name),
+            None => String::new(),
+        },
+        None => picker.message.clone().unwrap_or_default(),
+    };
+    if let Some(chunk) = footer.first() {
+        render_left_text(f, theme, *chunk, &status);
+    }
+    if let Some(chunk) = footer.get(2) {
+        render_text(
+            f,
+            theme,
+            *chunk,
+            "enter: play  n: new  r: rename  d: duplicate  x: delete  q: quit",
+        );
+    }
+}
+
+fn profile_label(profile: &ProfileInfo) -> String {
+    let summary = match profile.summary {
+        None => "unreadable save".to_owned(),
+        Some(summary) => format!(
+            "{} bars, {} prestige points",
+            summary.bars,
+            Float(summary.prestige_points)
+        ),
+    };
+    let last_played = profile.last_played.map_or("never".to_owned(), |at| {
+        at.format("%Y-%m-%d %H:%M").to_string()
+    });
+    format!(
+        "{name}: {summary}, last played {last_played}",
+        name = profile.name
+    )
+}
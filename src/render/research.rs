@@ -0,0 +1,56 @@
+use strum::*;
+use tui::{backend::Backend, layout::*, Frame};
+
+use crate::app::App;
+use crate::balance;
+use crate::render::util::*;
+use crate::research::ResearchNode;
+use crate::theme::Theme;
+use crate::ui::Research;
+
+/// How far each level of the tree is indented.
+const INDENT: u16 = 4;
+
+pub(crate) fn render<B: Backend>(f: &mut Frame<B>, theme: &Theme, app: &App, ui_state: Research) {
+    let chunk = render_border(f, theme, f.size(), "Research");
+    let chunks = rect_to_lines(chunk);
+    let research = &app.research;
+
+    render_left_text(
+        f,
+        theme,
+        chunks[0],
+        &format!(
+            "Research points: {points}, earned by bars from level {level}",
+            points = research.points,
+            level = balance::get().research.min_level
+        ),
+    );
+
+    // chunks[1]
+
+    for (node, chunk) in ResearchNode::iter().zip(chunks.iter().skip(2)) {
+        let status = if research.is_unlocked(node) {
+            "researched".to_owned()
+        } else if research.is_available(node) {
+            node.cost().to_string()
+        } else {
+            let requires: Vec<_> = node.requires().iter().map(|node| node.name()).collect();
+            format!("needs {}", requires.join(", "))
+        };
+        let indent = (node.depth() as u16 * INDENT).min(chunk.width);
+        let chunk = Rect {
+            x: chunk.x + indent,
+            width: chunk.width - indent,
+            ..*chunk
+        };
+        let button = mk_button_align(
+            theme,
+            &format!("{}: {} | {status}", node.name(), node.description()),
+            node == ui_state.highlight,
+            research.can_unlock(node),
+            Alignment::Left,
+        );
+        f.render_widget(button, chunk);
+    }
+}
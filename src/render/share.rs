@@ -0,0 +1,64 @@
+use tui::{backend::Backend, layout::*, Frame};
+
+use crate::render::util::*;
+use crate::theme::Theme;
+use crate::ui::Share;
+
+fn layout(size: Rect) -> Vec<Rect> {
+    Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(7), Constraint::Min(0)])
+        .split(size)
+}
+
+/// Whether `exported` fits under `lines` other lines below the help.
+pub(super) fn fits(size: Rect, exported: &str, lines: u16) -> bool {
+    let area = layout(size)[1];
+    exported.len() <= usize::from(area.width) * usize::from(area.height.saturating_sub(lines))
+}
+
+pub(crate) fn render<B: Backend>(f: &mut Frame<B>, theme: &Theme, ui_state: &Share) {
+    let chunks = layout(f.size());
+
+    let lines = rect_to_lines(render_border(f, theme, chunks[0], "Share"));
+    let help = [
+        "ctrl+e: export this game as a save string",
+        "enter: import the typed or pasted save string, replacing this game",
+        "esc: back",
+    ];
+    for (text, chunk) in help.into_iter().zip(&lines) {
+        render_left_text(f, theme, *chunk, text);
+    }
+    if let Some(chunk) = lines.get(4) {
+        let text = if ui_state.confirm_import {
+            "Replace this game with the pasted save? y/n".to_owned()
+        } else {
+            // Only the end of a long string fits
+            let width = chunk.width.saturating_sub("Import: _".len() as u16) as usize;
+            let skip = ui_state.input.chars().count().saturating_sub(width);
+            let input: String = ui_state.input.chars().skip(skip).collect();
+            format!("Import: {input}_")
+        };
+        render_left_text(f, theme, *chunk, &text);
+    }
+
+    // Not bordered, so that as much as possible fits. Line breaks are
+    // ignored on import.
+    let area = chunks[1];
+    let fits = |exported: &str, lines: u16| fits(f.size(), exported, lines);
+    let text = match (&ui_state.message, &ui_state.exported, &ui_state.export_file) {
+        (Some(message), _, _) => message.clone(),
+        (None, Some(exported), Some(path)) if fits(exported, 1) => {
+            format!("Also written to {path}:\n{exported}")
+        }
+        (None, Some(_), Some(path)) => {
+            format!("The save string is too long to show here, copy it from {path}")
+        }
+        (None, Some(exported), None) if fits(exported, 0) => exported.clone(),
+        (None, Some(_), None) => {
+            "The save string is too long to show here, run antsy export instead".to_owned()
+        }
+        (None, None, _) => String::new(),
+    };
+    render_left_text(f, theme, area, &text);
+}
@@ -7,18 +7,25 @@ use tui::{
     Frame,
 };
 
-pub(super) fn mk_text_line_fg(fg_color: Color, text: &str) -> Paragraph<'_> {
+use crate::theme::Theme;
+
+pub(super) fn mk_text_line_fg<'a>(theme: &Theme, fg_color: Color, text: &'a str) -> Paragraph<'a> {
     Paragraph::new(text)
         .alignment(Alignment::Center)
-        .style(Style::default().fg(fg_color).bg(Color::Black))
+        .style(Style::default().fg(fg_color).bg(theme.background))
         .wrap(Wrap { trim: true })
 }
 
-pub(super) fn render_border<B: Backend>(f: &mut Frame<B>, chunk: Rect, title: &str) -> Rect {
+pub(super) fn render_border<B: Backend>(
+    f: &mut Frame<B>,
+    theme: &Theme,
+    chunk: Rect,
+    title: &str,
+) -> Rect {
     f.render_widget(
         Block::default()
             .title(title)
-            .style(Style::default().bg(Color::Black))
+            .style(Style::default().fg(theme.text).bg(theme.background))
             .borders(Borders::ALL),
         chunk,
     );
@@ -28,19 +35,24 @@ pub(super) fn render_border<B: Backend>(f: &mut Frame<B>, chunk: Rect, title: &s
     })
 }
 
-pub(super) fn render_text<B: Backend>(f: &mut Frame<B>, chunk: Rect, text: &str) {
+pub(super) fn render_text<B: Backend>(f: &mut Frame<B>, theme: &Theme, chunk: Rect, text: &str) {
     let w = Paragraph::new(text)
         .alignment(Alignment::Center)
-        .style(Style::default().fg(Color::White).bg(Color::Black))
+        .style(Style::default().fg(theme.text).bg(theme.background))
         .wrap(Wrap { trim: false });
 
     f.render_widget(w, chunk);
 }
 
-pub(super) fn render_left_text<B: Backend>(f: &mut Frame<B>, chunk: Rect, text: &str) {
+pub(super) fn render_left_text<B: Backend>(
+    f: &mut Frame<B>,
+    theme: &Theme,
+    chunk: Rect,
+    text: &str,
+) {
     let w = Paragraph::new(text)
         .alignment(Alignment::Left)
-        .style(Style::default().fg(Color::White).bg(Color::Black))
+        .style(Style::default().fg(theme.text).bg(theme.background))
         .wrap(Wrap { trim: false });
 
     f.render_widget(w, chunk);
@@ -57,46 +69,66 @@ pub(super) fn rect_to_lines(r: Rect) -> Vec<Rect> {
         .collect()
 }
 
-pub(super) fn mk_button(label: &str, highlight: bool, can_afford: bool) -> Paragraph<'static> {
-    let color = if highlight {
-        Color::Yellow
-    } else {
-        Color::White
-    };
-    let modifier = if can_afford {
-        Modifier::BOLD | Modifier::UNDERLINED
-    } else {
-        Modifier::empty()
-    };
-    let text = Span::styled(
-        format!("  {label}  "),
-        Style::default().fg(color).add_modifier(modifier),
-    );
-    Paragraph::new(text)
-        .alignment(Alignment::Center)
-        .wrap(Wrap { trim: true })
+/// How much wider a button is with markers: `>[✓] label ` instead of
+/// `  label  `.
+pub(super) const MARKERS_WIDTH: u16 = 2;
+
+pub(super) fn mk_button(
+    theme: &Theme,
+    label: &str,
+    highlight: bool,
+    can_afford: bool,
+) -> Paragraph<'static> {
+    mk_button_align(theme, label, highlight, can_afford, Alignment::Center)
+}
+
+/// A button with one space around the label instead of two, for where
+/// room is short. Markers keep their usual padding.
+pub(super) fn mk_tight_button(
+    theme: &Theme,
+    label: &str,
+    highlight: bool,
+    can_afford: bool,
+) -> Paragraph<'static> {
+    mk_button_padded(theme, label, highlight, can_afford, Alignment::Center, " ")
 }
 
 pub(super) fn mk_button_align(
+    theme: &Theme,
     label: &str,
     highlight: bool,
     can_afford: bool,
     alignment: Alignment,
+) -> Paragraph<'static> {
+    mk_button_padded(theme, label, highlight, can_afford, alignment, "  ")
+}
+
+fn mk_button_padded(
+    theme: &Theme,
+    label: &str,
+    highlight: bool,
+    can_afford: bool,
+    alignment: Alignment,
+    padding: &str,
 ) -> Paragraph<'static> {
     let color = if highlight {
-        Color::Yellow
+        theme.highlight
     } else {
-        Color::White
+        theme.text
     };
     let modifier = if can_afford {
         Modifier::BOLD | Modifier::UNDERLINED
     } else {
         Modifier::empty()
     };
-    let text = Span::styled(
-        format!("  {label}  "),
-        Style::default().fg(color).add_modifier(modifier),
-    );
+    let text = if theme.markers {
+        let cursor = if highlight { ">" } else { " " };
+        let mark = if can_afford { "✓" } else { " " };
+        format!("{cursor}[{mark}] {label} ")
+    } else {
+        format!("{padding}{label}{padding}")
+    };
+    let text = Span::styled(text, Style::default().fg(color).add_modifier(modifier));
     Paragraph::new(text)
         .alignment(alignment)
         .wrap(Wrap { trim: true })
@@ -0,0 +1,248 @@
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::iter::Peekable;
+use std::time::{Duration, Instant};
+
+use crate::app::App;
+use crate::controls;
+use crate::opts::Opts;
+use crate::save;
+use crate::strategy::{Purchase, Strategies, Strategy};
+
+// A replay file is json lines: a `Header` followed by one `Tick` per tick.
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    strategy: String,
+    save: save::App,
+    // Doesn't exist in old replays, which had no random events
+    random_events: Option<bool>,
+}
+
+/// Nanoseconds since the start of the recording, and the actions taken
+/// since the previous tick.
+#[derive(Serialize, Deserialize)]
+struct Tick(u64, Vec<Action>);
+
+#[derive(Serialize, Deserialize)]
+enum Action {
+    PurchaseBarUpgrade { upgrade: save::Upgrade, row: usize },
+    PurchaseGlobalUpgrade { upgrade: save::GlobalUpgrade },
+    PurchasePrestigeUpgrade { upgrade: save::PrestigeUpgrade },
+    Prestige,
+    ToggleChallenge { challenge: save::Challenge },
+    UpgradeAny,
+    UnlockResearch { node: save::ResearchNode },
+    AdjustTransfer { row: usize, up: bool },
+    ChargeBar { row: usize },
+    Import { code: String },
+    DevCommand { command: String },
+}
+
+impl Action {
+    fn from_game(action: &controls::Action) -> Option<Self> {
+        use controls::Action as Game;
+        Some(match *action {
+            Game::Quit | Game::Noop | Game::Export => return None,
+            Game::PurchaseUpgrade(Purchase::Bar { upgrade, row }) => Action::PurchaseBarUpgrade {
+                upgrade: save::Upgrade::from_game(&upgrade),
+                row,
+            },
+            Game::PurchaseUpgrade(Purchase::Global { upgrade }) => Action::PurchaseGlobalUpgrade {
+                upgrade: save::GlobalUpgrade::from_game(upgrade),
+            },
+            Game::PurchasePrestigeUpgrade(upgrade) => Action::PurchasePrestigeUpgrade {
+                upgrade: save::PrestigeUpgrade::from_game(&upgrade),
+            },
+            Game::Prestige => Action::Prestige,
+            Game::ToggleChallenge(challenge) => Action::ToggleChallenge {
+                challenge: save::Challenge::from_game(challenge),
+            },
+            Game::UpgradeAny => Action::UpgradeAny,
+            Game::UnlockResearch(node) => Action::UnlockResearch {
+                node: save::ResearchNode::from_game(node),
+            },
+            Game::AdjustTransfer { row, up } => Action::AdjustTransfer { row, up },
+            Game::ChargeBar { row } => Action::ChargeBar { row },
+            Game::Import(ref code) => Action::Import { code: code.clone() },
+            Game::DevCommand(ref command) => Action::DevCommand {
+                command: command.clone(),
+            },
+        })
+    }
+
+    fn into_game(self) -> controls::Action {
+        use controls::Action as Game;
+        match self {
+            Action::PurchaseBarUpgrade { upgrade, row } => Game::PurchaseUpgrade(Purchase::Bar {
+                upgrade: upgrade.into_game(),
+                row,
+            }),
+            Action::PurchaseGlobalUpgrade { upgrade } => Game::PurchaseUpgrade(Purchase::Global {
+                upgrade: upgrade.into_game(),
+            }),
+            Action::PurchasePrestigeUpgrade { upgrade } => {
+                Game::PurchasePrestigeUpgrade(upgrade.into_game())
+            }
+            Action::Prestige => Game::Prestige,
+            Action::ToggleChallenge { challenge } => Game::ToggleChallenge(challenge.into_game()),
+            Action::UpgradeAny => Game::UpgradeAny,
+            Action::UnlockResearch { node } => Game::UnlockResearch(node.into_game()),
+            Action::AdjustTransfer { row, up } => Game::AdjustTransfer { row, up },
+            Action::ChargeBar { row } => Game::ChargeBar { row },
+            Action::Import { code } => Game::Import(code),
+            Action::DevCommand { command } => Game::DevCommand(command),
+        }
+    }
+}
+
+pub(crate) struct Recorder {
+    file: BufWriter<File>,
+    start: Instant,
+    pending: Vec<Action>,
+}
+
+impl Recorder {
+    /// Starts a recording of `app`, which must not have ticked yet.
+    pub(crate) fn create(path: &str, app: &App, strategy: Strategies) -> anyhow::Result<Self> {
+        let file = File::create(path).with_context(|| format!("Could not create {path}"))?;
+        let mut recorder = Self {
+            file: BufWriter::new(file),
+            start: app.tick,
+            pending: vec![],
+        };
+        let header = Header {
+            strategy: strategy.to_string(),
+            save: save::App::from_game(app),
+            random_events: Some(!app.opts.no_random_events),
+        };
+        recorder.write_line(&header)?;
+        Ok(recorder)
+    }
+
+    pub(crate) fn record_action(&mut self, action: &controls::Action) {
+        self.pending.extend(Action::from_game(action));
+    }
+
+    pub(crate) fn record_tick(&mut self, now: Instant) -> io::Result<()> {
+        let at = (now - self.start).as_nanos() as u64;
+        let tick = Tick(at, std::mem::take(&mut self.pending));
+        self.write_line(&tick)
+    }
+
+    pub(crate) fn finish(mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+
+    fn write_line<T: Serialize>(&mut self, value: &T) -> io::Result<()> {
+        serde_json::to_writer(&mut self.file, value)?;
+        self.file.write_all(b"\n")
+    }
+}
+
+pub(crate) struct Playback {
+    pub(crate) app: App,
+    strategy: Box<dyn Strategy>,
+    start: Instant,
+    ticks: Peekable<std::vec::IntoIter<Tick>>,
+}
+
+impl Playback {
+    pub(crate) fn load(path: &str, mut opts: Opts, start: Instant) -> anyhow::Result<Self> {
+        let file = File::open(path).with_context(|| format!("Could not open {path}"))?;
+        let mut lines = BufReader::new(file).lines();
+        let header: Header = match lines.next() {
+            None => anyhow::bail!("{path} is empty"),
+            Some(line) => serde_json::from_str(&line?).context("Invalid replay header")?,
+        };
+        let ticks = lines
+            .enumerate()
+            .map(|(i, line)| {
+                serde_json::from_str(&line?)
+                    .with_context(|| format!("Invalid tick on line {}", i + 2))
+            })
+            .collect::<anyhow::Result<Vec<Tick>>>()?;
+        let problems = header.save.validate();
+        if !problems.is_empty() {
+            anyhow::bail!(
+                "The save in {path} has problems:\n{}",
+                crate::app::problem_list(&problems)
+            );
+        }
+        let strategy: Strategies = header
+            .strategy
+            .parse()
+            .with_context(|| format!("Unknown strategy {}", header.strategy))?;
+        // Random events are rolled the same way as when recording
+        opts.no_random_events = !header.random_events.unwrap_or(false);
+
+        Ok(Self {
+            app: App::from_save(header.save, opts, start),
+            strategy: strategy.build(),
+            start,
+            ticks: ticks.into_iter().peekable(),
+        })
+    }
+
+    /// When the next tick happens, relative to the start of the replay.
+    pub(crate) fn next_at(&mut self) -> Option<Duration> {
+        self.ticks.peek().map(|tick| Duration::from_nanos(tick.0))
+    }
+
+    /// Applies the actions of the next tick and runs it. Returns false
+    /// once the replay is over.
+    pub(crate) fn step(&mut self) -> bool {
+        match self.ticks.next() {
+            None => false,
+            Some(Tick(at, actions)) => {
+                for action in actions {
+                    self.app
+                        .apply_action(action.into_game(), self.strategy.as_mut());
+                }
+                self.app.on_tick(self.start + Duration::from_nanos(at));
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn playback_reproduces_recording() {
+        let path = std::env::temp_dir().join("antsy-test-replay.jsonl");
+        let path = path.to_str().unwrap();
+        let mut app = App::for_test();
+        app.opts.no_random_events = false;
+        app.random_events.next_at = app.tick + Duration::from_secs(10);
+        let mut strategy = Strategies::Greedy.build();
+        let mut recorder = Recorder::create(path, &app, Strategies::Greedy).unwrap();
+
+        let mut now = app.tick;
+        for i in 0..3_000 {
+            if i % 50 == 0 {
+                recorder.record_action(&controls::Action::UpgradeAny);
+                app.apply_action(controls::Action::UpgradeAny, strategy.as_mut());
+            }
+            now += Duration::from_millis(40 + i % 7);
+            app.on_tick(now);
+            recorder.record_tick(now).unwrap();
+        }
+        recorder.finish().unwrap();
+
+        let mut playback = Playback::load(path, App::for_test().opts, Instant::now()).unwrap();
+        while playback.step() {}
+
+        let expected = serde_json::to_value(save::App::from_game(&app)).unwrap();
+        let actual = serde_json::to_value(save::App::from_game(&playback.app)).unwrap();
+        assert!(app
+            .bars
+            .iter()
+            .any(|bar| bar.get_upgrade_u(crate::Upgrade::Gain) > 0));
+        assert_eq!(expected, actual);
+    }
+}
@@ -0,0 +1,197 @@
+use std::collections::HashSet;
+use strum::*;
+
+use crate::balance;
+use crate::float::Float;
+use crate::upgrade::{GlobalUpgrade, Upgrade};
+
+/// Research points and the nodes they were spent on. Both are kept when
+/// prestiging.
+pub(crate) struct Research {
+    pub(crate) points: Float,
+    pub(crate) unlocked: HashSet<ResearchNode>,
+}
+
+impl Research {
+    pub(crate) fn new() -> Self {
+        Self {
+            points: 0.into(),
+            unlocked: HashSet::new(),
+        }
+    }
+
+    /// Research points a bar at `level` earns for `completions`
+    /// completions.
+    pub(crate) fn produced(level: usize, completions: usize, research_gain_levels: usize) -> Float {
+        let balance = &balance::get().research;
+        if level < balance.min_level {
+            return 0.into();
+        }
+        Float(
+            balance.points_per_completion * (1. + research_gain_levels as f64) * completions as f64,
+        )
+    }
+
+    pub(crate) fn is_unlocked(&self, node: ResearchNode) -> bool {
+        self.unlocked.contains(&node)
+    }
+
+    /// Whether everything `node` builds on is unlocked.
+    pub(crate) fn is_available(&self, node: ResearchNode) -> bool {
+        node.requires().iter().all(|&r| self.is_unlocked(r))
+    }
+
+    pub(crate) fn can_unlock(&self, node: ResearchNode) -> bool {
+        !self.is_unlocked(node) && self.is_available(node) && self.points >= node.cost()
+    }
+
+    pub(crate) fn try_unlock(&mut self, node: ResearchNode) -> bool {
+        if !self.can_unlock(node) {
+            return false;
+        }
+        self.points -= node.cost();
+        self.unlocked.insert(node);
+        true
+    }
+
+    /// Whether `upgrade` can be bought, as some are unlocked by research.
+    pub(crate) fn allows_upgrade(&self, upgrade: Upgrade) -> bool {
+        upgrade != Upgrade::Quintuple || self.is_unlocked(ResearchNode::Quintuple)
+    }
+
+    pub(crate) fn allows_global_upgrade(&self, upgrade: GlobalUpgrade) -> bool {
+        upgrade != GlobalUpgrade::ResearchGain || self.is_unlocked(ResearchNode::Grants)
+    }
+
+    pub(crate) fn gain_multiplier(&self) -> Float {
+        self.multiplier(
+            ResearchNode::Efficiency,
+            balance::get().research.gain_multiplier,
+        )
+    }
+
+    pub(crate) fn speed_multiplier(&self) -> Float {
+        self.multiplier(
+            ResearchNode::Momentum,
+            balance::get().research.speed_multiplier,
+        )
+    }
+
+    /// What the `level_speed` increment on level up is multiplied by.
+    pub(crate) fn level_speed_factor(&self) -> f64 {
+        if self.is_unlocked(ResearchNode::Practice) {
+            balance::get().research.level_speed_factor
+        } else {
+            1.
+        }
+    }
+
+    fn multiplier(&self, node: ResearchNode, multiplier: f64) -> Float {
+        if self.is_unlocked(node) {
+            Float(multiplier)
+        } else {
+            Float(1.)
+        }
+    }
+}
+
+/// A node in the research tree, listed so that every node comes after the
+/// nodes it requires.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, EnumIter, EnumCount, Hash)]
+pub(crate) enum ResearchNode {
+    Efficiency,
+    Quintuple,
+    Momentum,
+    Practice,
+    Grants,
+}
+
+impl ResearchNode {
+    /// The nodes that have to be unlocked before this one.
+    pub(crate) fn requires(self) -> &'static [ResearchNode] {
+        use ResearchNode::*;
+        match self {
+            Efficiency | Momentum => &[],
+            Quintuple => &[Efficiency],
+            Practice => &[Momentum],
+            Grants => &[Efficiency, Momentum],
+        }
+    }
+
+    /// The nodes that require this one.
+    pub(crate) fn unlocks(self) -> impl Iterator<Item = ResearchNode> {
+        ResearchNode::iter().filter(move |node| node.requires().contains(&self))
+    }
+
+    /// How far from the roots of the tree the node is.
+    pub(crate) fn depth(self) -> usize {
+        self.requires()
+            .iter()
+            .map(|node| node.depth() + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub(crate) fn cost(self) -> Float {
+        Float(balance::get().research.nodes.cost(self))
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            ResearchNode::Efficiency => "Efficiency",
+            ResearchNode::Quintuple => "Quintuple",
+            ResearchNode::Momentum => "Momentum",
+            ResearchNode::Practice => "Practice",
+            ResearchNode::Grants => "Grants",
+        }
+    }
+
+    pub(crate) fn description(self) -> String {
+        let balance = &balance::get().research;
+        let percent = |factor: f64| ((factor - 1.) * 100.).round();
+        match self {
+            ResearchNode::Efficiency => {
+                format!("Bars gain {}% more", percent(balance.gain_multiplier))
+            }
+            ResearchNode::Quintuple => "Bars can buy x5".to_owned(),
+            ResearchNode::Momentum => {
+                format!("Bars are {}% faster", percent(balance.speed_multiplier))
+            }
+            ResearchNode::Practice => format!(
+                "Levelling up speeds bars up {}% more",
+                percent(balance.level_speed_factor)
+            ),
+            ResearchNode::Grants => "Research gain can be bought globally".to_owned(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nodes_need_their_requirements_and_points() {
+        let mut research = Research::new();
+        research.points = Float(100.);
+        assert!(!research.try_unlock(ResearchNode::Grants));
+        assert!(research.try_unlock(ResearchNode::Efficiency));
+        assert!(!research.try_unlock(ResearchNode::Efficiency));
+        assert!(research.try_unlock(ResearchNode::Momentum));
+        assert!(research.try_unlock(ResearchNode::Grants));
+        assert_eq!(research.points, Float(60.));
+        assert!(research.allows_global_upgrade(GlobalUpgrade::ResearchGain));
+        assert!(!research.allows_upgrade(Upgrade::Quintuple));
+    }
+
+    #[test]
+    fn nodes_come_after_their_requirements() {
+        let nodes: Vec<_> = ResearchNode::iter().collect();
+        for (i, node) in nodes.iter().enumerate() {
+            for required in node.requires() {
+                assert!(nodes[..i].contains(required), "{node:?}");
+            }
+        }
+        assert_eq!(ResearchNode::Grants.depth(), 1);
+    }
+}
@@ -1,3 +1,4 @@
+use anyhow::Context;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::time::{Duration, Instant};
@@ -6,7 +7,12 @@ use strum::*;
 use crate::opts::Opts;
 use crate::ui::Ui;
 
-#[derive(Serialize, Deserialize)]
+mod tools;
+mod validate;
+
+pub(crate) use validate::Problem;
+
+#[derive(Serialize, Deserialize, Clone)]
 pub(crate) struct App {
     bars: Vec<Bar>,
     bars_to_spawn: usize,
@@ -14,6 +20,10 @@ pub(crate) struct App {
     global_upgrades: HashMap<GlobalUpgrade, usize>,
     // Doesn't exist in old saves
     prestige: Option<Prestige>,
+    // Doesn't exist in old saves
+    research: Option<Research>,
+    // Doesn't exist in old saves
+    random_events: Option<RandomEvents>,
 }
 
 impl App {
@@ -28,9 +38,45 @@ impl App {
                 .map(|(u, n)| (GlobalUpgrade::from_game(*u), *n))
                 .collect(),
             prestige: Some(Prestige::from_game(&a.prestige)),
+            research: Some(Research::from_game(&a.research)),
+            random_events: Some(RandomEvents::from_game(&a.random_events, a.tick)),
+        }
+    }
+
+    pub(crate) fn read(path: &str) -> anyhow::Result<App> {
+        let contents =
+            std::fs::read_to_string(path).with_context(|| format!("Could not read {path}"))?;
+        serde_json::from_str(&contents).with_context(|| format!("Could not parse {path}"))
+    }
+
+    pub(crate) fn write(&self, path: &str) -> anyhow::Result<()> {
+        let contents = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, contents).with_context(|| format!("Could not write {path}"))
+    }
+
+    /// The save of a game that hasn't started yet.
+    pub(crate) fn fresh() -> App {
+        App {
+            bars: vec![],
+            bars_to_spawn: crate::balance::get().app.initial_bars,
+            last_bar_number: 0,
+            global_upgrades: crate::upgrade::GlobalUpgrade::iter()
+                .map(|u| (GlobalUpgrade::from_game(u), 0))
+                .collect(),
+            prestige: Some(Prestige::from_game(&crate::prestige::Prestige::new())),
+            research: Some(Research::from_game(&crate::research::Research::new())),
+            random_events: None,
         }
     }
 
+    pub(crate) fn bar_count(&self) -> usize {
+        self.bars.len()
+    }
+
+    pub(crate) fn prestige_points(&self) -> f64 {
+        self.prestige.as_ref().map_or(0., |p| p.current)
+    }
+
     pub(crate) fn into_game(self, opts: Opts, now: Instant) -> crate::app::App {
         let mut global_upgrades: HashMap<_, _> = self
             .global_upgrades
@@ -39,7 +85,13 @@ impl App {
             .collect();
         // When adding new upgrades, they may not exist in saves.
         for upgrade in crate::upgrade::GlobalUpgrade::iter() {
-            global_upgrades.entry(upgrade).or_insert(0);
+            global_upgrades.entry(upgrade).or_insert_with(|| {
+                log::info!("Save has no {upgrade:?} global upgrade, starting it at 0");
+                0
+            });
+        }
+        if self.prestige.is_none() {
+            log::info!("Save is from before prestige, starting without prestige");
         }
 
         crate::app::App {
@@ -47,30 +99,45 @@ impl App {
             tick: now,
             last_bar_spawn: None,
             bars_to_spawn: self.bars_to_spawn,
-            ui: Ui::new(opts.start_state),
+            ui: Ui::new(opts.start_state, opts.dev),
             last_bar_number: self.last_bar_number,
             global_upgrades,
             last_save: None,
             opts,
             last_automation: HashMap::new(),
+            clock: crate::app::Clock::new(now),
+            events: Default::default(),
+            counters: Default::default(),
+            last_metrics: None,
             prestige: self
                 .prestige
                 .map_or(crate::prestige::Prestige::new(), Prestige::into_game),
+            research: self
+                .research
+                .map_or(crate::research::Research::new(), Research::into_game),
+            random_events: match self.random_events {
+                Some(random_events) => random_events.into_game(now),
+                None => crate::random_events::RandomEvents::new(
+                    crate::random_events::Rng::from_time(),
+                    now,
+                ),
+            },
         }
     }
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Copy, Clone)]
-enum GlobalUpgrade {
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Copy, Clone, Debug)]
+pub(crate) enum GlobalUpgrade {
     Speed,
     ExpBoost,
     ProgressBars,
     Gain,
     ExpGain,
+    ResearchGain,
 }
 
 impl GlobalUpgrade {
-    fn from_game(u: crate::GlobalUpgrade) -> GlobalUpgrade {
+    pub(crate) fn from_game(u: crate::GlobalUpgrade) -> GlobalUpgrade {
         use GlobalUpgrade::*;
         match u {
             crate::GlobalUpgrade::Speed => Speed,
@@ -78,9 +145,10 @@ impl GlobalUpgrade {
             crate::GlobalUpgrade::ProgressBars => ProgressBars,
             crate::GlobalUpgrade::Gain => Gain,
             crate::GlobalUpgrade::ExpGain => ExpGain,
+            crate::GlobalUpgrade::ResearchGain => ResearchGain,
         }
     }
-    fn into_game(self) -> crate::GlobalUpgrade {
+    pub(crate) fn into_game(self) -> crate::GlobalUpgrade {
         use GlobalUpgrade::*;
         match self {
             Speed => crate::GlobalUpgrade::Speed,
@@ -88,11 +156,12 @@ impl GlobalUpgrade {
             ProgressBars => crate::GlobalUpgrade::ProgressBars,
             Gain => crate::GlobalUpgrade::Gain,
             ExpGain => crate::GlobalUpgrade::ExpGain,
+            ResearchGain => crate::GlobalUpgrade::ResearchGain,
         }
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 struct Bar {
     progress: f64,
     gathered: f64,
@@ -102,6 +171,8 @@ struct Bar {
     exp: f64,
     level: usize,
     boost_remaining: Duration,
+    // Doesn't exist in old saves
+    charge_cooldown: Option<Duration>,
     gain_exponent: usize,
     level_speed: f64,
 }
@@ -127,43 +198,51 @@ impl Bar {
                     Duration::from_secs(0)
                 }
             }),
+            charge_cooldown: b.charge_cooldown(now),
             gain_exponent: b.gain_exponent,
             level_speed: b.level_speed.into(),
         }
     }
 
     fn into_game(self, now: Instant) -> crate::Bar {
+        let mut upgrades: HashMap<_, _> = self
+            .upgrades
+            .into_iter()
+            .map(|(u, n)| (u.into_game(), n))
+            .collect();
+        // Quintuple doesn't exist in old saves
+        upgrades.entry(crate::Upgrade::Quintuple).or_insert(0);
         crate::Bar {
             progress: self.progress.into(),
             gathered: self.gathered.into(),
             transfer_ratio: self.transfer_ratio.into(),
             last_completion: None,
-            upgrades: self
-                .upgrades
-                .into_iter()
-                .map(|(u, n)| (u.into_game(), n))
-                .collect(),
+            upgrades,
             number: self.number,
             exp: self.exp.into(),
             level: self.level,
-            boost_until: Some(now + self.boost_remaining),
+            boost_until: now.checked_add(self.boost_remaining),
+            charge_ready_at: self
+                .charge_cooldown
+                .and_then(|cooldown| now.checked_add(cooldown)),
             gain_exponent: self.gain_exponent,
             level_speed: self.level_speed.into(),
         }
     }
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Copy, Clone)]
-pub enum Upgrade {
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Copy, Clone, Debug)]
+pub(crate) enum Upgrade {
     Speed,
     Gain,
     Double,
     Triple,
     Quadruple,
+    Quintuple,
 }
 
 impl Upgrade {
-    fn from_game(u: &crate::Upgrade) -> Self {
+    pub(crate) fn from_game(u: &crate::Upgrade) -> Self {
         use Upgrade::*;
         match u {
             crate::Upgrade::Speed => Speed,
@@ -171,10 +250,11 @@ impl Upgrade {
             crate::Upgrade::Double => Double,
             crate::Upgrade::Triple => Triple,
             crate::Upgrade::Quadruple => Quadruple,
+            crate::Upgrade::Quintuple => Quintuple,
         }
     }
 
-    fn into_game(self) -> crate::Upgrade {
+    pub(crate) fn into_game(self) -> crate::Upgrade {
         use Upgrade::*;
         match self {
             Speed => crate::Upgrade::Speed,
@@ -182,15 +262,19 @@ impl Upgrade {
             Double => crate::Upgrade::Double,
             Triple => crate::Upgrade::Triple,
             Quadruple => crate::Upgrade::Quadruple,
+            Quintuple => crate::Upgrade::Quintuple,
         }
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 struct Prestige {
     current: f64,
     // Doesn't exist in old saves
     upgrades: Option<HashMap<PrestigeUpgrade, usize>>,
+    challenge: Option<Challenge>,
+    // Doesn't exist in old saves
+    completed_challenges: Option<Vec<Challenge>>,
 }
 
 impl Prestige {
@@ -203,10 +287,22 @@ impl Prestige {
                     .map(|(u, n)| (PrestigeUpgrade::from_game(u), *n))
                     .collect(),
             ),
+            challenge: p.challenge.map(Challenge::from_game),
+            completed_challenges: Some({
+                // Sorted so that saves don't change from run to run
+                let mut completed: Vec<_> = p.completed_challenges.iter().copied().collect();
+                completed.sort();
+                completed.into_iter().map(Challenge::from_game).collect()
+            }),
         }
     }
     fn into_game(self) -> crate::prestige::Prestige {
-        let Self { current, upgrades } = self;
+        let Self {
+            current,
+            upgrades,
+            challenge,
+            completed_challenges,
+        } = self;
 
         let mut upgrades = upgrades.map_or_else(HashMap::new, |upgrades| {
             upgrades
@@ -216,18 +312,59 @@ impl Prestige {
         });
         // When adding new upgrades, they may not exist in saves.
         for upgrade in crate::prestige::PrestigeUpgrade::iter() {
-            upgrades.entry(upgrade).or_insert(0);
+            upgrades.entry(upgrade).or_insert_with(|| {
+                log::info!("Save has no {upgrade:?} prestige upgrade, starting it at 0");
+                0
+            });
         }
 
         crate::prestige::Prestige {
             current: current.into(),
             upgrades,
+            challenge: challenge.map(Challenge::into_game),
+            completed_challenges: completed_challenges
+                .unwrap_or_default()
+                .into_iter()
+                .map(Challenge::into_game)
+                .collect(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum Challenge {
+    NoGlobalUpgrades,
+    NoTransfer,
+    NoSpeedUpgrades,
+    SlowSpawns,
+}
+
+impl Challenge {
+    pub(crate) fn from_game(c: crate::challenge::Challenge) -> Self {
+        use crate::challenge::Challenge as Game;
+        use Challenge::*;
+        match c {
+            Game::NoGlobalUpgrades => NoGlobalUpgrades,
+            Game::NoTransfer => NoTransfer,
+            Game::NoSpeedUpgrades => NoSpeedUpgrades,
+            Game::SlowSpawns => SlowSpawns,
+        }
+    }
+
+    pub(crate) fn into_game(self) -> crate::challenge::Challenge {
+        use crate::challenge::Challenge as Game;
+        use Challenge::*;
+        match self {
+            NoGlobalUpgrades => Game::NoGlobalUpgrades,
+            NoTransfer => Game::NoTransfer,
+            NoSpeedUpgrades => Game::NoSpeedUpgrades,
+            SlowSpawns => Game::SlowSpawns,
         }
     }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
-enum PrestigeUpgrade {
+pub(crate) enum PrestigeUpgrade {
     CompleteFaster,
     LevelUpFaster,
     TransferExtraExp,
@@ -242,7 +379,7 @@ enum PrestigeUpgrade {
 }
 
 impl PrestigeUpgrade {
-    fn from_game(u: &crate::prestige::PrestigeUpgrade) -> Self {
+    pub(crate) fn from_game(u: &crate::prestige::PrestigeUpgrade) -> Self {
         use crate::prestige::PrestigeUpgrade as Game;
         use PrestigeUpgrade::*;
         match u {
@@ -260,7 +397,7 @@ impl PrestigeUpgrade {
         }
     }
 
-    fn into_game(self) -> crate::prestige::PrestigeUpgrade {
+    pub(crate) fn into_game(self) -> crate::prestige::PrestigeUpgrade {
         use crate::prestige::PrestigeUpgrade as Game;
         use PrestigeUpgrade::*;
         match self {
@@ -278,3 +415,211 @@ impl PrestigeUpgrade {
         }
     }
 }
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Research {
+    points: f64,
+    unlocked: Vec<ResearchNode>,
+}
+
+impl Research {
+    fn from_game(r: &crate::research::Research) -> Self {
+        // Sorted so that saves don't change from run to run
+        let mut unlocked: Vec<_> = r.unlocked.iter().copied().collect();
+        unlocked.sort();
+        Self {
+            points: r.points.into(),
+            unlocked: unlocked.into_iter().map(ResearchNode::from_game).collect(),
+        }
+    }
+
+    fn into_game(self) -> crate::research::Research {
+        crate::research::Research {
+            points: self.points.into(),
+            unlocked: self
+                .unlocked
+                .into_iter()
+                .map(ResearchNode::into_game)
+                .collect(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum ResearchNode {
+    Efficiency,
+    Quintuple,
+    Momentum,
+    Practice,
+    Grants,
+}
+
+impl ResearchNode {
+    pub(crate) fn from_game(n: crate::research::ResearchNode) -> Self {
+        use crate::research::ResearchNode as Game;
+        use ResearchNode::*;
+        match n {
+            Game::Efficiency => Efficiency,
+            Game::Quintuple => Quintuple,
+            Game::Momentum => Momentum,
+            Game::Practice => Practice,
+            Game::Grants => Grants,
+        }
+    }
+
+    pub(crate) fn into_game(self) -> crate::research::ResearchNode {
+        use crate::research::ResearchNode as Game;
+        use ResearchNode::*;
+        match self {
+            Efficiency => Game::Efficiency,
+            Quintuple => Game::Quintuple,
+            Momentum => Game::Momentum,
+            Practice => Game::Practice,
+            Grants => Game::Grants,
+        }
+    }
+}
+
+/// The random number generator's state is kept so that loading a save
+/// doesn't reroll what comes next.
+#[derive(Serialize, Deserialize, Clone)]
+struct RandomEvents {
+    rng: u64,
+    next_in: Duration,
+    active: Option<ActiveEvent>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct ActiveEvent {
+    event: RandomEvent,
+    remaining: Duration,
+}
+
+impl RandomEvents {
+    fn from_game(r: &crate::random_events::RandomEvents, now: Instant) -> Self {
+        Self {
+            rng: r.rng.0,
+            next_in: r.next_at.saturating_duration_since(now),
+            active: r.active.map(|active| ActiveEvent {
+                event: RandomEvent::from_game(active.event),
+                remaining: active.until.saturating_duration_since(now),
+            }),
+        }
+    }
+
+    fn into_game(self, now: Instant) -> crate::random_events::RandomEvents {
+        crate::random_events::RandomEvents {
+            rng: crate::random_events::Rng(self.rng),
+            next_at: now + self.next_in,
+            active: self.active.map(|active| crate::random_events::ActiveEvent {
+                event: active.event.into_game(),
+                until: now + active.remaining,
+            }),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum RandomEvent {
+    GoldenBar { number: usize },
+    SpeedSurge,
+    MarketCrash,
+}
+
+impl RandomEvent {
+    pub(crate) fn from_game(e: crate::random_events::RandomEvent) -> Self {
+        use crate::random_events::RandomEvent as Game;
+        match e {
+            Game::GoldenBar { number } => RandomEvent::GoldenBar { number },
+            Game::SpeedSurge => RandomEvent::SpeedSurge,
+            Game::MarketCrash => RandomEvent::MarketCrash,
+        }
+    }
+
+    pub(crate) fn into_game(self) -> crate::random_events::RandomEvent {
+        use crate::random_events::RandomEvent as Game;
+        match self {
+            RandomEvent::GoldenBar { number } => Game::GoldenBar { number },
+            RandomEvent::SpeedSurge => Game::SpeedSurge,
+            RandomEvent::MarketCrash => Game::MarketCrash,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::float::Float;
+    use proptest::prelude::*;
+
+    fn round_trip(app: &crate::app::App) -> (serde_json::Value, serde_json::Value) {
+        let saved = App::from_game(app);
+        let before = serde_json::to_value(&saved).unwrap();
+        let loaded = saved.into_game(crate::app::App::for_test().opts, app.tick);
+        let after = serde_json::to_value(App::from_game(&loaded)).unwrap();
+        (before, after)
+    }
+
+    #[test]
+    fn old_save_without_prestige_loads() {
+        let save: App = serde_json::from_str(include_str!("../adam.json")).unwrap();
+        let app = save.into_game(crate::app::App::for_test().opts, Instant::now());
+        assert!(!app.bars.is_empty());
+        assert_eq!(app.prestige.current, Float(0.));
+        for upgrade in crate::prestige::PrestigeUpgrade::iter() {
+            assert_eq!(app.prestige.level(upgrade), 0);
+        }
+    }
+
+    #[test]
+    fn boost_is_saved_as_remaining_duration() {
+        let mut app = crate::app::App::for_test();
+        let mut bar = crate::Bar::new(1);
+        bar.boost_until = Some(app.tick + Duration::from_secs(3));
+        app.bars.push_back(bar);
+        let save = App::from_game(&app);
+        assert_eq!(save.bars[0].boost_remaining, Duration::from_secs(3));
+    }
+
+    proptest! {
+        #[test]
+        fn save_load_save_is_identical(
+            bars in prop::collection::vec(
+                (0f64..100., 0f64..1e12, 0usize..50, 0usize..5, 0u64..60, 0usize..20),
+                0..20,
+            ),
+            global_upgrades in prop::collection::vec(0usize..30, crate::GlobalUpgrade::COUNT),
+            prestige_points in 0f64..1e4,
+            prestige_upgrades in
+                prop::collection::vec(0usize..8, crate::prestige::PrestigeUpgrade::COUNT),
+        ) {
+            let mut app = crate::app::App::for_test();
+            for (i, (progress, gathered, level, exponent, boost, upgrade_level)) in
+                bars.into_iter().enumerate()
+            {
+                let mut bar = crate::Bar::new(i + 1);
+                bar.progress = Float(progress);
+                bar.gathered = Float(gathered);
+                bar.level = level;
+                bar.gain_exponent = exponent;
+                bar.boost_until = Some(app.tick + Duration::from_secs(boost));
+                for upgrade in crate::Upgrade::iter() {
+                    bar.upgrades.insert(upgrade, upgrade_level);
+                }
+                app.bars.push_front(bar);
+                app.last_bar_number = i + 1;
+            }
+            for (upgrade, level) in crate::GlobalUpgrade::iter().zip(global_upgrades) {
+                app.global_upgrades.insert(upgrade, level);
+            }
+            app.prestige.current = Float(prestige_points);
+            let prestige_upgrades = crate::prestige::PrestigeUpgrade::iter().zip(prestige_upgrades);
+            for (upgrade, level) in prestige_upgrades {
+                app.prestige.upgrades.insert(upgrade, level);
+            }
+
+            let (before, after) = round_trip(&app);
+            prop_assert_eq!(before, after);
+        }
+    }
+}
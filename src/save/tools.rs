@@ -0,0 +1,315 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use strum::*;
+
+use super::{App, GlobalUpgrade, Prestige, PrestigeUpgrade, Upgrade};
+use crate::app::problem_list;
+use crate::float::Float;
+
+impl App {
+    /// A readable summary of everything in the save.
+    pub(crate) fn inspect(&self) -> String {
+        let mut out = String::new();
+        let prestige = self
+            .prestige
+            .clone()
+            .unwrap_or_else(|| Prestige::from_game(&crate::prestige::Prestige::new()));
+        let _ = writeln!(
+            out,
+            "Bars: {bars}, {spawn} to spawn, last bar number {number}",
+            bars = self.bars.len(),
+            spawn = self.bars_to_spawn,
+            number = self.last_bar_number
+        );
+        for bar in &self.bars {
+            let upgrades = crate::Upgrade::iter()
+                .map(|u| Upgrade::from_game(&u))
+                .map(|u| format!("{u:?} {}", bar.upgrades.get(&u).unwrap_or(&0)))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let _ = writeln!(
+                out,
+                "  #{number}: L{level}, gathered {gathered}, progress {progress}%, \
+                 exp {exp}, x10^{exponent} gain; {upgrades}",
+                number = bar.number,
+                level = bar.level,
+                gathered = Float(bar.gathered),
+                progress = Float(bar.progress),
+                exp = Float(bar.exp),
+                exponent = bar.gain_exponent,
+            );
+        }
+        let globals = crate::upgrade::GlobalUpgrade::iter()
+            .map(GlobalUpgrade::from_game)
+            .map(|u| format!("{u:?} {}", self.global_upgrades.get(&u).unwrap_or(&0)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let _ = writeln!(out, "Global upgrades: {globals}");
+        let upgrades = prestige.upgrades.unwrap_or_default();
+        let prestige_upgrades = crate::prestige::PrestigeUpgrade::iter()
+            .map(|u| PrestigeUpgrade::from_game(&u))
+            .map(|u| format!("{u:?} {}", upgrades.get(&u).unwrap_or(&0)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let _ = writeln!(
+            out,
+            "Prestige points: {}\nPrestige upgrades: {prestige_upgrades}",
+            Float(prestige.current)
+        );
+        let completed = prestige
+            .completed_challenges
+            .unwrap_or_default()
+            .iter()
+            .map(|c| format!("{c:?}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let _ = writeln!(
+            out,
+            "Challenge: {challenge}, completed: {completed}",
+            challenge = prestige
+                .challenge
+                .map_or("none".to_owned(), |c| format!("{c:?}")),
+        );
+        if let Some(research) = &self.research {
+            let unlocked = research
+                .unlocked
+                .iter()
+                .map(|n| format!("{n:?}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let _ = writeln!(
+                out,
+                "Research points: {}, researched: {unlocked}",
+                Float(research.points)
+            );
+        }
+        if let Some(random_events) = &self.random_events {
+            let _ = writeln!(
+                out,
+                "Random event: {active}, next in {next_in}s",
+                active = random_events
+                    .active
+                    .as_ref()
+                    .map_or("none".to_owned(), |a| {
+                        format!("{:?} for {}s", a.event, a.remaining.as_secs())
+                    }),
+                next_in = random_events.next_in.as_secs()
+            );
+        }
+        let problems = self.validate();
+        if !problems.is_empty() {
+            let _ = writeln!(out, "Problems:\n{}", problem_list(&problems));
+        }
+        out
+    }
+
+    /// Every field that differs from `other`, as `path: ours -> theirs`.
+    pub(crate) fn diff(&self, other: &App) -> Vec<String> {
+        let ours = fields(&self.normalized());
+        let theirs = fields(&other.normalized());
+        let ours_by_path: HashMap<_, _> = ours.iter().map(|(p, v)| (p.as_str(), v)).collect();
+        let theirs_by_path: HashMap<_, _> = theirs.iter().map(|(p, v)| (p.as_str(), v)).collect();
+        let only_theirs = theirs
+            .iter()
+            .filter(|(path, _)| !ours_by_path.contains_key(path.as_str()));
+        let show = |value: Option<&&serde_json::Value>| {
+            value.map_or("missing".to_owned(), |value| value.to_string())
+        };
+        ours.iter()
+            .chain(only_theirs)
+            .map(|(path, _)| path.as_str())
+            .filter(|path| ours_by_path.get(path) != theirs_by_path.get(path))
+            .map(|path| {
+                format!(
+                    "{path}: {} -> {}",
+                    show(ours_by_path.get(path)),
+                    show(theirs_by_path.get(path))
+                )
+            })
+            .collect()
+    }
+
+    /// Sets the field at `path`, written like the paths in `Problem`s
+    /// without the leading `$.`, to `value`. Nothing changes if the result
+    /// isn't a valid save.
+    pub(crate) fn set(&mut self, path: &str, value: &str) -> anyhow::Result<()> {
+        let mut edited = self.clone();
+        edited.set_unchecked(path, value)?;
+        let problems = edited.validate();
+        if !problems.is_empty() {
+            anyhow::bail!(
+                "Setting {path} to {value} breaks the save:\n{}",
+                problem_list(&problems)
+            );
+        }
+        *self = edited;
+        Ok(())
+    }
+
+    fn set_unchecked(&mut self, path: &str, value: &str) -> anyhow::Result<()> {
+        let parts: Vec<&str> = path.split('.').collect();
+        match parts[..] {
+            ["bars_to_spawn"] => self.bars_to_spawn = parse(value)?,
+            ["last_bar_number"] => self.last_bar_number = parse(value)?,
+            ["global_upgrades", upgrade] => {
+                self.global_upgrades
+                    .insert(parse_name(upgrade)?, parse(value)?);
+            }
+            ["prestige", "current"] => self.prestige_mut().current = parse(value)?,
+            ["prestige", "upgrades", upgrade] => {
+                let upgrade = parse_name(upgrade)?;
+                let value = parse(value)?;
+                self.prestige_mut()
+                    .upgrades
+                    .get_or_insert_with(Default::default)
+                    .insert(upgrade, value);
+            }
+            [bar, ..] if bar.starts_with("bars[") && bar.ends_with(']') => {
+                let index: usize = bar["bars[".len()..bar.len() - 1]
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("Invalid bar index in {bar}"))?;
+                let count = self.bars.len();
+                let Some(bar) = self.bars.get_mut(index) else {
+                    anyhow::bail!("There is no bar {index}, the save has {count}");
+                };
+                match parts[1..] {
+                    ["progress"] => bar.progress = parse(value)?,
+                    ["gathered"] => bar.gathered = parse(value)?,
+                    ["transfer_ratio"] => bar.transfer_ratio = parse(value)?,
+                    ["number"] => bar.number = parse(value)?,
+                    ["exp"] => bar.exp = parse(value)?,
+                    ["level"] => bar.level = parse(value)?,
+                    ["gain_exponent"] => bar.gain_exponent = parse(value)?,
+                    ["level_speed"] => bar.level_speed = parse(value)?,
+                    ["upgrades", upgrade] => {
+                        bar.upgrades.insert(parse_name(upgrade)?, parse(value)?);
+                    }
+                    _ => anyhow::bail!("Unknown bar field {path}"),
+                }
+            }
+            _ => anyhow::bail!("Unknown field {path}"),
+        }
+        Ok(())
+    }
+
+    /// Fills in what old saves leave out with the values they load as, so
+    /// that it doesn't show up as a difference.
+    fn normalized(&self) -> App {
+        let mut save = self.clone();
+        for upgrade in crate::upgrade::GlobalUpgrade::iter() {
+            save.global_upgrades
+                .entry(GlobalUpgrade::from_game(upgrade))
+                .or_insert(0);
+        }
+        let upgrades = save
+            .prestige_mut()
+            .upgrades
+            .get_or_insert_with(Default::default);
+        for upgrade in crate::prestige::PrestigeUpgrade::iter() {
+            upgrades
+                .entry(PrestigeUpgrade::from_game(&upgrade))
+                .or_insert(0);
+        }
+        save
+    }
+
+    fn prestige_mut(&mut self) -> &mut Prestige {
+        self.prestige
+            .get_or_insert_with(|| Prestige::from_game(&crate::prestige::Prestige::new()))
+    }
+}
+
+/// Every leaf value in the save's json with its path, in order.
+fn fields(save: &App) -> Vec<(String, serde_json::Value)> {
+    fn walk(path: String, value: serde_json::Value, out: &mut Vec<(String, serde_json::Value)>) {
+        match value {
+            serde_json::Value::Object(map) => {
+                for (key, value) in map {
+                    walk(format!("{path}.{key}"), value, out);
+                }
+            }
+            serde_json::Value::Array(values) => {
+                for (i, value) in values.into_iter().enumerate() {
+                    walk(format!("{path}[{i}]"), value, out);
+                }
+            }
+            value => out.push((path, value)),
+        }
+    }
+    let mut out = vec![];
+    let value = serde_json::to_value(save).expect("Saves should serialize");
+    walk("$".to_owned(), value, &mut out);
+    out
+}
+
+fn parse<T: std::str::FromStr>(value: &str) -> anyhow::Result<T> {
+    value.parse().map_err(|_| {
+        anyhow::anyhow!(
+            "Invalid value {value}, expected {}",
+            std::any::type_name::<T>()
+        )
+    })
+}
+
+/// Parses the name of an upgrade as it's written in saves.
+fn parse_name<T: serde::de::DeserializeOwned>(name: &str) -> anyhow::Result<T> {
+    Ok(serde_json::from_value(serde_json::Value::String(
+        name.to_owned(),
+    ))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn adam() -> App {
+        serde_json::from_str(include_str!("../../adam.json")).unwrap()
+    }
+
+    #[test]
+    fn inspect_lists_every_bar() {
+        let save = adam();
+        let inspected = save.inspect();
+        assert!(inspected.starts_with(&format!("Bars: {}", save.bars.len())));
+        assert_eq!(
+            inspected.lines().filter(|l| l.starts_with("  #")).count(),
+            save.bars.len()
+        );
+        assert!(!inspected.contains("Problems"));
+    }
+
+    #[test]
+    fn set_changes_fields_and_diff_shows_them() {
+        let old = adam();
+        let mut new = adam();
+        new.set("prestige.current", "12.5").unwrap();
+        new.set("bars[1].upgrades.Double", "2").unwrap();
+        new.set("global_upgrades.ExpGain", "7").unwrap();
+        assert_eq!(
+            old.diff(&new),
+            [
+                format!(
+                    "$.bars[1].upgrades.Double: {} -> 2",
+                    old.bars[1].upgrades[&Upgrade::Double]
+                ),
+                format!(
+                    "$.global_upgrades.ExpGain: {} -> 7",
+                    old.global_upgrades[&GlobalUpgrade::ExpGain]
+                ),
+                "$.prestige.current: 0.0 -> 12.5".to_owned(),
+            ]
+        );
+    }
+
+    #[test]
+    fn set_rejects_unknown_fields_and_broken_values() {
+        let mut save = adam();
+        assert!(save.set("bars[0].speed", "1").is_err());
+        assert!(save.set("bars[999].level", "1").is_err());
+        assert!(save.set("global_upgrades.Faster", "1").is_err());
+        assert!(save.set("bars[0].level", "one").is_err());
+        let err = save.set("bars[0].level", "0").unwrap_err();
+        assert!(err.to_string().contains("breaks the save"), "{err}");
+        assert_eq!(save.bars[0].level, adam().bars[0].level);
+    }
+}
@@ -0,0 +1,419 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::time::Duration;
+use strum::*;
+
+use super::{App, Bar, GlobalUpgrade, Prestige, RandomEvent, RandomEvents, Research, Upgrade};
+use crate::balance;
+use crate::bar::{MAX_BOOST, MAX_GAIN_EXPONENT};
+
+/// Something in a save that the game can't handle, and where in the save's
+/// json it is.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Problem {
+    pub(crate) path: String,
+    pub(crate) message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+#[derive(Default)]
+struct Problems(Vec<Problem>);
+
+impl Problems {
+    fn push(&mut self, path: &str, message: String) {
+        self.0.push(Problem {
+            path: path.to_owned(),
+            message,
+        });
+    }
+
+    /// Resets `value` to `default` unless it's finite and at least `min`.
+    fn check_float(&mut self, path: &str, value: &mut f64, min: f64, default: f64) {
+        if !value.is_finite() || *value < min {
+            self.push(
+                path,
+                format!("{value} should be a number of at least {min}"),
+            );
+            *value = default;
+        }
+    }
+
+    /// Lowers `level` to the highest level whose cost is finite, since the
+    /// ones above can't be reached by playing and overflow when used.
+    fn check_level(&mut self, path: &str, level: &mut usize, cost: impl Fn(usize) -> Option<f64>) {
+        if cost(*level).is_some_and(f64::is_finite) {
+            return;
+        }
+        let (mut low, mut high) = (0, *level);
+        while low + 1 < high {
+            let mid = low + (high - low) / 2;
+            if cost(mid).is_some_and(f64::is_finite) {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        self.push(path, format!("level {level} is out of range"));
+        *level = low;
+    }
+}
+
+impl App {
+    /// Every problem that stops the save from loading and playing
+    /// correctly. Empty if the save is fine.
+    pub(crate) fn validate(&self) -> Vec<Problem> {
+        self.clone().repair()
+    }
+
+    /// Fixes every problem `validate` reports, and returns them.
+    pub(crate) fn repair(&mut self) -> Vec<Problem> {
+        let mut problems = Problems::default();
+
+        for upgrade in crate::upgrade::GlobalUpgrade::iter() {
+            let key = GlobalUpgrade::from_game(upgrade);
+            if let Some(level) = self.global_upgrades.get_mut(&key) {
+                problems.check_level(&format!("$.global_upgrades.{key:?}"), level, |level| {
+                    Some(upgrade.cost(level).0)
+                });
+            }
+        }
+        let global_speed_levels = self
+            .global_upgrades
+            .get(&GlobalUpgrade::Speed)
+            .copied()
+            .unwrap_or(0);
+
+        if let Some(research) = &mut self.research {
+            problems.check_float("$.research.points", &mut research.points, 0., 0.);
+        }
+        let research = self
+            .research
+            .clone()
+            .map_or_else(crate::research::Research::new, Research::into_game);
+
+        for (i, bar) in self.bars.iter_mut().enumerate() {
+            let path = format!("$.bars[{i}]");
+            bar.repair(&path, global_speed_levels, &research, &mut problems);
+        }
+        self.repair_bar_numbers(&mut problems);
+
+        if let Some(prestige) = &mut self.prestige {
+            prestige.repair(&mut problems);
+        }
+
+        let bar_numbers: HashSet<_> = self.bars.iter().map(|bar| bar.number).collect();
+        if let Some(random_events) = &mut self.random_events {
+            random_events.repair(&bar_numbers, &mut problems);
+        }
+
+        problems.0
+    }
+
+    /// Bars are numbered `last_bar_number` downwards from the newest bar at
+    /// the front. Renumbers them if numbers are reused or too high, which
+    /// only changes which bar upgrade costs appear to come from.
+    fn repair_bar_numbers(&mut self, problems: &mut Problems) {
+        let mut seen = HashSet::new();
+        let mut valid = true;
+        for (i, bar) in self.bars.iter().enumerate() {
+            let path = format!("$.bars[{i}].number");
+            if !seen.insert(bar.number) {
+                problems.push(&path, format!("{} is used by another bar", bar.number));
+                valid = false;
+            } else if bar.number > self.last_bar_number {
+                problems.push(
+                    &path,
+                    format!(
+                        "{} is above last_bar_number {}",
+                        bar.number, self.last_bar_number
+                    ),
+                );
+                valid = false;
+            }
+        }
+        if !valid {
+            self.last_bar_number = self.last_bar_number.max(self.bars.len());
+            for (i, bar) in self.bars.iter_mut().enumerate() {
+                bar.number = self.last_bar_number - i;
+            }
+        }
+    }
+}
+
+impl Bar {
+    fn repair(
+        &mut self,
+        path: &str,
+        global_speed_levels: usize,
+        research: &crate::research::Research,
+        problems: &mut Problems,
+    ) {
+        let field = |name: &str| format!("{path}.{name}");
+
+        problems.check_float(&field("progress"), &mut self.progress, 0., 0.);
+        problems.check_float(&field("gathered"), &mut self.gathered, 0., 0.);
+        problems.check_float(&field("exp"), &mut self.exp, 0., 0.);
+        let balance = &balance::get().bar;
+        if !(0. ..=balance.max_transfer_ratio).contains(&self.transfer_ratio) {
+            problems.push(
+                &field("transfer_ratio"),
+                format!(
+                    "{} should be between 0 and {}",
+                    self.transfer_ratio, balance.max_transfer_ratio
+                ),
+            );
+            self.transfer_ratio = balance.transfer_ratio;
+        }
+        if self.level == 0 {
+            problems.push(&field("level"), "should be at least 1".to_owned());
+            self.level = 1;
+        }
+        let max_level = crate::Bar::max_level();
+        if self.level > max_level {
+            problems.push(
+                &field("level"),
+                format!("{} is above the maximum {max_level}", self.level),
+            );
+            self.level = max_level;
+        }
+        if !self.level_speed.is_finite() || self.level_speed <= 0. {
+            problems.push(
+                &field("level_speed"),
+                format!("{} should be a positive number", self.level_speed),
+            );
+            self.level_speed = crate::Bar::level_speed_for(self.level, research).0;
+        }
+        if self.boost_remaining > MAX_BOOST {
+            problems.push(
+                &field("boost_remaining"),
+                format!("{:?} is longer than boosts last", self.boost_remaining),
+            );
+            self.boost_remaining = MAX_BOOST;
+        }
+        let max_cooldown = Duration::from_secs(balance.charge_cooldown_secs);
+        if let Some(cooldown) = self.charge_cooldown.filter(|c| *c > max_cooldown) {
+            problems.push(
+                &field("charge_cooldown"),
+                format!("{cooldown:?} is longer than the cooldown"),
+            );
+            self.charge_cooldown = Some(max_cooldown);
+        }
+
+        for upgrade in crate::Upgrade::iter() {
+            let key = Upgrade::from_game(&upgrade);
+            let path = format!("{path}.upgrades.{key:?}");
+            match self.upgrades.get_mut(&key) {
+                // Doesn't exist in old saves
+                None if key == Upgrade::Quintuple => (),
+                None => {
+                    problems.push(&path, "is missing".to_owned());
+                    self.upgrades.insert(key, 0);
+                }
+                Some(level) => {
+                    problems.check_level(&path, level, |level| Some(upgrade.cost(level).0))
+                }
+            }
+        }
+
+        // Checked last, as it depends on the speed
+        let mut bar = self.clone().into_game(std::time::Instant::now());
+        bar.gain_exponent = 0;
+        let gain_exponent = bar.settled_gain_exponent(global_speed_levels, research);
+        if self.gain_exponent > gain_exponent
+            || self.gain_exponent + 1 < gain_exponent
+            || self.gain_exponent > MAX_GAIN_EXPONENT
+        {
+            problems.push(
+                &field("gain_exponent"),
+                format!("{} doesn't match the bar's speed", self.gain_exponent),
+            );
+            self.gain_exponent = gain_exponent;
+        }
+    }
+}
+
+impl Prestige {
+    fn repair(&mut self, problems: &mut Problems) {
+        problems.check_float("$.prestige.current", &mut self.current, 0., 0.);
+        let cost_base = balance::get().prestige.upgrade_cost_base;
+        for (upgrade, level) in self.upgrades.iter_mut().flatten() {
+            let path = format!("$.prestige.upgrades.{upgrade:?}");
+            let max_level = upgrade.into_game().max_level();
+            if let Some(max_level) = max_level.filter(|max| *level > *max) {
+                problems.push(&path, format!("{level} is above the maximum {max_level}"));
+                *level = max_level;
+            }
+            problems.check_level(&path, level, |level| {
+                u32::try_from(level)
+                    .ok()
+                    .and_then(|level| cost_base.checked_pow(level))
+                    .map(|cost| cost as f64)
+            });
+        }
+    }
+}
+
+impl RandomEvents {
+    /// Durations are limited to what the balance could have produced, as
+    /// the game can't add much more than that to the current time.
+    fn repair(&mut self, bar_numbers: &HashSet<usize>, problems: &mut Problems) {
+        let balance = &balance::get().random_events;
+        // Events are at most one and a half times the mean interval apart
+        let max_interval = balance.mean_interval() * 2;
+        if self.next_in > max_interval {
+            problems.push(
+                "$.random_events.next_in",
+                format!("{:?} is longer than events are apart", self.next_in),
+            );
+            self.next_in = balance.mean_interval();
+        }
+        let Some(active) = &mut self.active else {
+            return;
+        };
+        if let RandomEvent::GoldenBar { number } = active.event {
+            if !bar_numbers.contains(&number) {
+                problems.push(
+                    "$.random_events.active.event",
+                    format!("there is no bar #{number} to be golden"),
+                );
+                self.active = None;
+                return;
+            }
+        }
+        let duration = balance.duration(active.event.into_game());
+        if active.remaining > duration {
+            problems.push(
+                "$.random_events.active.remaining",
+                format!("{:?} is longer than the event lasts", active.remaining),
+            );
+            active.remaining = duration;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::save::PrestigeUpgrade;
+
+    fn adam() -> App {
+        serde_json::from_str(include_str!("../../adam.json")).unwrap()
+    }
+
+    fn paths(problems: &[Problem]) -> Vec<&str> {
+        problems.iter().map(|p| p.path.as_str()).collect()
+    }
+
+    #[test]
+    fn adam_is_valid() {
+        assert_eq!(adam().validate(), []);
+    }
+
+    #[test]
+    fn problems_have_paths_and_are_repaired() {
+        let mut save = adam();
+        save.bars[0].gathered = -5.;
+        save.bars[1].progress = f64::NAN;
+        save.bars[2].level = 0;
+        save.bars[3].upgrades.remove(&Upgrade::Triple);
+        save.bars[4].gain_exponent = 400;
+        save.bars[5].transfer_ratio = 0.8;
+        save.bars[6].number = save.bars[5].number;
+        save.prestige = Some(Prestige::from_game(&crate::prestige::Prestige::new()));
+        save.prestige.as_mut().unwrap().current = f64::INFINITY;
+        save.prestige
+            .as_mut()
+            .unwrap()
+            .upgrades
+            .as_mut()
+            .unwrap()
+            .insert(PrestigeUpgrade::UpgradeAnyButton, 9);
+
+        let problems = save.repair();
+        assert_eq!(
+            paths(&problems),
+            [
+                "$.bars[0].gathered",
+                "$.bars[1].progress",
+                "$.bars[2].level",
+                "$.bars[3].upgrades.Triple",
+                "$.bars[4].gain_exponent",
+                "$.bars[5].transfer_ratio",
+                "$.bars[6].number",
+                "$.prestige.current",
+                "$.prestige.upgrades.UpgradeAnyButton",
+            ]
+        );
+        assert_eq!(save.validate(), []);
+        assert_eq!(save.bars[0].gathered, 0.);
+        assert_eq!(save.bars[2].level, 1);
+    }
+
+    #[test]
+    fn random_events_are_limited_to_what_the_game_could_roll() {
+        let mut save = adam();
+        let now = std::time::Instant::now();
+        let mut random_events =
+            crate::random_events::RandomEvents::new(crate::random_events::Rng(1), now);
+        random_events.next_at = now + std::time::Duration::from_secs(1 << 40);
+        random_events.active = Some(crate::random_events::ActiveEvent {
+            event: crate::random_events::RandomEvent::GoldenBar { number: 1 << 20 },
+            until: now,
+        });
+        save.random_events = Some(RandomEvents::from_game(&random_events, now));
+
+        let problems = save.repair();
+        assert_eq!(
+            paths(&problems),
+            ["$.random_events.next_in", "$.random_events.active.event"]
+        );
+        assert_eq!(save.validate(), []);
+    }
+
+    #[test]
+    fn unreachable_levels_are_lowered() {
+        let mut save = adam();
+        save.bars[0].upgrades.insert(Upgrade::Double, usize::MAX);
+        save.global_upgrades.insert(GlobalUpgrade::Gain, 1 << 40);
+        let problems = save.repair();
+        assert_eq!(
+            paths(&problems),
+            ["$.global_upgrades.Gain", "$.bars[0].upgrades.Double"]
+        );
+        let level = save.bars[0].upgrades[&Upgrade::Double];
+        assert!(crate::Upgrade::Double.cost(level).0.is_finite());
+        assert!(!crate::Upgrade::Double.cost(level + 1).0.is_finite());
+    }
+
+    #[test]
+    fn crafted_bars_are_bounded() {
+        let mut json = serde_json::to_value(adam()).unwrap();
+        json["bars"][0]["level"] = serde_json::json!(1_000_000_000_000_000_000u64);
+        json["bars"][0]["level_speed"] = serde_json::json!(-1);
+        json["bars"][1]["boost_remaining"] = serde_json::json!({"secs": u64::MAX, "nanos": 0});
+        json["bars"][2]["charge_cooldown"] = serde_json::json!({"secs": u64::MAX, "nanos": 0});
+        let mut save: App = serde_json::from_value(json).unwrap();
+
+        let problems = save.repair();
+        assert_eq!(
+            paths(&problems),
+            [
+                "$.bars[0].level",
+                "$.bars[0].level_speed",
+                "$.bars[0].gain_exponent",
+                "$.bars[1].boost_remaining",
+                "$.bars[2].charge_cooldown",
+            ]
+        );
+        assert_eq!(save.validate(), []);
+        assert_eq!(save.bars[0].level, crate::Bar::max_level());
+        let app = save.into_game(crate::app::App::for_test().opts, std::time::Instant::now());
+        assert!(app.bars[1].boost_until.is_some());
+        assert!(app.bars[2].charge_ready_at.is_some());
+    }
+}
@@ -0,0 +1,166 @@
+use anyhow::Context;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use std::io::{Read, Write};
+use std::path::Path;
+
+use crate::save;
+
+// A save string is `antsy1.<data>.<checksum>`, where data is the save
+// json, deflated and base64 encoded, and checksum is the crc32 of the
+// deflated bytes in hex.
+
+const PREFIX: &str = "antsy1";
+
+/// Saves are a few kilobytes, so a string that inflates to more than this
+/// isn't one.
+const MAX_SAVE_BYTES: u64 = 16 * 1024 * 1024;
+
+pub(crate) fn export(save: &save::App) -> String {
+    encode(&serde_json::to_vec(save).expect("Saves should serialize"))
+}
+
+fn encode(json: &[u8]) -> String {
+    let mut encoder = DeflateEncoder::new(vec![], Compression::best());
+    encoder
+        .write_all(json)
+        .expect("Writing to a vec can't fail");
+    let data = encoder.finish().expect("Writing to a vec can't fail");
+    format!(
+        "{PREFIX}.{}.{:08x}",
+        URL_SAFE_NO_PAD.encode(&data),
+        crc32fast::hash(&data)
+    )
+}
+
+/// Parses a string made by `export`. Whitespace is ignored, so strings
+/// that were wrapped over several lines can be pasted as they are.
+pub(crate) fn import(code: &str) -> anyhow::Result<save::App> {
+    let code: String = code.chars().filter(|c| !c.is_whitespace()).collect();
+    let mut parts = code.split('.');
+    let (Some(prefix), Some(data), Some(checksum), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        anyhow::bail!("Not a save string, expected {PREFIX}.<data>.<checksum>");
+    };
+    if prefix != PREFIX {
+        anyhow::bail!("Unsupported save string version {prefix}");
+    }
+    let data = URL_SAFE_NO_PAD
+        .decode(data)
+        .context("Save string is corrupted")?;
+    let checksum = u32::from_str_radix(checksum, 16).context("Invalid checksum")?;
+    if crc32fast::hash(&data) != checksum {
+        anyhow::bail!("Checksum doesn't match, the save string is corrupted or incomplete");
+    }
+    let mut json = vec![];
+    DeflateDecoder::new(&data[..])
+        .take(MAX_SAVE_BYTES + 1)
+        .read_to_end(&mut json)
+        .context("Save string is corrupted")?;
+    if json.len() as u64 > MAX_SAVE_BYTES {
+        anyhow::bail!("Save string holds more than {MAX_SAVE_BYTES} bytes, it isn't a save");
+    }
+    let save: save::App =
+        serde_json::from_slice(&json).context("Save string doesn't contain a valid save")?;
+    let problems = save.validate();
+    if !problems.is_empty() {
+        anyhow::bail!(
+            "The save in the save string has problems:\n{}",
+            crate::app::problem_list(&problems)
+        );
+    }
+    Ok(save)
+}
+
+/// Where the save string of the save at `save_file` is written, since it
+/// can be too long to show or copy from the terminal.
+pub(crate) fn export_path(save_file: &str) -> String {
+    Path::new(save_file)
+        .with_extension("export.txt")
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Reads the save at `path` and makes a save string of it.
+pub(crate) fn export_file(path: &str) -> anyhow::Result<String> {
+    Ok(export(&save::App::read(path)?))
+}
+
+/// Writes the save in `code` to `path`, refusing to overwrite an existing
+/// save unless `force` is set.
+pub(crate) fn import_file(code: &str, path: &str, force: bool) -> anyhow::Result<()> {
+    let save = import(code)?;
+    if !force && Path::new(path).exists() {
+        anyhow::bail!("{path} already exists, pass --force to overwrite it");
+    }
+    save.write(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn adam() -> save::App {
+        serde_json::from_str(include_str!("../adam.json")).unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let code = export(&adam());
+        let imported = import(&code).unwrap();
+        assert_eq!(
+            serde_json::to_value(adam()).unwrap(),
+            serde_json::to_value(imported).unwrap()
+        );
+    }
+
+    #[test]
+    fn wrapped_strings_import() {
+        let code = export(&adam());
+        let (a, b) = code.split_at(code.len() / 2);
+        assert!(import(&format!("  {a}\n{b}\n")).is_ok());
+    }
+
+    #[test]
+    fn import_file_does_not_overwrite() {
+        let path = std::env::temp_dir().join("antsy-test-import.json");
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+        let code = export(&adam());
+
+        import_file(&code, path, false).unwrap();
+        assert!(import_file(&code, path, false).is_err());
+        import_file(&code, path, true).unwrap();
+        let exported = import(&export_file(path).unwrap()).unwrap();
+        assert_eq!(
+            serde_json::to_value(adam()).unwrap(),
+            serde_json::to_value(exported).unwrap()
+        );
+    }
+
+    #[test]
+    fn corruption_is_caught() {
+        let code = export(&adam());
+        let i = PREFIX.len() + 10;
+        let replacement = if &code[i..=i] == "A" { "B" } else { "A" };
+        let corrupted = format!("{}{replacement}{}", &code[..i], &code[i + 1..]);
+        let Err(err) = import(&corrupted) else {
+            panic!("Corrupted string was imported");
+        };
+        assert!(err.to_string().contains("Checksum"), "{err}");
+
+        let truncated = &code[..code.len() - 20];
+        assert!(import(truncated).is_err());
+        assert!(import("hello").is_err());
+    }
+
+    #[test]
+    fn oversized_saves_are_rejected() {
+        let code = encode(&vec![b' '; MAX_SAVE_BYTES as usize + 1]);
+        let Err(err) = import(&code) else {
+            panic!("Oversized string was imported");
+        };
+        assert!(err.to_string().contains("isn't a save"), "{err}");
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::VecDeque;
+use strum::*;
+
+use crate::app::App;
+use crate::bar::Bar;
+use crate::float::Float;
+use crate::prestige::Prestige;
+use crate::upgrade::{GlobalUpgrade, Upgrade};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Purchase {
+    Bar { upgrade: Upgrade, row: usize },
+    Global { upgrade: GlobalUpgrade },
+}
+
+/// Read-only view of the game state that strategies decide from.
+pub(crate) struct GameView<'a> {
+    app: &'a App,
+}
+
+impl<'a> GameView<'a> {
+    pub(crate) fn new(app: &'a App) -> Self {
+        Self { app }
+    }
+
+    pub(crate) fn bars(&self) -> &'a VecDeque<Bar> {
+        &self.app.bars
+    }
+
+    pub(crate) fn global_upgrade(&self, upgrade: GlobalUpgrade) -> usize {
+        self.app.get_global_upgrade_u(upgrade)
+    }
+
+    pub(crate) fn prestige(&self) -> &'a Prestige {
+        &self.app.prestige
+    }
+
+    /// What `purchase` costs, or `None` if the current challenge doesn't
+    /// allow it.
+    pub(crate) fn cost(&self, purchase: Purchase) -> Option<Float> {
+        match purchase {
+            Purchase::Bar { upgrade, row } => self.app.upgrade_cost(row, upgrade),
+            Purchase::Global { upgrade } => self.app.global_upgrade_cost(upgrade),
+        }
+    }
+
+    pub(crate) fn can_afford(&self, purchase: Purchase) -> bool {
+        match purchase {
+            Purchase::Bar { upgrade, row } => self.app.can_afford(row, upgrade),
+            Purchase::Global { upgrade } => self.app.can_afford_global(upgrade),
+        }
+    }
+
+    /// Every purchase that exists right now, affordable or not.
+    pub(crate) fn purchases(&self) -> impl Iterator<Item = Purchase> + 'a {
+        let bar_len = self.bars().len();
+        GlobalUpgrade::iter()
+            .map(|upgrade| Purchase::Global { upgrade })
+            .chain(Upgrade::iter().flat_map(move |upgrade| {
+                (0..bar_len).map(move |row| Purchase::Bar { upgrade, row })
+            }))
+    }
+}
+
+/// Decides what to buy. Purchases are attempted in order and the ones
+/// that can't be afforded by the time they are reached are skipped.
+pub(crate) trait Strategy {
+    fn decide(&mut self, view: &GameView) -> Vec<Purchase>;
+}
+
+#[derive(EnumString, strum::Display, Copy, Clone, Debug)]
+#[strum(serialize_all = "kebab-case")]
+pub(crate) enum Strategies {
+    Greedy,
+    Cheapest,
+}
+
+impl Strategies {
+    pub(crate) fn build(self) -> Box<dyn Strategy> {
+        match self {
+            Strategies::Greedy => Box::new(Greedy),
+            Strategies::Cheapest => Box::new(Cheapest),
+        }
+    }
+}
+
+/// Buys the first affordable upgrade in preference order, globals
+/// first, and bar upgrades from the oldest bar.
+pub(crate) struct Greedy;
+
+impl Strategy for Greedy {
+    fn decide(&mut self, view: &GameView) -> Vec<Purchase> {
+        let globals = GlobalUpgrade::upgrade_preference_order()
+            .into_iter()
+            .map(|upgrade| Purchase::Global { upgrade });
+        let bar_len = view.bars().len();
+        let bars = Upgrade::upgrade_preference_order()
+            .into_iter()
+            .flat_map(|upgrade| {
+                (0..bar_len)
+                    .rev()
+                    .map(move |row| Purchase::Bar { upgrade, row })
+            });
+        globals
+            .chain(bars)
+            .find(|purchase| view.can_afford(*purchase))
+            .into_iter()
+            .collect()
+    }
+}
+
+/// Buys the cheapest affordable upgrade.
+pub(crate) struct Cheapest;
+
+impl Strategy for Cheapest {
+    fn decide(&mut self, view: &GameView) -> Vec<Purchase> {
+        view.purchases()
+            .filter(|purchase| view.can_afford(*purchase))
+            .min_by(|a, b| {
+                view.cost(*a)
+                    .partial_cmp(&view.cost(*b))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .into_iter()
+            .collect()
+    }
+}
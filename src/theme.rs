@@ -0,0 +1,291 @@
+use anyhow::Context;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
+use strum::*;
+use tui::style::Color;
+
+use crate::opts::Opts;
+
+/// The colors everything is drawn with.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Theme {
+    pub(crate) background: Color,
+    pub(crate) text: Color,
+    /// The selected button, and the bar that pays for the selected upgrade
+    pub(crate) highlight: Color,
+    /// Bars that levelled up recently and run faster
+    pub(crate) boosted: Color,
+    /// Bars cycle through these, newest first
+    pub(crate) bars: Vec<Color>,
+    /// Mark buttons with `>` when selected and `[✓]` when affordable,
+    /// and draw bars as text, for when colors and underlines don't show
+    pub(crate) markers: bool,
+}
+
+#[derive(EnumString, EnumIter, strum::Display, Copy, Clone, Debug)]
+#[strum(serialize_all = "kebab-case")]
+pub(crate) enum Themes {
+    Dark,
+    Light,
+    HighContrast,
+    Solarized,
+    /// The terminal's own colors, used when `NO_COLOR` is set
+    Monochrome,
+}
+
+impl Themes {
+    pub(crate) fn build(self) -> Theme {
+        match self {
+            Themes::Dark => Theme {
+                background: Color::Black,
+                text: Color::White,
+                highlight: Color::Yellow,
+                boosted: Color::Yellow,
+                bars: vec![Color::Blue, Color::White, Color::Green, Color::Red],
+                markers: false,
+            },
+            Themes::Light => Theme {
+                background: Color::White,
+                text: Color::Black,
+                highlight: Color::Magenta,
+                boosted: Color::Magenta,
+                bars: vec![Color::Blue, Color::DarkGray, Color::Green, Color::Red],
+                markers: false,
+            },
+            Themes::HighContrast => Theme {
+                background: Color::Black,
+                text: Color::White,
+                highlight: Color::LightYellow,
+                boosted: Color::LightCyan,
+                bars: vec![Color::White, Color::LightGreen, Color::LightMagenta],
+                markers: false,
+            },
+            Themes::Solarized => Theme {
+                background: Color::Rgb(0x00, 0x2b, 0x36),
+                text: Color::Rgb(0x83, 0x94, 0x96),
+                highlight: Color::Rgb(0xb5, 0x89, 0x00),
+                boosted: Color::Rgb(0xcb, 0x4b, 0x16),
+                bars: vec![
+                    Color::Rgb(0x26, 0x8b, 0xd2),
+                    Color::Rgb(0x2a, 0xa1, 0x98),
+                    Color::Rgb(0x85, 0x99, 0x00),
+                    Color::Rgb(0xd3, 0x36, 0x82),
+                ],
+                markers: false,
+            },
+            Themes::Monochrome => Theme {
+                background: Color::Reset,
+                text: Color::Reset,
+                highlight: Color::Reset,
+                boosted: Color::Reset,
+                bars: vec![Color::Reset],
+                markers: true,
+            },
+        }
+    }
+}
+
+/// A theme in the themes file. Colors that aren't given are taken from
+/// the `base` theme, or dark.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CustomTheme {
+    base: Option<String>,
+    background: Option<String>,
+    text: Option<String>,
+    highlight: Option<String>,
+    boosted: Option<String>,
+    bars: Option<Vec<String>>,
+    markers: Option<bool>,
+}
+
+impl Theme {
+    /// The theme chosen with `--theme` and `--accessible`. Setting
+    /// `NO_COLOR` overrides `--theme` with monochrome.
+    pub(crate) fn from_opts(opts: &Opts, no_color: bool) -> anyhow::Result<Theme> {
+        let mut theme = if no_color {
+            Themes::Monochrome.build()
+        } else {
+            Theme::load(&opts.theme, opts.themes_file.as_deref())?
+        };
+        theme.markers |= opts.accessible;
+        Ok(theme)
+    }
+
+    /// The built-in theme called `name`, or else the one in the themes
+    /// file, by default `$XDG_CONFIG_HOME/antsy/themes.json`.
+    pub(crate) fn load(name: &str, themes_file: Option<&str>) -> anyhow::Result<Theme> {
+        if let Ok(theme) = name.parse::<Themes>() {
+            return Ok(theme.build());
+        }
+        let path = match themes_file {
+            Some(path) => PathBuf::from(path),
+            None => dirs::config_dir()
+                .context("Could not find a config directory, pass --themes-file")?
+                .join("antsy")
+                .join("themes.json"),
+        };
+        let themes: HashMap<String, CustomTheme> = match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .with_context(|| format!("Could not parse themes file {path:?}"))?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound && themes_file.is_none() => {
+                HashMap::new()
+            }
+            Err(err) => {
+                return Err(err).with_context(|| format!("Could not read themes file {path:?}"))
+            }
+        };
+        let Some(custom) = themes.get(name) else {
+            let built_in: Vec<_> = Themes::iter().map(|theme| theme.to_string()).collect();
+            anyhow::bail!(
+                "Unknown theme {name}, use one of {} or define it in {path:?}",
+                built_in.join(", ")
+            );
+        };
+        custom
+            .resolve()
+            .with_context(|| format!("Invalid theme {name} in {path:?}"))
+    }
+}
+
+impl CustomTheme {
+    fn resolve(&self) -> anyhow::Result<Theme> {
+        let base = match &self.base {
+            None => Themes::Dark,
+            Some(base) => base
+                .parse()
+                .map_err(|_| anyhow::anyhow!("Unknown base theme {base}"))?,
+        };
+        let mut theme = base.build();
+        let colors = [
+            (&self.background, &mut theme.background),
+            (&self.text, &mut theme.text),
+            (&self.highlight, &mut theme.highlight),
+            (&self.boosted, &mut theme.boosted),
+        ];
+        for (custom, color) in colors {
+            if let Some(custom) = custom {
+                *color = parse_color(custom)?;
+            }
+        }
+        if let Some(bars) = &self.bars {
+            if bars.is_empty() {
+                anyhow::bail!("bars needs at least one color");
+            }
+            theme.bars = bars
+                .iter()
+                .map(|color| parse_color(color))
+                .collect::<anyhow::Result<_>>()?;
+        }
+        if let Some(markers) = self.markers {
+            theme.markers = markers;
+        }
+        Ok(theme)
+    }
+}
+
+/// Whether the user asked for no colors, see https://no-color.org.
+pub(crate) fn no_color() -> bool {
+    std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty())
+}
+
+/// Parses a color name like `light-blue`, a `#rrggbb` hex color, or a
+/// 0-255 terminal palette index.
+fn parse_color(color: &str) -> anyhow::Result<Color> {
+    if let Some(hex) = color.strip_prefix('#') {
+        let value = u32::from_str_radix(hex, 16)
+            .ok()
+            .filter(|_| hex.len() == 6)
+            .with_context(|| format!("Invalid hex color {color}, expected #rrggbb"))?;
+        let [_, r, g, b] = value.to_be_bytes();
+        return Ok(Color::Rgb(r, g, b));
+    }
+    if let Ok(index) = color.parse() {
+        return Ok(Color::Indexed(index));
+    }
+    Ok(match color.to_lowercase().replace('_', "-").as_str() {
+        "reset" => Color::Reset,
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" => Color::Gray,
+        "dark-gray" => Color::DarkGray,
+        "light-red" => Color::LightRed,
+        "light-green" => Color::LightGreen,
+        "light-yellow" => Color::LightYellow,
+        "light-blue" => Color::LightBlue,
+        "light-magenta" => Color::LightMagenta,
+        "light-cyan" => Color::LightCyan,
+        "white" => Color::White,
+        _ => anyhow::bail!("Unknown color {color}"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use structopt::StructOpt;
+
+    fn themes_file(test: &str, contents: &str) -> String {
+        let path = std::env::temp_dir().join(format!("antsy-test-themes-{test}.json"));
+        std::fs::write(&path, contents).unwrap();
+        path.to_str().unwrap().to_owned()
+    }
+
+    #[test]
+    fn built_in_themes_load_by_name() {
+        for theme in Themes::iter() {
+            let loaded = Theme::load(&theme.to_string(), Some("/nonexistent")).unwrap();
+            assert_eq!(loaded, theme.build());
+        }
+    }
+
+    #[test]
+    fn custom_themes_override_their_base() {
+        let path = themes_file(
+            "custom",
+            r##"{ "mine": { "base": "light", "text": "#102030", "bars": ["red", "light-blue", "42"] } }"##,
+        );
+        let theme = Theme::load("mine", Some(&path)).unwrap();
+        assert_eq!(theme.text, Color::Rgb(0x10, 0x20, 0x30));
+        assert_eq!(theme.background, Themes::Light.build().background);
+        assert_eq!(
+            theme.bars,
+            [Color::Red, Color::LightBlue, Color::Indexed(42)]
+        );
+    }
+
+    #[test]
+    fn bad_themes_are_rejected() {
+        let path = themes_file("bad", r#"{ "bad": { "text": "chartreuse" } }"#);
+        assert!(Theme::load("bad", Some(&path)).is_err());
+        assert!(Theme::load("missing", Some(&path)).is_err());
+        let err = Theme::load("mine", Some("/nonexistent/themes.json")).unwrap_err();
+        assert!(format!("{err:#}").contains("/nonexistent/themes.json"));
+        assert!(parse_color("#12345").is_err());
+    }
+
+    #[test]
+    fn no_color_overrides_the_theme() {
+        let mut opts = Opts::from_iter(["antsy", "--theme", "solarized"]);
+        assert_eq!(
+            Theme::from_opts(&opts, false).unwrap(),
+            Themes::Solarized.build()
+        );
+        assert_eq!(
+            Theme::from_opts(&opts, true).unwrap(),
+            Themes::Monochrome.build()
+        );
+
+        opts.accessible = true;
+        let theme = Theme::from_opts(&opts, false).unwrap();
+        assert!(theme.markers);
+        assert_eq!(theme.background, Themes::Solarized.build().background);
+    }
+}
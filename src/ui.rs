@@ -1,31 +1,42 @@
 use strum::*;
+use tui::layout::Rect;
+
+use crate::research::ResearchNode;
 
 pub(crate) mod normal;
 pub(crate) mod prestige;
-
-use crate::prestige::PrestigeUpgrade;
+pub(crate) mod profiles;
 
 #[derive(EnumString, Copy, Clone)]
 #[strum(serialize_all = "kebab-case")]
 pub(crate) enum UiStates {
     Normal,
     Prestige,
+    Research,
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug)]
 pub(crate) struct Ui {
     pub(crate) state: UiState,
+    /// Only exists with --dev
+    pub(crate) console: Option<Console>,
+    /// The terminal size at the last draw, empty without a terminal
+    pub(crate) size: Rect,
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug)]
 pub(crate) enum UiState {
     Normal(Normal),
     Prestige(Prestige),
+    Research(Research),
+    Share(Share),
 }
 
 #[derive(Copy, Clone, Debug)]
 pub(crate) struct Normal {
     pub(crate) highlight: normal::Highlight,
+    /// How many of the newest events the log is scrolled back past
+    pub(crate) log_scroll: usize,
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -33,24 +44,66 @@ pub(crate) struct Prestige {
     pub(crate) highlight: prestige::Highlight,
 }
 
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct Research {
+    pub(crate) highlight: ResearchNode,
+}
+
+/// Exporting the game as a save string, and importing one over it.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Share {
+    /// The pasted save string
+    pub(crate) input: String,
+    /// Whether replacing the game with `input` waits for a y/n answer
+    pub(crate) confirm_import: bool,
+    pub(crate) exported: Option<String>,
+    /// Where the exported string was also written, to copy it from
+    pub(crate) export_file: Option<String>,
+    pub(crate) message: Option<String>,
+}
+
+/// The developer console, shown over the current screen while open.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Console {
+    pub(crate) open: bool,
+    pub(crate) input: String,
+    /// Result of the last command
+    pub(crate) output: String,
+}
+
 impl Ui {
-    pub(crate) fn new(active: Option<UiStates>) -> Self {
+    pub(crate) fn new(active: Option<UiStates>, dev: bool) -> Self {
         Self {
             state: match active {
                 None | Some(UiStates::Normal) => UiState::Normal(Normal::new()),
                 Some(UiStates::Prestige) => UiState::Prestige(Prestige::new()),
+                Some(UiStates::Research) => UiState::Research(Research::new()),
             },
+            console: dev.then(Console::default),
+            size: Rect::default(),
         }
     }
 
+    #[allow(clippy::wrong_self_convention)]
     pub(crate) fn to_normal(&mut self) {
         self.state = UiState::Normal(Normal::new());
     }
 
+    #[allow(clippy::wrong_self_convention)]
     pub(crate) fn to_prestige(&mut self) {
         self.state = UiState::Prestige(Prestige::new());
     }
 
+    #[allow(clippy::wrong_self_convention)]
+    pub(crate) fn to_research(&mut self) {
+        self.state = UiState::Research(Research::new());
+    }
+
+    #[allow(clippy::wrong_self_convention)]
+    pub(crate) fn to_share(&mut self) {
+        self.state = UiState::Share(Share::default());
+    }
+
     // pub(crate) fn tag(&self) -> UiStates {
     //     match self.state {
     //         UiState::Normal { .. } => UiStates::Normal,
@@ -64,19 +117,13 @@ impl Ui {
             _ => None,
         }
     }
-
-    pub(crate) fn highlighted_prestige_upgrade(&self) -> Option<PrestigeUpgrade> {
-        match self.state {
-            UiState::Prestige(u) => u.highlight.upgrade(),
-            _ => None,
-        }
-    }
 }
 
 impl Normal {
     pub(crate) fn new() -> Self {
         Self {
             highlight: normal::Highlight::new(),
+            log_scroll: 0,
         }
     }
 }
@@ -88,3 +135,11 @@ impl Prestige {
         }
     }
 }
+
+impl Research {
+    pub(crate) fn new() -> Self {
+        Self {
+            highlight: ResearchNode::iter().next().unwrap(),
+        }
+    }
+}
@@ -1,5 +1,9 @@
+use crate::strategy::Purchase;
 use crate::upgrade::{GlobalUpgrade, Upgrade};
 
+/// How many events the log pane shows at once.
+pub(crate) const LOG_LINES: usize = 4;
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub(crate) enum Highlight {
     None,
@@ -11,4 +15,12 @@ impl Highlight {
     pub(crate) fn new() -> Self {
         Highlight::None
     }
+
+    pub(crate) fn purchase(self) -> Option<Purchase> {
+        match self {
+            Highlight::None => None,
+            Highlight::Bar { upgrade, row } => Some(Purchase::Bar { upgrade, row }),
+            Highlight::Global { upgrade } => Some(Purchase::Global { upgrade }),
+        }
+    }
 }
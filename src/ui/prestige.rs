@@ -1,5 +1,6 @@
 use strum::*;
 
+use crate::challenge::Challenge;
 use crate::prestige::PrestigeUpgrade;
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
@@ -7,6 +8,7 @@ pub(crate) enum Highlight {
     None,
     PrestigeButton,
     Upgrade(usize),
+    Challenge(usize),
 }
 
 impl Highlight {
@@ -21,4 +23,12 @@ impl Highlight {
             None
         }
     }
+
+    pub(crate) fn challenge(self) -> Option<Challenge> {
+        if let Highlight::Challenge(i) = self {
+            Challenge::iter().nth(i)
+        } else {
+            None
+        }
+    }
 }
@@ -0,0 +1,23 @@
+/// State of the profile picker shown before a game is loaded.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Picker {
+    pub(crate) highlight: usize,
+    pub(crate) prompt: Option<Prompt>,
+    /// Result of the last action, shown until the next key press
+    pub(crate) message: Option<String>,
+}
+
+/// A question the picker is waiting on an answer to.
+#[derive(Clone, Debug)]
+pub(crate) enum Prompt {
+    Create { name: String },
+    Rename { name: String },
+    Duplicate { name: String },
+    ConfirmDelete,
+}
+
+impl Picker {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+}
@@ -1,3 +1,4 @@
+use crate::balance;
 use crate::Float;
 use strum::*;
 
@@ -8,6 +9,8 @@ pub(crate) enum Upgrade {
     Double,
     Triple,
     Quadruple,
+    /// Unlocked by research
+    Quintuple,
 }
 
 impl Upgrade {
@@ -18,49 +21,29 @@ impl Upgrade {
             Gain => Double,
             Double => Triple,
             Triple => Quadruple,
-            Quadruple => Speed,
+            Quadruple => Quintuple,
+            Quintuple => Speed,
         }
     }
     pub(crate) fn prev(self) -> Upgrade {
         use Upgrade::*;
         match self {
-            Speed => Quadruple,
+            Speed => Quintuple,
             Gain => Speed,
             Double => Gain,
             Triple => Double,
             Quadruple => Triple,
+            Quintuple => Quadruple,
         }
     }
     pub(crate) fn base_cost(self) -> Float {
-        use Upgrade::*;
-        match self {
-            Speed => 125.,
-            Gain => 3.,
-            Double => 200.,
-            Triple => 5_000.,
-            Quadruple => 100_000.,
-        }
-        .into()
+        balance::get().upgrades.get(self).base_cost.into()
     }
     pub(crate) fn cost_target(self) -> i64 {
-        use Upgrade::*;
-        match self {
-            Speed => 0,
-            Gain => 0,
-            Double => 1,
-            Triple => 4,
-            Quadruple => 7,
-        }
+        balance::get().upgrades.get(self).cost_target
     }
     pub(crate) fn scaling(self) -> f64 {
-        use Upgrade::*;
-        match self {
-            Speed => 5.,
-            Gain => 2.,
-            Double => 100.,
-            Triple => 1_000.,
-            Quadruple => 10_000.,
-        }
+        balance::get().upgrades.get(self).scaling
     }
     pub(crate) fn cost(self, level: usize) -> Float {
         self.base_cost() * self.scaling().powf(level as f64)
@@ -68,7 +51,7 @@ impl Upgrade {
 
     pub const fn upgrade_preference_order() -> [Self; Self::COUNT] {
         use Upgrade::*;
-        [Quadruple, Triple, Double, Gain, Speed]
+        [Quintuple, Quadruple, Triple, Double, Gain, Speed]
     }
 }
 
@@ -79,6 +62,8 @@ pub(crate) enum GlobalUpgrade {
     ProgressBars,
     Gain,
     ExpGain,
+    /// Unlocked by research
+    ResearchGain,
 }
 
 impl GlobalUpgrade {
@@ -89,40 +74,26 @@ impl GlobalUpgrade {
             ExpBoost => ProgressBars,
             ProgressBars => Gain,
             Gain => ExpGain,
-            ExpGain => Speed,
+            ExpGain => ResearchGain,
+            ResearchGain => Speed,
         }
     }
     pub(crate) fn prev(self) -> Self {
         use GlobalUpgrade::*;
         match self {
-            Speed => ExpGain,
+            Speed => ResearchGain,
             ExpBoost => Speed,
             ProgressBars => ExpBoost,
             Gain => ProgressBars,
             ExpGain => Gain,
+            ResearchGain => ExpGain,
         }
     }
     fn base_cost(self) -> Float {
-        use GlobalUpgrade::*;
-        match self {
-            Speed => 300.,
-            ExpBoost => 30.,
-            ProgressBars => 22.,
-            Gain => 120.,
-            ExpGain => 10_000.,
-        }
-        .into()
+        balance::get().global_upgrades.get(self).base_cost.into()
     }
     fn scaling(self) -> Float {
-        use GlobalUpgrade::*;
-        match self {
-            Speed => 3.,
-            ExpBoost => 1.5,
-            ProgressBars => 3.5,
-            Gain => 3.,
-            ExpGain => 8.,
-        }
-        .into()
+        balance::get().global_upgrades.get(self).scaling.into()
     }
     pub(crate) fn cost(self, level: usize) -> Float {
         self.base_cost() * self.scaling().powf(level as f64)
@@ -130,6 +101,45 @@ impl GlobalUpgrade {
 
     pub const fn upgrade_preference_order() -> [Self; Self::COUNT] {
         use GlobalUpgrade::*;
-        [ProgressBars, Gain, Speed, ExpGain, ExpBoost]
+        [ProgressBars, Gain, Speed, ExpGain, ResearchGain, ExpBoost]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn upgrade_cost_scales_with_level() {
+        assert_eq!(Upgrade::Gain.cost(0), Float(3.));
+        assert_eq!(Upgrade::Gain.cost(3), Float(24.));
+        assert_eq!(Upgrade::Double.cost(1), Float(20_000.));
+    }
+
+    #[test]
+    fn global_upgrade_cost_scales_with_level() {
+        assert_eq!(GlobalUpgrade::Speed.cost(0), Float(300.));
+        assert_eq!(GlobalUpgrade::Speed.cost(2), Float(2_700.));
+        assert_eq!(GlobalUpgrade::ExpBoost.cost(1), Float(45.));
+    }
+
+    #[test]
+    fn next_and_prev_are_inverses() {
+        for upgrade in Upgrade::iter() {
+            assert_eq!(upgrade.next().prev(), upgrade);
+        }
+        for upgrade in GlobalUpgrade::iter() {
+            assert_eq!(upgrade.next().prev(), upgrade);
+        }
+    }
+
+    #[test]
+    fn preference_orders_contain_every_upgrade() {
+        let mut order = Upgrade::upgrade_preference_order();
+        order.sort();
+        assert!(order.into_iter().eq(Upgrade::iter()));
+        let mut order = GlobalUpgrade::upgrade_preference_order();
+        order.sort();
+        assert!(order.into_iter().eq(GlobalUpgrade::iter()));
     }
 }